@@ -1,4 +1,7 @@
-use crate::error::Result;
+use std::sync::Arc;
+
+use crate::color::{Rgba, parse_color};
+use crate::error::{LignumError, Result};
 
 /// Represents a color, gradient, or pattern that can be used for fill/stroke.
 #[derive(Clone, Debug, PartialEq)]
@@ -30,6 +33,11 @@ pub enum GradientKind {
         y1: f64,
         r1: f64,
     },
+    Conic {
+        start_angle: f64,
+        x: f64,
+        y: f64,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -39,12 +47,91 @@ pub struct CanvasGradient {
 }
 
 impl CanvasGradient {
-    /// Mirrors CanvasGradient.addColorStop.
-    pub fn add_color_stop(&mut self, offset: f64, color: impl Into<String>) {
-        self.stops.push(GradientStop {
-            offset,
-            color: color.into(),
-        });
+    /// Adds a color stop, rejecting offsets outside [0, 1] with `IndexSize` and
+    /// unparsable colors with `Syntax`. Mirrors CanvasGradient.addColorStop.
+    pub fn add_color_stop(&mut self, offset: f64, color: impl Into<String>) -> Result<()> {
+        if !offset.is_finite() {
+            return Err(LignumError::Type(format!("color stop offset {offset} is not finite")));
+        }
+        if !(0.0..=1.0).contains(&offset) {
+            return Err(LignumError::IndexSize(format!(
+                "color stop offset {offset} is outside the range [0, 1]"
+            )));
+        }
+        let color = color.into();
+        if parse_color(&color).is_none() {
+            return Err(LignumError::Syntax(format!("cannot parse color stop {color:?}")));
+        }
+        self.stops.push(GradientStop { offset, color });
+        Ok(())
+    }
+
+    /// Returns the parsed stops ordered by offset, keeping insertion order for equal offsets.
+    /// Unparsable colors fall back to opaque black.
+    pub fn sorted_stops(&self) -> Vec<(f64, Rgba)> {
+        let mut stops: Vec<(f64, Rgba)> = self
+            .stops
+            .iter()
+            .map(|stop| {
+                let color = parse_color(&stop.color).unwrap_or(Rgba::BLACK);
+                (stop.offset.clamp(0.0, 1.0), color)
+            })
+            .collect();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        stops
+    }
+
+    /// Parses and sorts the stops once, for backends that sample many offsets per paint.
+    pub fn prepare(&self) -> PreparedStops {
+        PreparedStops {
+            stops: self.sorted_stops(),
+        }
+    }
+
+    /// Evaluates the gradient color at `offset`. Prefer [`CanvasGradient::prepare`] when
+    /// sampling more than once.
+    pub fn color_at(&self, offset: f64) -> Rgba {
+        self.prepare().color_at(offset)
+    }
+}
+
+/// Gradient stops already parsed and ordered by offset. See [`CanvasGradient::prepare`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreparedStops {
+    stops: Vec<(f64, Rgba)>,
+}
+
+impl PreparedStops {
+    pub fn stops(&self) -> &[(f64, Rgba)] {
+        &self.stops
+    }
+
+    /// Evaluates the gradient color at `offset`, interpolating in premultiplied space and
+    /// padding with the first/last stop outside the stop range, as Canvas does.
+    pub fn color_at(&self, offset: f64) -> Rgba {
+        let stops = &self.stops;
+        let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+            return Rgba::TRANSPARENT;
+        };
+        if offset < first.0 {
+            return first.1;
+        }
+        if offset >= last.0 {
+            return last.1;
+        }
+        for pair in stops.windows(2) {
+            let ((o0, c0), (o1, c1)) = (pair[0], pair[1]);
+            if offset < o1 {
+                let t = (offset - o0) / (o1 - o0);
+                let a = c0.a + (c1.a - c0.a) * t;
+                if a <= 0.0 {
+                    return Rgba::TRANSPARENT;
+                }
+                let channel = |v0: f64, v1: f64| (v0 * c0.a + (v1 * c1.a - v0 * c0.a) * t) / a;
+                return Rgba::new(channel(c0.r, c1.r), channel(c0.g, c1.g), channel(c0.b, c1.b), a);
+            }
+        }
+        last.1
     }
 }
 
@@ -58,9 +145,195 @@ pub enum PatternRepetition {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct CanvasPattern {
+    /// Straight-alpha RGBA copy of the source image, shared between clones of the pattern.
+    pub image: Arc<ImageData>,
     pub repetition: PatternRepetition,
-    /// Optional 2D transform expressed as an SVG/Canvas DOMMatrix (a, b, c, d, e, f).
-    pub transform: Option<[f64; 6]>,
+    /// Optional transform from pattern space to the user space at fill time.
+    pub transform: Option<Matrix2D>,
+}
+
+impl CanvasPattern {
+    /// Snapshots the pixels of `image` into a new pattern. Mirrors createPattern().
+    pub fn new(image: &dyn CanvasImageSource, repetition: PatternRepetition) -> Result<Self> {
+        let width = image.width();
+        let height = image.height();
+        let data = image
+            .data_rgba()
+            .ok_or_else(|| LignumError::Type("CanvasImageSource missing RGBA data".into()))?;
+
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|v| v.checked_mul(4));
+        if expected != Some(data.len()) {
+            return Err(LignumError::IndexSize(format!(
+                "RGBA buffer holds {} bytes, expected width*height*4 for {width}x{height}",
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            image: Arc::new(ImageData {
+                width,
+                height,
+                data: data.to_vec(),
+            }),
+            repetition,
+            transform: None,
+        })
+    }
+
+    /// Sets the pattern transform; matrices with non-finite components are ignored.
+    /// Mirrors CanvasPattern.setTransform().
+    pub fn set_transform(&mut self, transform: Matrix2D) {
+        if transform.is_finite() {
+            self.transform = Some(transform);
+        }
+    }
+}
+
+/// A 2D affine transform laid out like a DOMMatrix: `[a c e; b d f; 0 0 1]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix2D {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Matrix2D {
+    pub const IDENTITY: Matrix2D = Matrix2D::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    /// Builds a matrix from `[a, b, c, d, e, f]`.
+    pub const fn from_array(m: [f64; 6]) -> Self {
+        Self::new(m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+
+    /// Returns the components as `[a, b, c, d, e, f]`.
+    pub const fn to_array(&self) -> [f64; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.to_array().iter().all(|v| v.is_finite())
+    }
+
+    /// Returns `self · other`, the transform that applies `other` first. Mirrors DOMMatrix.multiply().
+    pub fn multiply(&self, other: &Matrix2D) -> Matrix2D {
+        let [a, b, c, d, e, f] = self.to_array();
+        let [na, nb, nc, nd, ne, nf] = other.to_array();
+        Matrix2D::new(
+            a * na + c * nb,
+            b * na + d * nb,
+            a * nc + c * nd,
+            b * nc + d * nd,
+            a * ne + c * nf + e,
+            b * ne + d * nf + f,
+        )
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// Returns the inverse, or `None` when the matrix is singular or not finite. Mirrors DOMMatrix.inverse().
+    pub fn invert(&self) -> Option<Matrix2D> {
+        let [a, b, c, d, e, f] = self.to_array();
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        Some(Matrix2D::new(
+            d / det,
+            -b / det,
+            -c / det,
+            a / det,
+            (c * f - d * e) / det,
+            (b * e - a * f) / det,
+        ))
+    }
+
+    /// Maps a point, including the translation. Mirrors DOMMatrix.transformPoint().
+    pub fn transform_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// Maps a direction or distance, ignoring the translation.
+    pub fn transform_vector(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+
+    /// Splits the matrix into translate · rotate · skew-x · scale. A reflection shows up as
+    /// a negative `scale_y`.
+    pub fn decompose(&self) -> Decomposition2D {
+        let scale_x = self.a.hypot(self.b);
+        let (cos, sin) = if scale_x > 0.0 {
+            (self.a / scale_x, self.b / scale_x)
+        } else {
+            (1.0, 0.0)
+        };
+        let scale_y = self.d * cos - self.c * sin;
+        let sheared = self.c * cos + self.d * sin;
+        Decomposition2D {
+            translate_x: self.e,
+            translate_y: self.f,
+            rotation: sin.atan2(cos),
+            skew_x: if scale_y != 0.0 { sheared / scale_y } else { 0.0 },
+            scale_x,
+            scale_y,
+        }
+    }
+}
+
+/// The parts of a [`Matrix2D`] as returned by [`Matrix2D::decompose`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decomposition2D {
+    pub translate_x: f64,
+    pub translate_y: f64,
+    /// Rotation in radians.
+    pub rotation: f64,
+    /// Horizontal shear factor, the tangent of the skew angle.
+    pub skew_x: f64,
+    pub scale_x: f64,
+    pub scale_y: f64,
+}
+
+impl Decomposition2D {
+    /// Rebuilds the matrix the parts came from.
+    pub fn recompose(&self) -> Matrix2D {
+        let (sin, cos) = self.rotation.sin_cos();
+        Matrix2D::new(1.0, 0.0, 0.0, 1.0, self.translate_x, self.translate_y)
+            .multiply(&Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0))
+            .multiply(&Matrix2D::new(1.0, 0.0, self.skew_x, 1.0, 0.0, 0.0))
+            .multiply(&Matrix2D::new(self.scale_x, 0.0, 0.0, self.scale_y, 0.0, 0.0))
+    }
+}
+
+impl Default for Matrix2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<[f64; 6]> for Matrix2D {
+    fn from(m: [f64; 6]) -> Self {
+        Self::from_array(m)
+    }
+}
+
+impl From<Matrix2D> for [f64; 6] {
+    fn from(m: Matrix2D) -> Self {
+        m.to_array()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -171,12 +444,21 @@ pub enum ImageSmoothingQuality {
     High,
 }
 
+pub trait CanvasSize {
+    /// Returns the width of the drawing surface. Mirrors HTMLCanvasElement.width.
+    fn width(&self) -> Result<f64>;
+    /// Returns the height of the drawing surface. Mirrors HTMLCanvasElement.height.
+    fn height(&self) -> Result<f64>;
+    /// Resizes the surface, clearing it and resetting all context state, the state stack and the current path. Mirrors assigning HTMLCanvasElement.width/height.
+    fn set_size(&mut self, width: f64, height: f64) -> Result<()>;
+}
+
 pub trait CanvasState {
     /// Saves all current drawing state attributes onto a stack (transform, styles, clipping, etc.). Mirrors CanvasRenderingContext2D.save().
     fn save(&mut self) -> Result<()>;
     /// Pops the last saved state from the stack and restores it. Mirrors CanvasRenderingContext2D.restore().
     fn restore(&mut self) -> Result<()>;
-    /// Resets the state to defaults (as if a new context) without touching the stack. Mirrors CanvasRenderingContext2D.reset().
+    /// Resets the state to defaults (as if a new context), emptying the state stack and the current path. Mirrors CanvasRenderingContext2D.reset().
     fn reset(&mut self) -> Result<()>;
 
     /// Sets the global alpha multiplier applied to all drawing ops. Mirrors globalAlpha.
@@ -213,6 +495,10 @@ pub trait CanvasTransforms {
     fn set_transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()>;
     /// Resets the transform to the identity matrix. Mirrors resetTransform().
     fn reset_transform(&mut self) -> Result<()>;
+    /// Returns the current transform. Mirrors getTransform().
+    fn get_transform(&self) -> Result<Matrix2D>;
+    /// Replaces the current transform with `matrix`. Mirrors setTransform(DOMMatrix2DInit).
+    fn set_transform_matrix(&mut self, matrix: &Matrix2D) -> Result<()>;
 }
 
 pub trait CanvasCompositing {
@@ -293,6 +579,9 @@ pub trait CanvasFillStrokeStyles {
         r1: f64,
     ) -> Result<CanvasGradient>;
 
+    /// Creates a conic gradient sweeping clockwise around (x, y) from `start_angle` radians. Mirrors createConicGradient().
+    fn create_conic_gradient(&mut self, start_angle: f64, x: f64, y: f64) -> Result<CanvasGradient>;
+
     /// Creates a pattern from an image source with repetition behavior. Mirrors createPattern().
     fn create_pattern(
         &mut self,
@@ -328,6 +617,7 @@ pub trait CanvasPaths {
     /// Adds an arc that smoothly connects a line to another line. Mirrors arcTo().
     fn arc_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, radius: f64) -> Result<()>;
     /// Adds a rotated ellipse arc segment. Mirrors ellipse().
+    #[allow(clippy::too_many_arguments)]
     fn ellipse(
         &mut self,
         x: f64,
@@ -393,6 +683,7 @@ pub trait CanvasImageData {
     /// Paints the provided ImageData at (dx, dy). Mirrors putImageData().
     fn put_image_data(&mut self, data: &ImageData, dx: f64, dy: f64) -> Result<()>;
     /// Paints a dirty rect subset of ImageData at (dx, dy). Mirrors putImageData() with dirty rect.
+    #[allow(clippy::too_many_arguments)]
     fn put_image_data_dirty(
         &mut self,
         data: &ImageData,
@@ -418,6 +709,7 @@ pub trait CanvasDrawImage {
         dh: f64,
     ) -> Result<()>;
     /// Draws a source sub-rectangle into a destination rectangle. Mirrors drawImage(image, sx, sy, sw, sh, dx, dy, dw, dh).
+    #[allow(clippy::too_many_arguments)]
     fn draw_image_subrect(
         &mut self,
         image: &dyn CanvasImageSource,
@@ -434,6 +726,23 @@ pub trait CanvasDrawImage {
 
 pub trait CanvasPathDrawingStyles: CanvasLineStyles + CanvasFillStrokeStyles {}
 
+/// Document structure and metadata for backends that keep a tree of elements, such as SVG.
+/// Not part of the Canvas API; backends without a document accept the calls and ignore them.
+pub trait CanvasDocument {
+    /// Opens a group holding everything drawn until the matching `end_group`, with an optional id, class and extra attributes.
+    fn begin_group(&mut self, id: Option<&str>, class: Option<&str>, attributes: &[(&str, &str)]) -> Result<()>;
+    /// Closes the innermost open group. Does nothing when no group is open.
+    fn end_group(&mut self) -> Result<()>;
+    /// Sets the id of the next drawn element or group.
+    fn set_element_id(&mut self, id: &str) -> Result<()>;
+    /// Sets an accessible title for the next drawn element or group.
+    fn set_title(&mut self, title: &str) -> Result<()>;
+    /// Sets an accessible description for the next drawn element or group.
+    fn set_desc(&mut self, desc: &str) -> Result<()>;
+    /// Sets a `data-{name}` attribute on the next drawn element or group.
+    fn set_data_attribute(&mut self, name: &str, value: &str) -> Result<()>;
+}
+
 pub trait CanvasImageSource {
     fn width(&self) -> u32;
     fn height(&self) -> u32;
@@ -442,6 +751,64 @@ pub trait CanvasImageSource {
     fn data_rgba(&self) -> Option<&[u8]>;
 }
 
+impl ImageData {
+    /// Copies the `width` x `height` region starting at (`x`, `y`). Pixels outside this
+    /// image come back transparent black. Fails with `IndexSize` when the region is too
+    /// large to allocate.
+    pub fn sub_image(&self, x: i64, y: i64, width: u32, height: u32) -> Result<ImageData> {
+        crop_rgba(&self.data, self.width, self.height, x, y, width, height)
+    }
+}
+
+/// Rejects surface sizes that are negative or not finite.
+pub(crate) fn validate_size(width: f64, height: f64) -> Result<()> {
+    if !(width.is_finite() && height.is_finite() && width >= 0.0 && height >= 0.0) {
+        return Err(LignumError::IndexSize(format!("invalid canvas size {width}x{height}")));
+    }
+    Ok(())
+}
+
+/// Byte length of a `width` x `height` RGBA buffer, rejecting sizes that cannot be allocated.
+pub(crate) fn image_data_len(width: u32, height: u32) -> Result<usize> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(4))
+        .filter(|&len| len <= isize::MAX as usize)
+        .ok_or_else(|| LignumError::IndexSize(format!("image data too large: {width}x{height}")))
+}
+
+/// Copies a region out of tightly packed RGBA pixels, see [`ImageData::sub_image`].
+pub(crate) fn crop_rgba(
+    src: &[u8],
+    src_width: u32,
+    src_height: u32,
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+) -> Result<ImageData> {
+    let mut data = vec![0; image_data_len(width, height)?];
+    for row in 0..height as i64 {
+        let src_y = y.saturating_add(row);
+        if src_y < 0 || src_y >= src_height as i64 {
+            continue;
+        }
+        // Clip the row to the source's horizontal extent.
+        let start = x.max(0);
+        let end = x.saturating_add(width as i64).min(src_width as i64);
+        if start >= end {
+            continue;
+        }
+        let from = (src_y as usize * src_width as usize + start as usize) * 4;
+        let to = (row as usize * width as usize + (start - x) as usize) * 4;
+        let len = (end - start) as usize * 4;
+        if let (Some(from), Some(to)) = (src.get(from..from + len), data.get_mut(to..to + len)) {
+            to.copy_from_slice(from);
+        }
+    }
+    Ok(ImageData { width, height, data })
+}
+
 impl CanvasImageSource for ImageData {
     fn width(&self) -> u32 {
         self.width
@@ -457,7 +824,8 @@ impl CanvasImageSource for ImageData {
 }
 
 pub trait CanvasRenderingContext2D:
-    CanvasState
+    CanvasSize
+    + CanvasState
     + CanvasTransforms
     + CanvasCompositing
     + CanvasRectangles
@@ -470,3 +838,108 @@ pub trait CanvasRenderingContext2D:
 }
 
 impl<T> CanvasPathDrawingStyles for T where T: CanvasLineStyles + CanvasFillStrokeStyles {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_eq(actual: Matrix2D, expected: Matrix2D) {
+        for (a, b) in actual.to_array().iter().zip(expected.to_array()) {
+            assert!((a - b).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn multiply_applies_the_argument_first() {
+        let translate = Matrix2D::new(1.0, 0.0, 0.0, 1.0, 10.0, 0.0);
+        let scale = Matrix2D::new(2.0, 0.0, 0.0, 2.0, 0.0, 0.0);
+        assert_eq!(translate.multiply(&scale).transform_point(1.0, 1.0), (12.0, 2.0));
+        assert_eq!(scale.multiply(&translate).transform_point(1.0, 1.0), (22.0, 2.0));
+        assert_eq!(scale.multiply(&translate).transform_vector(1.0, 1.0), (2.0, 2.0));
+    }
+
+    #[test]
+    fn invert_undoes_the_transform() {
+        let m = Matrix2D::new(2.0, 1.0, -1.0, 3.0, 5.0, -7.0);
+        let inv = m.invert().unwrap();
+        assert_matrix_eq(m.multiply(&inv), Matrix2D::IDENTITY);
+        assert_eq!(Matrix2D::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0).invert(), None);
+        assert_eq!(Matrix2D::new(f64::NAN, 0.0, 0.0, 1.0, 0.0, 0.0).invert(), None);
+        // A tiny but non-zero scale is still invertible.
+        let tiny = Matrix2D::new(1e-7, 0.0, 0.0, 1e-7, 0.0, 0.0);
+        assert_matrix_eq(tiny.multiply(&tiny.invert().unwrap()), Matrix2D::IDENTITY);
+    }
+
+    #[test]
+    fn image_data_len_rejects_sizes_that_overflow() {
+        assert_eq!(image_data_len(3, 2).unwrap(), 24);
+        assert_eq!(image_data_len(40_000, 40_000).unwrap(), 6_400_000_000);
+        assert!(matches!(image_data_len(u32::MAX, u32::MAX), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn sub_image_pads_and_rejects_regions_too_large_to_allocate() {
+        let image = ImageData {
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let region = image.sub_image(1, 0, 2, 1).unwrap();
+        assert_eq!(region.data, vec![5, 6, 7, 8, 0, 0, 0, 0]);
+        assert!(image.sub_image(i64::MAX, i64::MAX, 1, 1).unwrap().data.iter().all(|&b| b == 0));
+        assert!(matches!(image.sub_image(0, 0, u32::MAX, u32::MAX), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn pattern_rejects_unusable_sources() {
+        struct Undecoded;
+        impl CanvasImageSource for Undecoded {
+            fn width(&self) -> u32 {
+                1
+            }
+            fn height(&self) -> u32 {
+                1
+            }
+            fn data_rgba(&self) -> Option<&[u8]> {
+                None
+            }
+        }
+
+        let short = ImageData {
+            width: 2,
+            height: 2,
+            data: vec![0; 12],
+        };
+        assert!(matches!(
+            CanvasPattern::new(&Undecoded, PatternRepetition::Repeat),
+            Err(LignumError::Type(_))
+        ));
+        assert!(matches!(
+            CanvasPattern::new(&short, PatternRepetition::Repeat),
+            Err(LignumError::IndexSize(_))
+        ));
+    }
+
+    #[test]
+    fn decompose_recomposes_to_the_original() {
+        let (sin, cos) = 0.5f64.sin_cos();
+        let parts = Matrix2D::new(1.0, 0.0, 0.0, 1.0, 4.0, 5.0)
+            .multiply(&Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0))
+            .multiply(&Matrix2D::new(1.0, 0.0, 0.3, 1.0, 0.0, 0.0))
+            .multiply(&Matrix2D::new(2.0, 0.0, 0.0, 3.0, 0.0, 0.0))
+            .decompose();
+        assert!((parts.rotation - 0.5).abs() < 1e-9);
+        assert!((parts.skew_x - 0.3).abs() < 1e-9);
+        assert!((parts.scale_x - 2.0).abs() < 1e-9);
+        assert!((parts.scale_y - 3.0).abs() < 1e-9);
+        assert_eq!((parts.translate_x, parts.translate_y), (4.0, 5.0));
+
+        for m in [
+            Matrix2D::new(1.0, 0.0, 0.0, -1.0, 0.0, 0.0),
+            Matrix2D::new(0.0, 0.0, 1.0, 2.0, 3.0, 4.0),
+            Matrix2D::new(-2.0, 1.0, 0.5, 0.25, 0.0, 1.0),
+        ] {
+            assert_matrix_eq(m.decompose().recompose(), m);
+        }
+    }
+}
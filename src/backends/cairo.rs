@@ -1,19 +1,41 @@
 //! Cairo backend implementing the CanvasRenderingContext2D-like traits behind
 //! the optional `cairo` crate feature. The implementation favors fidelity where
 //! practical and uses no-ops or TODOs for APIs that Cairo does not support
-//! directly (shadows, image smoothing toggles, patterns, image data upload).
+//! directly (shadows). Image data access needs an image surface target.
+
+use std::any::Any;
+use std::borrow::Cow;
+use std::io::Write;
 
 use cairo::{
     Context, Extend, FillRule as CairoFillRule, Format, ImageSurface, LineCap as CairoLineCap, LineJoin as CairoLineJoin,
-    Operator, SurfacePattern, Filter,
+    MeshCorner, Operator, PdfSurface, PsSurface, Surface, SurfacePattern, SvgSurface, Filter,
 };
 
 use crate::api::*;
+use crate::api::{image_data_len, validate_size};
 use crate::error::{Result, LignumError};
+use crate::flatten;
+use crate::validate;
 
 /// Adapter that translates CanvasRenderingContext2D calls into Cairo operations.
 pub struct CairoCanvas {
     ctx: Context,
+    /// The surface `ctx` was created for. Kept separately because a context in an error
+    /// state reports a nil target.
+    target: Surface,
+    width: f64,
+    height: f64,
+    state: CairoState,
+    /// Whether the target was created around an output stream that `finish` can hand back.
+    has_stream: bool,
+    /// States pushed by `save`, each paired with a `ctx.save()` for the parts Cairo keeps.
+    stack: Vec<CairoState>,
+}
+
+/// Drawing state Cairo has no slot for, kept alongside the context's own.
+#[derive(Clone)]
+struct CairoState {
     fill_style: Paint,
     stroke_style: Paint,
     global_alpha: f64,
@@ -24,17 +46,20 @@ pub struct CairoCanvas {
     shadow_color: String,
     image_smoothing_enabled: bool,
     image_smoothing_quality: ImageSmoothingQuality,
+    line_dash: Vec<f64>,
     line_dash_offset: f64,
+    /// A transform Cairo cannot hold because it is not invertible. While set, drawing and
+    /// path calls do nothing, as everything they produce would collapse to nothing.
+    singular_transform: Option<Matrix2D>,
     font: String,
     text_align: TextAlign,
     text_baseline: TextBaseline,
     direction: Direction,
 }
 
-impl CairoCanvas {
-    pub fn new(ctx: Context) -> Self {
+impl Default for CairoState {
+    fn default() -> Self {
         Self {
-            ctx,
             fill_style: Paint::Color("#000000".into()),
             stroke_style: Paint::Color("#000000".into()),
             global_alpha: 1.0,
@@ -45,24 +70,181 @@ impl CairoCanvas {
             shadow_color: "rgba(0,0,0,0)".into(),
             image_smoothing_enabled: true,
             image_smoothing_quality: ImageSmoothingQuality::Medium,
+            line_dash: Vec::new(),
             line_dash_offset: 0.0,
+            singular_transform: None,
             font: "16px Sans".into(),
             text_align: TextAlign::Start,
             text_baseline: TextBaseline::Alphabetic,
             direction: Direction::Inherit,
         }
     }
+}
 
-    fn apply_composite(&self) {
-        self.ctx
-            .set_operator(map_composite(self.composite.clone()));
+impl CairoCanvas {
+    pub fn new(ctx: Context) -> Self {
+        let (width, height) = target_size(&ctx);
+        Self {
+            target: ctx.target(),
+            ctx,
+            width,
+            height,
+            state: CairoState::default(),
+            has_stream: false,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Creates a canvas drawing into a new, transparent ARGB32 image surface.
+    pub fn image(width: u32, height: u32) -> Result<Self> {
+        let (Ok(w), Ok(h)) = (i32::try_from(width), i32::try_from(height)) else {
+            return Err(LignumError::IndexSize(format!("invalid canvas size {width}x{height}")));
+        };
+        let surface = ImageSurface::create(Format::ARgb32, w, h)?;
+        Ok(Self::new(Context::new(&surface)?))
+    }
+
+    /// Creates a single-page PDF canvas of `width` x `height` points written to `writer`.
+    /// Call [`finish`](Self::finish) to complete the document and get the writer back.
+    pub fn pdf<W: Write + 'static>(writer: W, width: f64, height: f64) -> Result<Self> {
+        validate_size(width, height)?;
+        Self::for_stream(&*PdfSurface::for_stream(width, height, writer)?)
+    }
+
+    /// Creates an SVG canvas of `width` x `height` points written to `writer`.
+    pub fn svg<W: Write + 'static>(writer: W, width: f64, height: f64) -> Result<Self> {
+        validate_size(width, height)?;
+        Self::for_stream(&*SvgSurface::for_stream(width, height, writer)?)
+    }
+
+    /// Creates a PostScript canvas of `width` x `height` points written to `writer`.
+    pub fn ps<W: Write + 'static>(writer: W, width: f64, height: f64) -> Result<Self> {
+        validate_size(width, height)?;
+        Self::for_stream(&*PsSurface::for_stream(width, height, writer)?)
+    }
+
+    fn for_stream(surface: &Surface) -> Result<Self> {
+        let mut canvas = Self::new(Context::new(surface)?);
+        canvas.has_stream = true;
+        Ok(canvas)
+    }
+
+    /// The surface this canvas draws to.
+    pub fn surface(&self) -> Surface {
+        self.target.clone()
+    }
+
+    /// Encodes the canvas as PNG. Only image surfaces can be written this way.
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let Ok(image) = ImageSurface::try_from(self.target.clone()) else {
+            return Err(LignumError::NotSupported(
+                "only image surfaces can be written as PNG".into(),
+            ));
+        };
+        image.flush();
+        image.write_to_png(writer)?;
+        Ok(())
+    }
+
+    /// Finishes the surface, flushing any pending output, and returns the writer passed to
+    /// [`pdf`](Self::pdf), [`svg`](Self::svg) or [`ps`](Self::ps). `W` must be that
+    /// writer's type.
+    pub fn finish<W: Any>(self) -> Result<W> {
+        if !self.has_stream {
+            return Err(LignumError::NotSupported(
+                "canvas was not created with an output stream".into(),
+            ));
+        }
+        let stream = self
+            .target
+            .finish_output_stream()
+            .map_err(|err| LignumError::from(std::io::Error::from(err)))?;
+        stream
+            .downcast::<W>()
+            .map(|writer| *writer)
+            .map_err(|_| LignumError::Type("output stream has a different type".into()))
+    }
+
+    /// Whether a path call with these arguments takes effect: they must be finite and the
+    /// transform must be one Cairo can apply.
+    fn accepts(&self, args: &[f64]) -> bool {
+        validate::finite(args) && self.state.singular_transform.is_none()
+    }
+
+    /// Makes `matrix` the current transform. Cairo puts the context into a permanent error
+    /// state for matrices it cannot invert, so those are only remembered. Cairo's own check
+    /// decides, so any matrix it accepts is drawn with.
+    fn apply_transform(&mut self, matrix: Matrix2D) {
+        let [a, b, c, d, e, f] = matrix.to_array();
+        let cairo_matrix = cairo::Matrix::new(a, b, c, d, e, f);
+        if cairo_matrix.try_invert().is_ok() {
+            self.ctx.set_matrix(cairo_matrix);
+            self.state.singular_transform = None;
+        } else {
+            self.state.singular_transform = Some(matrix);
+        }
+    }
+
+    /// Sends the dash list to Cairo, which rejects a list of only zeros that Canvas treats
+    /// as a solid line.
+    fn apply_dash(&self) {
+        if self.state.line_dash.iter().all(|&v| v == 0.0) {
+            self.ctx.set_dash(&[], 0.0);
+        } else {
+            self.ctx.set_dash(&self.state.line_dash, self.state.line_dash_offset);
+        }
+    }
+
+    /// Runs `f` against an empty path and puts the current path back afterwards, so
+    /// rectangle, text and image helpers leave the user's path untouched.
+    fn with_detached_path<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let path = self.ctx.copy_path()?;
+        self.ctx.new_path();
+        let result = f();
+        self.ctx.new_path();
+        self.ctx.append_path(&path);
+        result
+    }
+
+    /// Runs `draw` and combines its result with the canvas the way Canvas composites: the
+    /// drawing is rendered on its own, multiplied by `global_alpha`, then blended with the
+    /// composite operation across the whole clip region. That is what lets `copy`,
+    /// `source-in` and friends clear pixels the drawing did not cover.
+    ///
+    /// `draw` must set its own source and leave the operator alone; the context operator is
+    /// `Over` between calls.
+    fn composited(&self, draw: impl FnOnce() -> Result<()>) -> Result<()> {
+        if self.state.singular_transform.is_some() {
+            return Ok(());
+        }
+        let alpha = self.state.global_alpha;
+        if self.state.composite == CompositeOperation::SourceOver && alpha >= 1.0 {
+            return draw();
+        }
+        self.ctx.push_group();
+        let drawn = draw();
+        let mut source = self.ctx.pop_group()?;
+        drawn?;
+        if alpha < 1.0 {
+            // Painting with a mask would blend Cairo's `SOURCE` with the destination instead
+            // of replacing it, so bake the alpha into the source first.
+            self.ctx.push_group();
+            self.ctx.set_source(&source)?;
+            self.ctx.paint_with_alpha(alpha)?;
+            source = self.ctx.pop_group()?;
+        }
+        self.ctx.save()?;
+        self.ctx.set_source(&source)?;
+        self.ctx.set_operator(map_composite(self.state.composite.clone()));
+        self.ctx.paint()?;
+        self.ctx.restore()?;
+        Ok(())
     }
 
     fn apply_paint(&self, paint: &Paint) -> Result<()> {
         match paint {
             Paint::Color(s) => {
                 let (r, g, b, a) = parse_color(s);
-                let a = a * self.global_alpha;
                 self.ctx.set_source_rgba(r, g, b, a);
             }
             Paint::Gradient(grad) => match &grad.kind {
@@ -70,7 +252,7 @@ impl CairoCanvas {
                     let pattern = cairo::LinearGradient::new(*x0, *y0, *x1, *y1);
                     for stop in &grad.stops {
                         let (r, g, b, a) = parse_color(&stop.color);
-                        pattern.add_color_stop_rgba(stop.offset, r, g, b, a * self.global_alpha);
+                        pattern.add_color_stop_rgba(stop.offset, r, g, b, a);
                     }
                     self.ctx.set_source(&pattern)?;
                 }
@@ -85,117 +267,318 @@ impl CairoCanvas {
                     let pattern = cairo::RadialGradient::new(*x0, *y0, *r0, *x1, *y1, *r1);
                     for stop in &grad.stops {
                         let (r, g, b, a) = parse_color(&stop.color);
-                        pattern.add_color_stop_rgba(stop.offset, r, g, b, a * self.global_alpha);
+                        pattern.add_color_stop_rgba(stop.offset, r, g, b, a);
                     }
                     self.ctx.set_source(&pattern)?;
                 }
+                GradientKind::Conic { start_angle, x, y } => {
+                    let pattern = self.conic_mesh(grad, *start_angle, *x, *y)?;
+                    self.ctx.set_source(&pattern)?;
+                }
             },
-            Paint::Pattern(_p) => {
-                // Proper pattern support requires access to concrete image sources.
-                todo!("Pattern painting is not implemented for Cairo backend yet");
+            Paint::Pattern(pattern) => {
+                let source = self.pattern_source(pattern)?;
+                self.ctx.set_source(&source)?;
             }
         }
 
         Ok(())
     }
 
+    /// Approximates a conic gradient with a mesh of pie-slice patches large enough to
+    /// cover the current clip, each interpolating between the colors at its two edges.
+    fn conic_mesh(&self, grad: &CanvasGradient, start_angle: f64, cx: f64, cy: f64) -> Result<cairo::Mesh> {
+        const SLICES: usize = 128;
+        let tau = std::f64::consts::TAU;
+
+        let (x1, y1, x2, y2) = self.ctx.clip_extents()?;
+        let radius = [(x1, y1), (x2, y1), (x1, y2), (x2, y2)]
+            .iter()
+            .map(|(x, y)| (x - cx).hypot(y - cy))
+            .fold(1.0, f64::max)
+            + 1.0;
+
+        // Slice boundaries: a uniform subdivision plus every stop offset, so hard
+        // transitions between coincident stops stay sharp.
+        let mut offsets: Vec<f64> = (0..=SLICES).map(|i| i as f64 / SLICES as f64).collect();
+        offsets.extend(grad.stops.iter().map(|stop| stop.offset.clamp(0.0, 1.0)));
+        offsets.sort_by(f64::total_cmp);
+        offsets.dedup();
+
+        let stops = grad.prepare();
+        let mesh = cairo::Mesh::new();
+        for pair in offsets.windows(2) {
+            let (t0, t1) = (pair[0], pair[1]);
+            // Evaluate just inside the slice so each side of a hard stop gets its own color.
+            let eps = (t1 - t0) * 1e-6;
+            let c0 = stops.color_at(t0 + eps);
+            let c1 = stops.color_at(t1 - eps);
+            let a0 = start_angle + t0 * tau;
+            let a1 = start_angle + t1 * tau;
+            // Cubic Bezier approximation of the arc between a0 and a1.
+            let k = 4.0 / 3.0 * ((a1 - a0) / 4.0).tan() * radius;
+            let (p0x, p0y) = (cx + radius * a0.cos(), cy + radius * a0.sin());
+            let (p1x, p1y) = (cx + radius * a1.cos(), cy + radius * a1.sin());
+
+            mesh.begin_patch();
+            mesh.move_to(cx, cy);
+            mesh.line_to(p0x, p0y);
+            mesh.curve_to(
+                p0x - k * a0.sin(),
+                p0y + k * a0.cos(),
+                p1x + k * a1.sin(),
+                p1y - k * a1.cos(),
+                p1x,
+                p1y,
+            );
+            mesh.line_to(cx, cy);
+            mesh.set_corner_color_rgba(MeshCorner::MeshCorner0, c0.r, c0.g, c0.b, c0.a);
+            mesh.set_corner_color_rgba(MeshCorner::MeshCorner1, c0.r, c0.g, c0.b, c0.a);
+            mesh.set_corner_color_rgba(MeshCorner::MeshCorner2, c1.r, c1.g, c1.b, c1.a);
+            mesh.set_corner_color_rgba(MeshCorner::MeshCorner3, c1.r, c1.g, c1.b, c1.a);
+            mesh.end_patch();
+        }
+        Ok(mesh)
+    }
+
+    fn pattern_source(&self, pattern: &CanvasPattern) -> Result<cairo::Pattern> {
+        let m = pattern.transform.unwrap_or_default();
+        let pattern_to_user = cairo::Matrix::new(m.a, m.b, m.c, m.d, m.e, m.f);
+        let Ok(user_to_pattern) = pattern_to_user.try_invert() else {
+            // A singular pattern transform paints nothing.
+            return Ok((*cairo::SolidPattern::from_rgba(0.0, 0.0, 0.0, 0.0)).clone());
+        };
+
+        let surface = self.image_surface_from_rgba(pattern.image.as_ref())?;
+        let source = SurfacePattern::create(&surface);
+        source.set_filter(self.image_filter());
+        match pattern.repetition {
+            PatternRepetition::Repeat => {
+                source.set_extend(Extend::Repeat);
+                source.set_matrix(user_to_pattern);
+                Ok((*source).clone())
+            }
+            PatternRepetition::NoRepeat => {
+                source.set_extend(Extend::None);
+                source.set_matrix(user_to_pattern);
+                Ok((*source).clone())
+            }
+            PatternRepetition::RepeatX | PatternRepetition::RepeatY => {
+                // Cairo cannot repeat along a single axis, so render the repeating
+                // pattern into a group restricted to one row (or column) of tiles.
+                source.set_extend(Extend::Repeat);
+                let width = pattern.image.width as f64;
+                let height = pattern.image.height as f64;
+                let path = self.ctx.copy_path()?;
+                self.ctx.new_path();
+                self.ctx.push_group();
+                self.ctx.set_operator(Operator::Over);
+                self.ctx.transform(pattern_to_user);
+                let (x1, y1, x2, y2) = self.ctx.clip_extents()?;
+                if pattern.repetition == PatternRepetition::RepeatX {
+                    self.ctx.rectangle(x1, 0.0, x2 - x1, height);
+                } else {
+                    self.ctx.rectangle(0.0, y1, width, y2 - y1);
+                }
+                self.ctx.set_source(&source)?;
+                self.ctx.fill()?;
+                let band = self.ctx.pop_group()?;
+                self.ctx.append_path(&path);
+                Ok(band)
+            }
+        }
+    }
+
     fn apply_font(&self) {
-        let (size, family) = parse_font(&self.font);
+        let (size, family) = parse_font(&self.state.font);
         self.ctx
-            .select_font_face(family, cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+            .select_font_face(&without_nul(family), cairo::FontSlant::Normal, cairo::FontWeight::Normal);
         self.ctx.set_font_size(size);
     }
 }
 
+/// Size of the surface `ctx` draws to. Image surfaces know theirs; for other surfaces the
+/// initial clip covers the page.
+fn target_size(ctx: &Context) -> (f64, f64) {
+    if let Ok(image) = ImageSurface::try_from(ctx.target()) {
+        return (image.width() as f64, image.height() as f64);
+    }
+    match ctx.clip_extents() {
+        Ok((x1, y1, x2, y2)) => (x2 - x1, y2 - y1),
+        Err(_) => (0.0, 0.0),
+    }
+}
+
+impl CanvasSize for CairoCanvas {
+    fn width(&self) -> Result<f64> {
+        Ok(self.width)
+    }
+
+    fn height(&self) -> Result<f64> {
+        Ok(self.height)
+    }
+
+    /// Replaces an image target with a new, transparent surface of the given size (rounded
+    /// up to whole pixels) and starts over with a fresh context. Other surfaces have a
+    /// fixed page size.
+    fn set_size(&mut self, width: f64, height: f64) -> Result<()> {
+        validate_size(width, height)?;
+        let Ok(image) = ImageSurface::try_from(self.target.clone()) else {
+            return Err(LignumError::NotSupported(
+                "Cairo backend can only resize image surfaces".into(),
+            ));
+        };
+        let (w, h) = (width.ceil(), height.ceil());
+        if w > i32::MAX as f64 || h > i32::MAX as f64 {
+            return Err(LignumError::IndexSize(format!("invalid canvas size {width}x{height}")));
+        }
+        let surface = ImageSurface::create(image.format(), w as i32, h as i32)?;
+        *self = Self::new(Context::new(&surface)?);
+        self.reset()
+    }
+}
+
 impl CanvasState for CairoCanvas {
     fn save(&mut self) -> Result<()> {
         self.ctx.save()?;
+        self.stack.push(self.state.clone());
         Ok(())
     }
 
     fn restore(&mut self) -> Result<()> {
-        self.ctx.restore()?;
+        // Cairo treats an unbalanced restore as an error; Canvas ignores it.
+        if let Some(state) = self.stack.pop() {
+            self.ctx.restore()?;
+            self.state = state;
+        }
         Ok(())
     }
 
+    /// Clears the surface and returns every property, the state stack and the path to
+    /// their defaults.
     fn reset(&mut self) -> Result<()> {
+        for _ in self.stack.drain(..) {
+            self.ctx.restore()?;
+        }
+        self.state = CairoState::default();
+
         self.ctx.identity_matrix();
         self.ctx.reset_clip();
+        self.ctx.new_path();
+        self.ctx.set_operator(Operator::Clear);
+        self.ctx.paint()?;
+        self.ctx.set_operator(Operator::Over);
+        self.ctx.set_line_width(1.0);
+        self.ctx.set_line_cap(CairoLineCap::Butt);
+        self.ctx.set_line_join(CairoLineJoin::Miter);
+        self.ctx.set_miter_limit(10.0);
         self.ctx.set_dash(&[], 0.0);
-        self.line_dash_offset = 0.0;
+        self.apply_font();
         Ok(())
     }
 
     fn set_global_alpha(&mut self, value: f64) -> Result<()> {
-        self.global_alpha = value;
+        if !validate::unit_interval(value) {
+            return Ok(());
+        }
+        self.state.global_alpha = value;
         Ok(())
     }
 
     fn global_alpha(&self) -> Result<f64> {
-        Ok(self.global_alpha)
+        Ok(self.state.global_alpha)
     }
 
     fn set_global_composite_operation(&mut self, op: CompositeOperation) -> Result<()> {
-        self.composite = op;
-        self.apply_composite();
+        self.state.composite = op;
         Ok(())
     }
 
     fn global_composite_operation(&self) -> Result<CompositeOperation> {
-        Ok(self.composite.clone())
+        Ok(self.state.composite.clone())
     }
 
     fn set_image_smoothing_enabled(&mut self, enabled: bool) -> Result<()> {
-        self.image_smoothing_enabled = enabled;
+        self.state.image_smoothing_enabled = enabled;
         Ok(())
     }
 
     fn image_smoothing_enabled(&self) -> Result<bool> {
-        Ok(self.image_smoothing_enabled)
+        Ok(self.state.image_smoothing_enabled)
     }
 
     fn set_image_smoothing_quality(&mut self, quality: ImageSmoothingQuality) -> Result<()> {
-        self.image_smoothing_quality = quality;
+        self.state.image_smoothing_quality = quality;
         Ok(())
     }
 
     fn image_smoothing_quality(&self) -> Result<ImageSmoothingQuality> {
-        Ok(self.image_smoothing_quality.clone())
+        Ok(self.state.image_smoothing_quality.clone())
     }
 }
 
 impl CanvasTransforms for CairoCanvas {
     fn scale(&mut self, x: f64, y: f64) -> Result<()> {
-        self.ctx.scale(x, y);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        let m = self.get_transform()?.multiply(&Matrix2D::new(x, 0.0, 0.0, y, 0.0, 0.0));
+        self.apply_transform(m);
         Ok(())
     }
 
     fn rotate(&mut self, radians: f64) -> Result<()> {
-        self.ctx.rotate(radians);
+        if !validate::finite(&[radians]) {
+            return Ok(());
+        }
+        let (sin, cos) = radians.sin_cos();
+        let m = self.get_transform()?.multiply(&Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0));
+        self.apply_transform(m);
         Ok(())
     }
 
     fn translate(&mut self, x: f64, y: f64) -> Result<()> {
-        self.ctx.translate(x, y);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        let m = self.get_transform()?.multiply(&Matrix2D::new(1.0, 0.0, 0.0, 1.0, x, y));
+        self.apply_transform(m);
         Ok(())
     }
 
     fn transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
-        let matrix = cairo::Matrix::new(a, b, c, d, e, f);
-        self.ctx.transform(matrix);
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
+        let m = self.get_transform()?.multiply(&Matrix2D::new(a, b, c, d, e, f));
+        self.apply_transform(m);
         Ok(())
     }
 
     fn set_transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
-        let matrix = cairo::Matrix::new(a, b, c, d, e, f);
-        self.ctx.set_matrix(matrix);
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
+        self.apply_transform(Matrix2D::new(a, b, c, d, e, f));
         Ok(())
     }
 
     fn reset_transform(&mut self) -> Result<()> {
-        self.ctx.identity_matrix();
+        self.apply_transform(Matrix2D::IDENTITY);
         Ok(())
     }
+
+    fn get_transform(&self) -> Result<Matrix2D> {
+        if let Some(m) = self.state.singular_transform {
+            return Ok(m);
+        }
+        let m = self.ctx.matrix();
+        Ok(Matrix2D::new(m.xx(), m.yx(), m.xy(), m.yy(), m.x0(), m.y0()))
+    }
+
+    fn set_transform_matrix(&mut self, matrix: &Matrix2D) -> Result<()> {
+        self.set_transform(matrix.a, matrix.b, matrix.c, matrix.d, matrix.e, matrix.f)
+    }
 }
 
 impl CairoCanvas {
@@ -204,24 +587,18 @@ impl CairoCanvas {
         let height = image.height();
         let data = image
             .data_rgba()
-            .ok_or_else(|| LignumError::Other(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "CanvasImageSource missing RGBA data",
-            ))))?;
+            .ok_or_else(|| LignumError::Type("CanvasImageSource missing RGBA data".into()))?;
 
         let expected = (width as usize)
             .checked_mul(height as usize)
             .and_then(|v| v.checked_mul(4))
-            .ok_or_else(|| LignumError::Other(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "image dimensions overflow",
-            ))))?;
+            .ok_or_else(|| LignumError::IndexSize(format!("image of {width}x{height} is too large")))?;
 
         if data.len() != expected {
-            return Err(LignumError::Other(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "RGBA buffer length does not match width*height*4",
-            ))));
+            return Err(LignumError::IndexSize(format!(
+                "RGBA buffer holds {} bytes, expected width*height*4 for {width}x{height}",
+                data.len()
+            )));
         }
 
         let mut buf = vec![0u8; expected];
@@ -241,68 +618,95 @@ impl CairoCanvas {
             buf[idx + 3] = a as u8;
         }
 
-        let stride = (width * 4) as i32;
-        let surface = ImageSurface::create_for_data(buf, Format::ARgb32, width as i32, height as i32, stride)?;
+        let (Ok(w), Ok(h)) = (i32::try_from(width), i32::try_from(height)) else {
+            return Err(LignumError::IndexSize(format!("image too large: {width}x{height}")));
+        };
+        let stride = w
+            .checked_mul(4)
+            .ok_or_else(|| LignumError::IndexSize(format!("image too large: {width}x{height}")))?;
+        let surface = ImageSurface::create_for_data(buf, Format::ARgb32, w, h, stride)?;
         Ok(surface)
     }
 
+    /// The target as an image surface, for operations that work on pixels.
+    fn image_target(&self, operation: &str) -> Result<ImageSurface> {
+        ImageSurface::try_from(self.target.clone()).map_err(|_| {
+            LignumError::NotSupported(format!("{operation} needs an image surface target"))
+        })
+    }
+
     fn make_image_pattern(&self, surface: &ImageSurface) -> SurfacePattern {
         let pattern = SurfacePattern::create(surface);
-        let filter = if !self.image_smoothing_enabled {
+        pattern.set_filter(self.image_filter());
+        pattern.set_extend(Extend::None);
+        pattern
+    }
+
+    fn image_filter(&self) -> Filter {
+        if !self.state.image_smoothing_enabled {
             Filter::Nearest
         } else {
-            match self.image_smoothing_quality {
+            match self.state.image_smoothing_quality {
                 ImageSmoothingQuality::Low => Filter::Fast,
                 ImageSmoothingQuality::Medium => Filter::Good,
                 ImageSmoothingQuality::High => Filter::Best,
             }
-        };
-        pattern.set_filter(filter);
-        pattern.set_extend(Extend::None);
-        pattern
+        }
     }
 }
 
 impl CanvasCompositing for CairoCanvas {
     fn set_shadow_offset_x(&mut self, value: f64) -> Result<()> {
-        self.shadow_offset_x = value;
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
+        self.state.shadow_offset_x = value;
         Ok(())
     }
 
     fn shadow_offset_x(&self) -> Result<f64> {
-        Ok(self.shadow_offset_x)
+        Ok(self.state.shadow_offset_x)
     }
 
     fn set_shadow_offset_y(&mut self, value: f64) -> Result<()> {
-        self.shadow_offset_y = value;
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
+        self.state.shadow_offset_y = value;
         Ok(())
     }
 
     fn shadow_offset_y(&self) -> Result<f64> {
-        Ok(self.shadow_offset_y)
+        Ok(self.state.shadow_offset_y)
     }
 
     fn set_shadow_blur(&mut self, value: f64) -> Result<()> {
-        self.shadow_blur = value;
+        if !validate::non_negative(value) {
+            return Ok(());
+        }
+        self.state.shadow_blur = value;
         Ok(())
     }
 
     fn shadow_blur(&self) -> Result<f64> {
-        Ok(self.shadow_blur)
+        Ok(self.state.shadow_blur)
     }
 
     fn set_shadow_color(&mut self, value: String) -> Result<()> {
-        self.shadow_color = value;
+        self.state.shadow_color = value;
         Ok(())
     }
 
     fn shadow_color(&self) -> Result<String> {
-        Ok(self.shadow_color.clone())
+        Ok(self.state.shadow_color.clone())
     }
 }
 
 impl CanvasLineStyles for CairoCanvas {
     fn set_line_width(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.ctx.set_line_width(value);
         Ok(())
     }
@@ -317,7 +721,7 @@ impl CanvasLineStyles for CairoCanvas {
     }
 
     fn line_cap(&self) -> Result<LineCap> {
-        Ok(map_line_cap_back(self.ctx.line_cap()))
+        map_line_cap_back(self.ctx.line_cap())
     }
 
     fn set_line_join(&mut self, value: LineJoin) -> Result<()> {
@@ -326,10 +730,13 @@ impl CanvasLineStyles for CairoCanvas {
     }
 
     fn line_join(&self) -> Result<LineJoin> {
-        Ok(map_line_join_back(self.ctx.line_join()))
+        map_line_join_back(self.ctx.line_join())
     }
 
     fn set_miter_limit(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.ctx.set_miter_limit(value);
         Ok(())
     }
@@ -339,43 +746,49 @@ impl CanvasLineStyles for CairoCanvas {
     }
 
     fn set_line_dash(&mut self, segments: Vec<f64>) -> Result<()> {
-        self.ctx.set_dash(&segments, self.line_dash_offset);
+        if !validate::line_dash(&segments) {
+            return Ok(());
+        }
+        self.state.line_dash = validate::normalize_line_dash(segments);
+        self.apply_dash();
         Ok(())
     }
 
     fn line_dash(&self) -> Result<Vec<f64>> {
-        Ok(self.ctx.dash().0)
+        Ok(self.state.line_dash.clone())
     }
 
     fn set_line_dash_offset(&mut self, value: f64) -> Result<()> {
-        self.line_dash_offset = value;
-        let (segments, _) = self.ctx.dash();
-        self.ctx.set_dash(&segments, self.line_dash_offset);
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
+        self.state.line_dash_offset = value;
+        self.apply_dash();
         Ok(())
     }
 
     fn line_dash_offset(&self) -> Result<f64> {
-        Ok(self.ctx.dash().1)
+        Ok(self.state.line_dash_offset)
     }
 }
 
 impl CanvasFillStrokeStyles for CairoCanvas {
     fn set_fill_style(&mut self, style: Paint) -> Result<()> {
-        self.fill_style = style;
+        self.state.fill_style = style;
         Ok(())
     }
 
     fn fill_style(&self) -> Result<Paint> {
-        Ok(self.fill_style.clone())
+        Ok(self.state.fill_style.clone())
     }
 
     fn set_stroke_style(&mut self, style: Paint) -> Result<()> {
-        self.stroke_style = style;
+        self.state.stroke_style = style;
         Ok(())
     }
 
     fn stroke_style(&self) -> Result<Paint> {
-        Ok(self.stroke_style.clone())
+        Ok(self.state.stroke_style.clone())
     }
 
     fn create_linear_gradient(
@@ -385,6 +798,7 @@ impl CanvasFillStrokeStyles for CairoCanvas {
         x1: f64,
         y1: f64,
     ) -> Result<CanvasGradient> {
+        validate::gradient_args(&[x0, y0, x1, y1])?;
         Ok(CanvasGradient {
             kind: GradientKind::Linear { x0, y0, x1, y1 },
             stops: Vec::new(),
@@ -400,6 +814,8 @@ impl CanvasFillStrokeStyles for CairoCanvas {
         y1: f64,
         r1: f64,
     ) -> Result<CanvasGradient> {
+        validate::gradient_args(&[x0, y0, r0, x1, y1, r1])?;
+        validate::radii(&[r0, r1])?;
         Ok(CanvasGradient {
             kind: GradientKind::Radial {
                 x0,
@@ -413,41 +829,64 @@ impl CanvasFillStrokeStyles for CairoCanvas {
         })
     }
 
+    fn create_conic_gradient(&mut self, start_angle: f64, x: f64, y: f64) -> Result<CanvasGradient> {
+        validate::gradient_args(&[start_angle, x, y])?;
+        Ok(CanvasGradient {
+            kind: GradientKind::Conic { start_angle, x, y },
+            stops: Vec::new(),
+        })
+    }
+
     fn create_pattern(
         &mut self,
-        _image: &dyn CanvasImageSource,
+        image: &dyn CanvasImageSource,
         repetition: PatternRepetition,
     ) -> Result<CanvasPattern> {
-        Ok(CanvasPattern {
-            repetition,
-            transform: None,
-        })
+        CanvasPattern::new(image, repetition)
     }
 }
 
 impl CanvasRectangles for CairoCanvas {
     fn clear_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        self.ctx.save()?;
-        self.ctx.rectangle(x, y, w, h);
-        self.ctx.set_operator(Operator::Clear);
-        self.ctx.fill()?;
-        self.ctx.restore()?;
-        self.apply_composite();
-        Ok(())
+        if !self.accepts(&[x, y, w, h]) {
+            return Ok(());
+        }
+        self.with_detached_path(|| {
+            self.ctx.save()?;
+            self.ctx.rectangle(x, y, w, h);
+            self.ctx.set_operator(Operator::Clear);
+            self.ctx.fill()?;
+            self.ctx.restore()?;
+            Ok(())
+        })
     }
 
     fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        self.ctx.rectangle(x, y, w, h);
-        self.apply_paint(&self.fill_style)?;
-        self.ctx.fill()?;
-        Ok(())
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
+        self.with_detached_path(|| {
+            self.ctx.rectangle(x, y, w, h);
+            self.composited(|| {
+                self.apply_paint(&self.state.fill_style)?;
+                self.ctx.fill_preserve()?;
+                Ok(())
+            })
+        })
     }
 
     fn stroke_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        self.ctx.rectangle(x, y, w, h);
-        self.apply_paint(&self.stroke_style)?;
-        self.ctx.stroke()?;
-        Ok(())
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
+        self.with_detached_path(|| {
+            self.ctx.rectangle(x, y, w, h);
+            self.composited(|| {
+                self.apply_paint(&self.state.stroke_style)?;
+                self.ctx.stroke_preserve()?;
+                Ok(())
+            })
+        })
     }
 }
 
@@ -463,11 +902,17 @@ impl CanvasPaths for CairoCanvas {
     }
 
     fn move_to(&mut self, x: f64, y: f64) -> Result<()> {
+        if !self.accepts(&[x, y]) {
+            return Ok(());
+        }
         self.ctx.move_to(x, y);
         Ok(())
     }
 
     fn line_to(&mut self, x: f64, y: f64) -> Result<()> {
+        if !self.accepts(&[x, y]) {
+            return Ok(());
+        }
         self.ctx.line_to(x, y);
         Ok(())
     }
@@ -481,11 +926,17 @@ impl CanvasPaths for CairoCanvas {
         x: f64,
         y: f64,
     ) -> Result<()> {
+        if !self.accepts(&[cp1x, cp1y, cp2x, cp2y, x, y]) {
+            return Ok(());
+        }
         self.ctx.curve_to(cp1x, cp1y, cp2x, cp2y, x, y);
         Ok(())
     }
 
     fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) -> Result<()> {
+        if !self.accepts(&[cpx, cpy, x, y]) {
+            return Ok(());
+        }
         let (sx, sy) = self.ctx.current_point()?;
         self.ctx.curve_to(
             sx + 2.0 / 3.0 * (cpx - sx),
@@ -507,6 +958,10 @@ impl CanvasPaths for CairoCanvas {
         end_angle: f64,
         ccw: bool,
     ) -> Result<()> {
+        if !self.accepts(&[x, y, radius, start_angle, end_angle]) {
+            return Ok(());
+        }
+        validate::radii(&[radius])?;
         if ccw {
             self.ctx.arc_negative(x, y, radius, start_angle, end_angle);
         } else {
@@ -516,6 +971,10 @@ impl CanvasPaths for CairoCanvas {
     }
 
     fn arc_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, radius: f64) -> Result<()> {
+        if !self.accepts(&[x1, y1, x2, y2, radius]) {
+            return Ok(());
+        }
+        validate::radii(&[radius])?;
         let (x0, y0) = self.ctx.current_point()?;
         let r = radius;
 
@@ -587,6 +1046,26 @@ impl CanvasPaths for CairoCanvas {
         end_angle: f64,
         ccw: bool,
     ) -> Result<()> {
+        if !self.accepts(&[x, y, radius_x, radius_y, rotation, start_angle, end_angle]) {
+            return Ok(());
+        }
+        validate::radii(&[radius_x, radius_y])?;
+        if radius_x == 0.0 || radius_y == 0.0 {
+            // Cairo rejects a zero scale, and the ellipse is a line segment anyway, so
+            // trace it point by point. `line_to` starts the subpath if there is none.
+            const STEPS: usize = 64;
+            let tau = std::f64::consts::TAU;
+            let span = if ccw { start_angle - end_angle } else { end_angle - start_angle };
+            let sweep = if span >= tau { tau } else { span.rem_euclid(tau) };
+            let sweep = if ccw { -sweep } else { sweep };
+            let (sin_r, cos_r) = rotation.sin_cos();
+            for i in 0..=STEPS {
+                let (sin_t, cos_t) = (start_angle + sweep * i as f64 / STEPS as f64).sin_cos();
+                let (ex, ey) = (radius_x * cos_t, radius_y * sin_t);
+                self.ctx.line_to(x + ex * cos_r - ey * sin_r, y + ex * sin_r + ey * cos_r);
+            }
+            return Ok(());
+        }
         self.ctx.save()?;
         self.ctx.translate(x, y);
         self.ctx.rotate(rotation);
@@ -597,140 +1076,166 @@ impl CanvasPaths for CairoCanvas {
     }
 
     fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
+        if !self.accepts(&[x, y, w, h]) {
+            return Ok(());
+        }
         self.ctx.rectangle(x, y, w, h);
         Ok(())
     }
 
     fn round_rect(&mut self, x: f64, y: f64, w: f64, h: f64, radii: &[f64]) -> Result<()> {
-        let r = radii.first().cloned().unwrap_or(0.0);
-        let r = r.min(w / 2.0).min(h / 2.0);
-        let right = x + w;
-        let bottom = y + h;
-
-        self.begin_path()?;
-        self.ctx.arc(
-            x + r,
-            y + r,
-            r,
-            std::f64::consts::PI,
-            1.5 * std::f64::consts::PI,
-        );
-        self.ctx
-            .arc(right - r, y + r, r, 1.5 * std::f64::consts::PI, 0.0);
-        self.ctx
-            .arc(right - r, bottom - r, r, 0.0, 0.5 * std::f64::consts::PI);
-        self.ctx.arc(
-            x + r,
-            bottom - r,
-            r,
-            0.5 * std::f64::consts::PI,
-            std::f64::consts::PI,
-        );
-        self.close_path()?;
+        if !self.accepts(&[x, y, w, h]) {
+            return Ok(());
+        }
+        let Some(radii) = validate::round_rect_radii(radii)? else {
+            return Ok(());
+        };
+        let corners = flatten::round_rect_corners(x, y, w, h, radii);
+
+        // Each arc joins on with a line from the previous one; Cairo turns a zero-radius
+        // arc into a line to its center, which is the square corner.
+        let (start_x, start_y) = corners[3].point(corners[3].to);
+        self.ctx.move_to(start_x, start_y);
+        for corner in &corners {
+            let ((cx, cy), r, start) = (corner.center, corner.radius, corner.start_angle());
+            if corner.clockwise {
+                self.ctx.arc(cx, cy, r, start, start + corner.sweep());
+            } else {
+                self.ctx.arc_negative(cx, cy, r, start, start + corner.sweep());
+            }
+        }
+        self.ctx.close_path();
+        self.ctx.move_to(x, y);
         Ok(())
     }
 
     fn fill(&mut self, fill_rule: FillRule) -> Result<()> {
         self.ctx.set_fill_rule(map_fill_rule(fill_rule));
-        self.apply_paint(&self.fill_style)?;
-        self.ctx.fill()?;
-        Ok(())
+        self.composited(|| {
+            self.apply_paint(&self.state.fill_style)?;
+            self.ctx.fill_preserve()?;
+            Ok(())
+        })
     }
 
     fn stroke(&mut self) -> Result<()> {
-        self.apply_paint(&self.stroke_style)?;
-        self.ctx.stroke()?;
-        Ok(())
+        self.composited(|| {
+            self.apply_paint(&self.state.stroke_style)?;
+            self.ctx.stroke_preserve()?;
+            Ok(())
+        })
     }
 
     fn clip(&mut self, fill_rule: FillRule) -> Result<()> {
         self.ctx.set_fill_rule(map_fill_rule(fill_rule));
-        self.ctx.clip();
+        self.ctx.clip_preserve();
         Ok(())
     }
 
     fn is_point_in_path(&self, x: f64, y: f64, _opts: HitOptions) -> Result<bool> {
+        if !self.accepts(&[x, y]) {
+            return Ok(false);
+        }
         Ok(self.ctx.in_fill(x, y)?)
     }
 
     fn is_point_in_stroke(&self, x: f64, y: f64) -> Result<bool> {
+        if !self.accepts(&[x, y]) {
+            return Ok(false);
+        }
         Ok(self.ctx.in_stroke(x, y)?)
     }
 }
 
 impl CanvasText for CairoCanvas {
     fn set_font(&mut self, value: String) -> Result<()> {
-        self.font = value;
+        self.state.font = value;
         Ok(())
     }
 
     fn font(&self) -> Result<String> {
-        Ok(self.font.clone())
+        Ok(self.state.font.clone())
     }
 
     fn set_text_align(&mut self, value: TextAlign) -> Result<()> {
-        self.text_align = value;
+        self.state.text_align = value;
         Ok(())
     }
 
     fn text_align(&self) -> Result<TextAlign> {
-        Ok(self.text_align.clone())
+        Ok(self.state.text_align.clone())
     }
 
     fn set_text_baseline(&mut self, value: TextBaseline) -> Result<()> {
-        self.text_baseline = value;
+        self.state.text_baseline = value;
         Ok(())
     }
 
     fn text_baseline(&self) -> Result<TextBaseline> {
-        Ok(self.text_baseline.clone())
+        Ok(self.state.text_baseline.clone())
     }
 
     fn set_direction(&mut self, value: Direction) -> Result<()> {
-        self.direction = value;
+        self.state.direction = value;
         Ok(())
     }
 
     fn direction(&self) -> Result<Direction> {
-        Ok(self.direction.clone())
+        Ok(self.state.direction.clone())
     }
 
-    fn fill_text(&mut self, text: &str, x: f64, y: f64, _max_width: Option<f64>) -> Result<()> {
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: Option<f64>) -> Result<()> {
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
+        let text = &*without_nul(text);
         self.apply_font();
-        self.apply_paint(&self.fill_style)?;
         let (tx, ty) = adjust_text_position(
             &self.ctx,
             text,
             x,
             y,
-            self.text_align.clone(),
-            self.text_baseline.clone(),
+            self.state.text_align.clone(),
+            self.state.text_baseline.clone(),
         )?;
-        self.ctx.move_to(tx, ty);
-        self.ctx.show_text(text)?;
-        Ok(())
+        self.with_detached_path(|| {
+            self.composited(|| {
+                self.apply_paint(&self.state.fill_style)?;
+                self.ctx.move_to(tx, ty);
+                self.ctx.show_text(text)?;
+                Ok(())
+            })
+        })
     }
 
-    fn stroke_text(&mut self, text: &str, x: f64, y: f64, _max_width: Option<f64>) -> Result<()> {
+    fn stroke_text(&mut self, text: &str, x: f64, y: f64, max_width: Option<f64>) -> Result<()> {
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
+        let text = &*without_nul(text);
         self.apply_font();
-        self.apply_paint(&self.stroke_style)?;
         let (tx, ty) = adjust_text_position(
             &self.ctx,
             text,
             x,
             y,
-            self.text_align.clone(),
-            self.text_baseline.clone(),
+            self.state.text_align.clone(),
+            self.state.text_baseline.clone(),
         )?;
-        self.ctx.move_to(tx, ty);
-        self.ctx.text_path(text);
-        self.ctx.stroke()?;
-        Ok(())
+        self.with_detached_path(|| {
+            self.composited(|| {
+                self.apply_paint(&self.state.stroke_style)?;
+                self.ctx.move_to(tx, ty);
+                self.ctx.text_path(text);
+                self.ctx.stroke()?;
+                Ok(())
+            })
+        })
     }
 
     fn measure_text(&self, text: &str) -> Result<TextMetrics> {
         self.apply_font();
-        let extents = self.ctx.text_extents(text)?;
+        let extents = self.ctx.text_extents(&without_nul(text))?;
         Ok(TextMetrics {
             width: extents.width(),
         })
@@ -739,26 +1244,79 @@ impl CanvasText for CairoCanvas {
 
 impl CanvasImageData for CairoCanvas {
     fn create_image_data(&mut self, width: u32, height: u32) -> Result<ImageData> {
+        if width == 0 || height == 0 {
+            return Err(LignumError::IndexSize("image data dimensions must be non-zero".into()));
+        }
         Ok(ImageData {
             width,
             height,
-            data: vec![0; (width * height * 4) as usize],
+            data: vec![0; image_data_len(width, height)?],
         })
     }
 
-    fn get_image_data(&self, _sx: u32, _sy: u32, sw: u32, sh: u32) -> Result<ImageData> {
-        // Reading back from Cairo surfaces would require access to the surface.
-        // Provide a zeroed buffer placeholder for now.
-        Ok(ImageData {
-            width: sw,
-            height: sh,
-            data: vec![0; (sw * sh * 4) as usize],
-        })
+    /// Reads back straight-alpha RGBA from an image surface target. Pixels outside the
+    /// surface come back transparent black.
+    fn get_image_data(&self, sx: u32, sy: u32, sw: u32, sh: u32) -> Result<ImageData> {
+        if sw == 0 || sh == 0 {
+            return Err(LignumError::IndexSize("image data dimensions must be non-zero".into()));
+        }
+        let image = self.image_target("get_image_data")?;
+        // RGB24 keeps the same 32-bit layout as ARGB32 with an unused alpha byte.
+        let opaque = match image.format() {
+            Format::ARgb32 => 0,
+            Format::Rgb24 => 0xff00_0000,
+            format => {
+                return Err(LignumError::NotSupported(format!(
+                    "get_image_data cannot read {format:?} image surfaces"
+                )));
+            }
+        };
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let stride = image.stride() as usize;
+        let mut data = vec![0; image_data_len(sw, sh)?];
+        image
+            .with_data(|pixels| {
+                for row in 0..sh as usize {
+                    let y = sy as usize + row;
+                    if y >= height {
+                        break;
+                    }
+                    for col in 0..(width.saturating_sub(sx as usize)).min(sw as usize) {
+                        let at = y * stride + (sx as usize + col) * 4;
+                        let argb = u32::from_ne_bytes(pixels[at..at + 4].try_into().unwrap()) | opaque;
+                        let to = (row * sw as usize + col) * 4;
+                        data[to..to + 4].copy_from_slice(&unpremultiply(argb));
+                    }
+                }
+            })
+            .map_err(|err| LignumError::Backend(Box::new(err)))?;
+        Ok(ImageData { width: sw, height: sh, data })
     }
 
-    fn put_image_data(&mut self, _data: &ImageData, _dx: f64, _dy: f64) -> Result<()> {
-        // Not implemented: would need to write pixels into a surface.
-        todo!("put_image_data is not implemented for Cairo backend yet");
+    /// Replaces the pixels under the image with its contents, ignoring the transform, clip,
+    /// global alpha and compositing as putImageData does.
+    fn put_image_data(&mut self, data: &ImageData, dx: f64, dy: f64) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        self.image_target("put_image_data")?;
+        if data.width == 0 || data.height == 0 {
+            return Ok(());
+        }
+        let source = self.image_surface_from_rgba(data)?;
+        let (dx, dy) = (dx.trunc(), dy.trunc());
+        self.with_detached_path(|| {
+            self.ctx.save()?;
+            self.ctx.identity_matrix();
+            self.ctx.reset_clip();
+            self.ctx.set_operator(Operator::Source);
+            self.ctx.set_source_surface(&source, dx, dy)?;
+            self.ctx.source().set_filter(Filter::Nearest);
+            self.ctx.rectangle(dx, dy, data.width as f64, data.height as f64);
+            self.ctx.fill()?;
+            self.ctx.restore()?;
+            Ok(())
+        })
     }
 
     fn put_image_data_dirty(
@@ -766,28 +1324,81 @@ impl CanvasImageData for CairoCanvas {
         data: &ImageData,
         dx: f64,
         dy: f64,
-        _dirty_x: u32,
-        _dirty_y: u32,
-        _dirty_width: u32,
-        _dirty_height: u32,
+        dirty_x: u32,
+        dirty_y: u32,
+        dirty_width: u32,
+        dirty_height: u32,
     ) -> Result<()> {
-        self.put_image_data(data, dx, dy)
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        // Clamp the dirty rectangle to the image, as putImageData does.
+        let width = dirty_width.min(data.width.saturating_sub(dirty_x));
+        let height = dirty_height.min(data.height.saturating_sub(dirty_y));
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let region = data.sub_image(dirty_x as i64, dirty_y as i64, width, height)?;
+        self.put_image_data(&region, dx.trunc() + dirty_x as f64, dy.trunc() + dirty_y as f64)
     }
 }
 
-impl CanvasDrawImage for CairoCanvas {
-    fn draw_image(&mut self, image: &dyn CanvasImageSource, dx: f64, dy: f64) -> Result<()> {
+/// Converts a premultiplied native-endian ARGB32 pixel to straight RGBA bytes.
+fn unpremultiply(argb: u32) -> [u8; 4] {
+    let a = argb >> 24;
+    if a == 0 {
+        return [0; 4];
+    }
+    let channel = |shift: u32| ((((argb >> shift) & 0xff) * 255 + a / 2) / a).min(255) as u8;
+    [channel(16), channel(8), channel(0), a as u8]
+}
+
+impl CairoCanvas {
+    /// Draws the source rectangle of `image` into the destination rectangle through the
+    /// shared compositing path.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_image(
+        &self,
+        image: &dyn CanvasImageSource,
+        sx: f64,
+        sy: f64,
+        sw: f64,
+        sh: f64,
+        dx: f64,
+        dy: f64,
+        dw: f64,
+        dh: f64,
+    ) -> Result<()> {
+        if sw == 0.0 || sh == 0.0 || dw == 0.0 || dh == 0.0 {
+            return Ok(());
+        }
         let surface = self.image_surface_from_rgba(image)?;
         let pattern = self.make_image_pattern(&surface);
+        self.with_detached_path(|| {
+            self.ctx.rectangle(dx, dy, dw, dh);
+            self.composited(|| {
+                // The source locks in the matrix current when it is set, so map the
+                // source rectangle onto the destination only for that call.
+                let user = self.ctx.matrix();
+                self.ctx.translate(dx, dy);
+                self.ctx.scale(dw / sw, dh / sh);
+                self.ctx.translate(-sx, -sy);
+                self.ctx.set_source(&pattern)?;
+                self.ctx.set_matrix(user);
+                self.ctx.fill_preserve()?;
+                Ok(())
+            })
+        })
+    }
+}
 
-        self.ctx.save()?;
-        self.apply_composite();
-        self.ctx.set_source(&pattern)?;
-        self.ctx.rectangle(dx, dy, image.width() as f64, image.height() as f64);
-        self.ctx.clip();
-        self.ctx.paint_with_alpha(self.global_alpha)?;
-        self.ctx.restore()?;
-        Ok(())
+impl CanvasDrawImage for CairoCanvas {
+    fn draw_image(&mut self, image: &dyn CanvasImageSource, dx: f64, dy: f64) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        let (w, h) = (image.width() as f64, image.height() as f64);
+        self.paint_image(image, 0.0, 0.0, w, h, dx, dy, w, h)
     }
 
     fn draw_image_scaled(
@@ -798,21 +1409,11 @@ impl CanvasDrawImage for CairoCanvas {
         dw: f64,
         dh: f64,
     ) -> Result<()> {
-        let surface = self.image_surface_from_rgba(image)?;
-        let pattern = self.make_image_pattern(&surface);
-        let scale_x = dw / image.width() as f64;
-        let scale_y = dh / image.height() as f64;
-
-        self.ctx.save()?;
-        self.apply_composite();
-        self.ctx.translate(dx, dy);
-        self.ctx.scale(scale_x, scale_y);
-        self.ctx.set_source(&pattern)?;
-        self.ctx.rectangle(0.0, 0.0, image.width() as f64, image.height() as f64);
-        self.ctx.clip();
-        self.ctx.paint_with_alpha(self.global_alpha)?;
-        self.ctx.restore()?;
-        Ok(())
+        if !validate::finite(&[dx, dy, dw, dh]) {
+            return Ok(());
+        }
+        let (w, h) = (image.width() as f64, image.height() as f64);
+        self.paint_image(image, 0.0, 0.0, w, h, dx, dy, dw, dh)
     }
 
     fn draw_image_subrect(
@@ -827,22 +1428,10 @@ impl CanvasDrawImage for CairoCanvas {
         dw: f64,
         dh: f64,
     ) -> Result<()> {
-        let surface = self.image_surface_from_rgba(image)?;
-        let pattern = self.make_image_pattern(&surface);
-        let scale_x = dw / sw;
-        let scale_y = dh / sh;
-
-        self.ctx.save()?;
-        self.apply_composite();
-        self.ctx.rectangle(dx, dy, dw, dh);
-        self.ctx.clip();
-        self.ctx.translate(dx, dy);
-        self.ctx.scale(scale_x, scale_y);
-        self.ctx.translate(-sx, -sy);
-        self.ctx.set_source(&pattern)?;
-        self.ctx.paint_with_alpha(self.global_alpha)?;
-        self.ctx.restore()?;
-        Ok(())
+        if !validate::finite(&[sx, sy, sw, sh, dx, dy, dw, dh]) {
+            return Ok(());
+        }
+        self.paint_image(image, sx, sy, sw, sh, dx, dy, dw, dh)
     }
 }
 
@@ -854,12 +1443,12 @@ fn map_line_cap(cap: LineCap) -> CairoLineCap {
     }
 }
 
-fn map_line_cap_back(cap: CairoLineCap) -> LineCap {
+fn map_line_cap_back(cap: CairoLineCap) -> Result<LineCap> {
     match cap {
-        CairoLineCap::Butt => LineCap::Butt,
-        CairoLineCap::Round => LineCap::Round,
-        CairoLineCap::Square => LineCap::Square,
-        _ => unreachable!("non-exhaustive cairo LineCap")
+        CairoLineCap::Butt => Ok(LineCap::Butt),
+        CairoLineCap::Round => Ok(LineCap::Round),
+        CairoLineCap::Square => Ok(LineCap::Square),
+        other => Err(LignumError::Backend(format!("unknown Cairo line cap {other:?}").into())),
     }
 }
 
@@ -871,12 +1460,12 @@ fn map_line_join(join: LineJoin) -> CairoLineJoin {
     }
 }
 
-fn map_line_join_back(join: CairoLineJoin) -> LineJoin {
+fn map_line_join_back(join: CairoLineJoin) -> Result<LineJoin> {
     match join {
-        CairoLineJoin::Bevel => LineJoin::Bevel,
-        CairoLineJoin::Miter => LineJoin::Miter,
-        CairoLineJoin::Round => LineJoin::Round,
-        _ => unreachable!("non-exhaustive cairo LineJoin")
+        CairoLineJoin::Bevel => Ok(LineJoin::Bevel),
+        CairoLineJoin::Miter => Ok(LineJoin::Miter),
+        CairoLineJoin::Round => Ok(LineJoin::Round),
+        other => Err(LignumError::Backend(format!("unknown Cairo line join {other:?}").into())),
     }
 }
 
@@ -887,6 +1476,9 @@ fn map_fill_rule(rule: FillRule) -> CairoFillRule {
     }
 }
 
+/// Cairo operator with the same Porter-Duff terms or blend function. Cairo's `fill` only
+/// touches pixels under the shape, so the Canvas behavior for unbounded operators comes from
+/// [`CairoCanvas::composited`], which paints the drawing across the whole clip.
 fn map_composite(op: CompositeOperation) -> Operator {
     match op {
         CompositeOperation::SourceOver => Operator::Over,
@@ -919,33 +1511,18 @@ fn map_composite(op: CompositeOperation) -> Operator {
 }
 
 fn parse_color(color: &str) -> (f64, f64, f64, f64) {
-    let c = color.trim();
-    if let Some(hex) = c.strip_prefix('#') {
-        match hex.len() {
-            6 => {
-                let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-                let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-                let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-                return (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, 1.0);
-            }
-            8 => {
-                let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-                let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-                let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-                let a = u8::from_str_radix(&hex[6..8], 16).unwrap_or(255);
-                return (
-                    r as f64 / 255.0,
-                    g as f64 / 255.0,
-                    b as f64 / 255.0,
-                    a as f64 / 255.0,
-                );
-            }
-            _ => {}
-        }
-    }
-
     // Fallback to opaque black if parsing fails.
-    (0.0, 0.0, 0.0, 1.0)
+    let c = crate::color::parse_color(color).unwrap_or(crate::color::Rgba::BLACK);
+    (c.r, c.g, c.b, c.a)
+}
+
+/// Cairo takes text as C strings, which cannot hold NUL, so those characters are dropped.
+fn without_nul(text: &str) -> Cow<'_, str> {
+    if text.contains('\0') {
+        Cow::Owned(text.replace('\0', ""))
+    } else {
+        Cow::Borrowed(text)
+    }
 }
 
 fn parse_font(font: &str) -> (f64, &str) {
@@ -995,3 +1572,852 @@ fn adjust_text_position(
 }
 
 impl CanvasRenderingContext2D for CairoCanvas {}
+
+/// Raster output has no element tree, so structure and metadata are accepted and dropped.
+impl CanvasDocument for CairoCanvas {
+    fn begin_group(&mut self, _id: Option<&str>, _class: Option<&str>, _attributes: &[(&str, &str)]) -> Result<()> {
+        Ok(())
+    }
+
+    fn end_group(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_element_id(&mut self, _id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_title(&mut self, _title: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_desc(&mut self, _desc: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_data_attribute(&mut self, _name: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_canvas(width: i32, height: i32) -> (ImageSurface, CairoCanvas) {
+        let surface = ImageSurface::create(Format::ARgb32, width, height).expect("surface");
+        let ctx = Context::new(&surface).expect("context");
+        (surface, CairoCanvas::new(ctx))
+    }
+
+    fn alpha_at(surface: &mut ImageSurface, x: usize, y: usize) -> u8 {
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().expect("surface data");
+        data[y * stride + x * 4 + 3]
+    }
+
+    /// Returns the premultiplied (r, g, b, a) bytes of a pixel; ARGB32 is stored native-endian.
+    fn rgba_at(surface: &mut ImageSurface, x: usize, y: usize) -> [u8; 4] {
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().expect("surface data");
+        let px = u32::from_ne_bytes(data[y * stride + x * 4..][..4].try_into().unwrap());
+        [(px >> 16) as u8, (px >> 8) as u8, px as u8, (px >> 24) as u8]
+    }
+
+    #[test]
+    fn set_size_replaces_the_image_surface() {
+        let (_surface, mut canvas) = image_canvas(16, 8);
+        assert_eq!((canvas.width().unwrap(), canvas.height().unwrap()), (16.0, 8.0));
+        canvas.translate(3.0, 3.0).unwrap();
+        canvas.set_global_alpha(0.5).unwrap();
+
+        canvas.set_size(4.5, 2.0).unwrap();
+        assert_eq!((canvas.width().unwrap(), canvas.height().unwrap()), (5.0, 2.0));
+        assert_eq!(canvas.global_alpha().unwrap(), 1.0);
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut target = ImageSurface::try_from(canvas.ctx.target()).unwrap();
+        drop(canvas);
+        assert_eq!((target.width(), target.height()), (5, 2));
+        assert_eq!(alpha_at(&mut target, 0, 0), 255);
+        assert_eq!(alpha_at(&mut target, 3, 0), 0);
+    }
+
+    #[test]
+    fn round_rect_uses_each_corner_radius_and_keeps_the_path() {
+        let mut canvas = CairoCanvas::image(20, 10).unwrap();
+        canvas.rect(0.0, 0.0, 2.0, 2.0).unwrap();
+        // The negative width mirrors the rectangle, so the top-left radius lands top right.
+        canvas.round_rect(20.0, 0.0, -10.0, 10.0, &[10.0, 0.0, 0.0, 0.0]).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+
+        assert_eq!(pixel(&canvas, 1, 1), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 11, 1), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 11, 9), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 15, 5), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 19, 0), vec![0, 0, 0, 0]);
+
+        // Radii too big for the sides are scaled down together, leaving a circle.
+        let mut canvas = CairoCanvas::image(10, 10).unwrap();
+        canvas.round_rect(0.0, 0.0, 10.0, 10.0, &[10.0]).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+        assert_eq!(pixel(&canvas, 5, 5), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(&canvas, 5, 1), vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn mirrored_round_rect_cuts_a_non_zero_hole() {
+        let mut canvas = CairoCanvas::image(10, 10).unwrap();
+        canvas.rect(0.0, 0.0, 10.0, 10.0).unwrap();
+        canvas.round_rect(8.0, 2.0, -6.0, 6.0, &[1.0]).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+        assert_eq!(pixel(&canvas, 5, 5), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 255]);
+
+        // Flipping both sides keeps the original winding, so the inner shape adds up.
+        let mut canvas = CairoCanvas::image(10, 10).unwrap();
+        canvas.rect(0.0, 0.0, 10.0, 10.0).unwrap();
+        canvas.round_rect(8.0, 8.0, -6.0, -6.0, &[1.0]).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+        assert_eq!(pixel(&canvas, 5, 5), vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn get_transform_round_trips_through_cairo() {
+        let (_surface, mut canvas) = image_canvas(4, 4);
+        let m = Matrix2D::new(1.0, 0.5, -0.5, 2.0, 3.0, 4.0);
+        canvas.set_transform_matrix(&m).unwrap();
+        assert_eq!(canvas.get_transform().unwrap(), m);
+        canvas.rotate(0.25).unwrap();
+        let expected = m.multiply(&Matrix2D::new(0.25f64.cos(), 0.25f64.sin(), -0.25f64.sin(), 0.25f64.cos(), 0.0, 0.0));
+        let actual = canvas.get_transform().unwrap();
+        for (a, b) in actual.to_array().iter().zip(expected.to_array()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn image_canvas_writes_png() {
+        let mut canvas = CairoCanvas::image(3, 2).unwrap();
+        assert_eq!((canvas.width().unwrap(), canvas.height().unwrap()), (3.0, 2.0));
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut png = Vec::new();
+        canvas.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(ImageSurface::try_from(canvas.surface()).is_ok());
+        assert!(matches!(canvas.finish::<Vec<u8>>(), Err(LignumError::NotSupported(_))));
+    }
+
+    #[test]
+    fn vector_canvases_return_their_writer() {
+        let mut pdf = CairoCanvas::pdf(Vec::new(), 100.0, 50.0).unwrap();
+        assert_eq!((pdf.width().unwrap(), pdf.height().unwrap()), (100.0, 50.0));
+        pdf.fill_rect(10.0, 10.0, 20.0, 20.0).unwrap();
+        assert!(matches!(pdf.write_png(&mut Vec::new()), Err(LignumError::NotSupported(_))));
+        assert!(pdf.finish::<Vec<u8>>().unwrap().starts_with(b"%PDF"));
+
+        let svg = CairoCanvas::svg(Vec::new(), 10.0, 10.0).unwrap();
+        let out = String::from_utf8(svg.finish::<Vec<u8>>().unwrap()).unwrap();
+        assert!(out.contains("<svg"));
+
+        let ps = CairoCanvas::ps(Vec::new(), 10.0, 10.0).unwrap();
+        assert!(ps.finish::<Vec<u8>>().unwrap().starts_with(b"%!PS"));
+
+        let pdf = CairoCanvas::pdf(Vec::new(), 10.0, 10.0).unwrap();
+        assert!(matches!(pdf.finish::<String>(), Err(LignumError::Type(_))));
+        assert!(matches!(CairoCanvas::pdf(Vec::new(), -1.0, 10.0), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn put_image_data_round_trips_and_ignores_state() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        canvas.fill_rect(0.0, 0.0, 4.0, 4.0).unwrap();
+        canvas.translate(1.0, 1.0).unwrap();
+        canvas.set_global_alpha(0.25).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::DestinationOver).unwrap();
+        canvas.rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        canvas.clip(FillRule::NonZero).unwrap();
+        let img = ImageData {
+            width: 2,
+            height: 1,
+            data: vec![255, 0, 0, 255, 0, 0, 255, 0],
+        };
+        canvas.put_image_data(&img, 1.9, 2.0).unwrap();
+
+        let read = canvas.get_image_data(1, 2, 2, 1).unwrap();
+        assert_eq!(read, ImageData { width: 2, height: 1, data: vec![255, 0, 0, 255, 0, 0, 0, 0] });
+        assert_eq!(canvas.get_image_data(0, 2, 1, 1).unwrap().data, vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn get_image_data_unpremultiplies_and_pads_out_of_bounds() {
+        let mut canvas = CairoCanvas::image(2, 2).unwrap();
+        canvas.set_fill_style(Paint::Color("rgba(0, 255, 0, 0.5)".into())).unwrap();
+        canvas.fill_rect(0.0, 0.0, 2.0, 2.0).unwrap();
+        let read = canvas.get_image_data(1, 1, 2, 2).unwrap();
+        assert_eq!(&read.data[..4], &[0, 255, 0, 128]);
+        assert!(read.data[4..].iter().all(|&b| b == 0));
+        assert!(matches!(canvas.get_image_data(0, 0, 0, 1), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn get_image_data_reads_rgb24_as_opaque_and_rejects_other_formats() {
+        let surface = ImageSurface::create(Format::Rgb24, 2, 1).unwrap();
+        let mut canvas = CairoCanvas::new(Context::new(&surface).unwrap());
+        canvas.set_fill_style(Paint::Color("#00ff00".into())).unwrap();
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let read = canvas.get_image_data(0, 0, 2, 1).unwrap();
+        assert_eq!(read.data, vec![0, 255, 0, 255, 0, 0, 0, 255]);
+
+        let surface = ImageSurface::create(Format::A8, 2, 1).unwrap();
+        let canvas = CairoCanvas::new(Context::new(&surface).unwrap());
+        assert!(matches!(canvas.get_image_data(0, 0, 2, 1), Err(LignumError::NotSupported(_))));
+    }
+
+    #[test]
+    fn put_image_data_dirty_writes_only_the_dirty_rect() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        let img = ImageData {
+            width: 2,
+            height: 2,
+            data: [[255, 255, 255, 255]; 4].concat(),
+        };
+        canvas.put_image_data_dirty(&img, 1.0, 1.0, 1, 0, 5, 1).unwrap();
+        let read = canvas.get_image_data(0, 0, 4, 4).unwrap();
+        let opaque: Vec<usize> = (0..16).filter(|i| read.data[i * 4 + 3] == 255).collect();
+        assert_eq!(opaque, vec![6]);
+
+        let mut pdf = CairoCanvas::pdf(Vec::new(), 4.0, 4.0).unwrap();
+        assert!(matches!(pdf.get_image_data(0, 0, 1, 1), Err(LignumError::NotSupported(_))));
+        assert!(matches!(pdf.put_image_data(&img, 0.0, 0.0), Err(LignumError::NotSupported(_))));
+    }
+
+    fn pixel(canvas: &CairoCanvas, x: u32, y: u32) -> Vec<u8> {
+        canvas.get_image_data(x, y, 1, 1).unwrap().data
+    }
+
+    #[test]
+    fn global_alpha_applies_to_every_paint_kind() {
+        let img = ImageData { width: 1, height: 1, data: vec![0, 0, 255, 255] };
+        let mut canvas = CairoCanvas::image(4, 1).unwrap();
+        canvas.set_global_alpha(0.5).unwrap();
+        let pattern = canvas.create_pattern(&img, PatternRepetition::Repeat).unwrap();
+        canvas.set_fill_style(Paint::Pattern(pattern)).unwrap();
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut grad = canvas.create_linear_gradient(0.0, 0.0, 4.0, 0.0).unwrap();
+        grad.add_color_stop(0.0, "#0f0").unwrap();
+        grad.add_color_stop(1.0, "#0f0").unwrap();
+        canvas.set_fill_style(Paint::Gradient(grad)).unwrap();
+        canvas.fill_rect(1.0, 0.0, 1.0, 1.0).unwrap();
+        canvas.draw_image(&img, 2.0, 0.0).unwrap();
+
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 255, 128]);
+        assert_eq!(pixel(&canvas, 1, 0), vec![0, 255, 0, 128]);
+        assert_eq!(pixel(&canvas, 2, 0), vec![0, 0, 255, 128]);
+        assert_eq!(pixel(&canvas, 3, 0), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn copy_replaces_the_clip_region_with_the_drawing() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        canvas.fill_rect(0.0, 0.0, 4.0, 4.0).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::Copy).unwrap();
+        canvas.set_global_alpha(0.5).unwrap();
+        canvas.set_fill_style(Paint::Color("#f00".into())).unwrap();
+        canvas.fill_rect(0.0, 0.0, 2.0, 2.0).unwrap();
+
+        // Copy replaces rather than blends, even with alpha, and clears what it misses.
+        assert_eq!(pixel(&canvas, 1, 1), vec![255, 0, 0, 128]);
+        assert_eq!(pixel(&canvas, 3, 3), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn source_in_and_destination_in_clear_outside_the_shape() {
+        let mut canvas = CairoCanvas::image(4, 1).unwrap();
+        canvas.fill_rect(0.0, 0.0, 2.0, 1.0).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::SourceIn).unwrap();
+        canvas.set_fill_style(Paint::Color("#00f".into())).unwrap();
+        canvas.fill_rect(1.0, 0.0, 2.0, 1.0).unwrap();
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(&canvas, 1, 0), vec![0, 0, 255, 255]);
+        assert_eq!(pixel(&canvas, 2, 0), vec![0, 0, 0, 0]);
+
+        let mut canvas = CairoCanvas::image(4, 1).unwrap();
+        canvas.fill_rect(0.0, 0.0, 4.0, 1.0).unwrap();
+        canvas.rect(0.0, 0.0, 3.0, 1.0).unwrap();
+        canvas.clip(FillRule::NonZero).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::DestinationIn).unwrap();
+        canvas.set_fill_style(Paint::Color("#00f".into())).unwrap();
+        canvas.fill_rect(1.0, 0.0, 1.0, 1.0).unwrap();
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(&canvas, 1, 0), vec![0, 0, 0, 255]);
+        // Outside the clip nothing changes.
+        assert_eq!(pixel(&canvas, 3, 0), vec![0, 0, 0, 255]);
+    }
+
+    const ALL_OPERATIONS: [CompositeOperation; 26] = [
+        CompositeOperation::SourceOver,
+        CompositeOperation::SourceIn,
+        CompositeOperation::SourceOut,
+        CompositeOperation::SourceAtop,
+        CompositeOperation::DestinationOver,
+        CompositeOperation::DestinationIn,
+        CompositeOperation::DestinationOut,
+        CompositeOperation::DestinationAtop,
+        CompositeOperation::Lighter,
+        CompositeOperation::Copy,
+        CompositeOperation::Xor,
+        CompositeOperation::Multiply,
+        CompositeOperation::Screen,
+        CompositeOperation::Overlay,
+        CompositeOperation::Darken,
+        CompositeOperation::Lighten,
+        CompositeOperation::ColorDodge,
+        CompositeOperation::ColorBurn,
+        CompositeOperation::HardLight,
+        CompositeOperation::SoftLight,
+        CompositeOperation::Difference,
+        CompositeOperation::Exclusion,
+        CompositeOperation::Hue,
+        CompositeOperation::Saturation,
+        CompositeOperation::Color,
+        CompositeOperation::Luminosity,
+    ];
+
+    fn lum(c: [f64; 3]) -> f64 {
+        0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+    }
+
+    fn clip_color(c: [f64; 3]) -> [f64; 3] {
+        let l = lum(c);
+        let n = c[0].min(c[1]).min(c[2]);
+        let x = c[0].max(c[1]).max(c[2]);
+        c.map(|v| {
+            let v = if n < 0.0 { l + (v - l) * l / (l - n) } else { v };
+            if x > 1.0 { l + (v - l) * (1.0 - l) / (x - l) } else { v }
+        })
+    }
+
+    fn set_lum(c: [f64; 3], l: f64) -> [f64; 3] {
+        let d = l - lum(c);
+        clip_color(c.map(|v| v + d))
+    }
+
+    fn set_sat(c: [f64; 3], s: f64) -> [f64; 3] {
+        let (n, x) = (c[0].min(c[1]).min(c[2]), c[0].max(c[1]).max(c[2]));
+        if x == n {
+            return [0.0; 3];
+        }
+        c.map(|v| (v - n) * s / (x - n))
+    }
+
+    fn sat(c: [f64; 3]) -> f64 {
+        c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+    }
+
+    /// Straight-alpha result of compositing `src` onto `dst` with `op`, following the
+    /// formulas of the W3C Compositing and Blending spec that Canvas refers to.
+    fn reference_composite(op: &CompositeOperation, src: [f64; 4], dst: [f64; 4]) -> [f64; 4] {
+        use CompositeOperation::*;
+        let (cs, a_s) = ([src[0], src[1], src[2]], src[3]);
+        let (cb, a_b) = ([dst[0], dst[1], dst[2]], dst[3]);
+        let separable = |f: fn(f64, f64) -> f64| [f(cb[0], cs[0]), f(cb[1], cs[1]), f(cb[2], cs[2])];
+        let hard_light = |b: f64, s: f64| {
+            if s <= 0.5 { b * 2.0 * s } else { let s = 2.0 * s - 1.0; b + s - b * s }
+        };
+        let blended = match op {
+            Multiply => Some(separable(|b, s| b * s)),
+            Screen => Some(separable(|b, s| b + s - b * s)),
+            Overlay => Some(separable(|b, s| {
+                if b <= 0.5 { s * 2.0 * b } else { let b = 2.0 * b - 1.0; s + b - s * b }
+            })),
+            Darken => Some(separable(f64::min)),
+            Lighten => Some(separable(f64::max)),
+            ColorDodge => Some(separable(|b, s| {
+                if b == 0.0 { 0.0 } else if s >= 1.0 { 1.0 } else { (b / (1.0 - s)).min(1.0) }
+            })),
+            ColorBurn => Some(separable(|b, s| {
+                if b >= 1.0 { 1.0 } else if s == 0.0 { 0.0 } else { 1.0 - ((1.0 - b) / s).min(1.0) }
+            })),
+            HardLight => Some([hard_light(cb[0], cs[0]), hard_light(cb[1], cs[1]), hard_light(cb[2], cs[2])]),
+            SoftLight => Some(separable(|b, s| {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 { ((16.0 * b - 12.0) * b + 4.0) * b } else { b.sqrt() };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            })),
+            Difference => Some(separable(|b, s| (b - s).abs())),
+            Exclusion => Some(separable(|b, s| b + s - 2.0 * b * s)),
+            Hue => Some(set_lum(set_sat(cs, sat(cb)), lum(cb))),
+            Saturation => Some(set_lum(set_sat(cb, sat(cs)), lum(cb))),
+            Color => Some(set_lum(cs, lum(cb))),
+            Luminosity => Some(set_lum(cb, lum(cs))),
+            _ => None,
+        };
+        let (fa, fb) = match op {
+            SourceIn => (a_b, 0.0),
+            SourceOut => (1.0 - a_b, 0.0),
+            SourceAtop => (a_b, 1.0 - a_s),
+            DestinationOver => (1.0 - a_b, 1.0),
+            DestinationIn => (0.0, a_s),
+            DestinationOut => (0.0, 1.0 - a_s),
+            DestinationAtop => (1.0 - a_b, a_s),
+            Lighter => (1.0, 1.0),
+            Copy => (1.0, 0.0),
+            Xor => (1.0 - a_b, 1.0 - a_s),
+            _ => (1.0, 1.0 - a_s),
+        };
+        // Blend modes mix the blended color into the source where the backdrop is opaque.
+        let cs = match blended {
+            Some(b) => [0, 1, 2].map(|i| (1.0 - a_b) * cs[i] + a_b * b[i]),
+            None => cs,
+        };
+        let a_o = (a_s * fa + a_b * fb).min(1.0);
+        if a_o == 0.0 {
+            return [0.0; 4];
+        }
+        let c = [0, 1, 2].map(|i| ((a_s * fa * cs[i] + a_b * fb * cb[i]) / a_o).min(1.0));
+        [c[0], c[1], c[2], a_o]
+    }
+
+    #[test]
+    fn every_composite_operation_matches_the_reference() {
+        // The destination covers x 0..2 and the drawing x 1..3: pixel 0 is backdrop only,
+        // 1 is both, 2 is drawing only and 3 is neither.
+        let dst = [0.8, 0.2, 0.4, 0.6];
+        let fill = [0.2, 0.6, 0.8, 0.8];
+        for op in ALL_OPERATIONS {
+            for alpha in [1.0, 0.5] {
+                let mut canvas = CairoCanvas::image(4, 1).unwrap();
+                canvas.set_fill_style(Paint::Color("rgba(204, 51, 102, 0.6)".into())).unwrap();
+                canvas.fill_rect(0.0, 0.0, 2.0, 1.0).unwrap();
+                canvas.set_global_composite_operation(op.clone()).unwrap();
+                canvas.set_global_alpha(alpha).unwrap();
+                canvas.set_fill_style(Paint::Color("rgba(51, 153, 204, 0.8)".into())).unwrap();
+                canvas.fill_rect(1.0, 0.0, 2.0, 1.0).unwrap();
+                let actual = canvas.get_image_data(0, 0, 4, 1).unwrap().data;
+
+                let src = [fill[0], fill[1], fill[2], fill[3] * alpha];
+                let none = [0.0; 4];
+                for (x, (s, d)) in [(none, dst), (src, dst), (src, none), (none, none)].into_iter().enumerate() {
+                    let expected = reference_composite(&op, s, d);
+                    let got = &actual[x * 4..x * 4 + 4];
+                    let alpha_ok = (got[3] as f64 - expected[3] * 255.0).abs() <= 2.0;
+                    // Cairo stores 8-bit premultiplied color, so straight color loses precision
+                    // as alpha drops, and means nothing once it reaches zero.
+                    let tolerance = (2.0 / expected[3]).max(4.0);
+                    let color_ok = expected[3] == 0.0
+                        || (0..3).all(|i| (got[i] as f64 - expected[i] * 255.0).abs() <= tolerance);
+                    assert!(
+                        alpha_ok && color_ok,
+                        "{op:?} alpha {alpha} pixel {x}: got {got:?}, expected {:?}",
+                        expected.map(|v| (v * 255.0).round())
+                    );
+                }
+            }
+        }
+    }
+
+    /// Expected pixels from the web-platform-tests `2d.composite.solid.*` and
+    /// `2d.composite.uncovered.fill.*` cases, which browsers are checked against.
+    #[test]
+    fn composite_operations_match_the_web_platform_tests() {
+        use CompositeOperation::*;
+        let close = |got: &[u8], expected: [u8; 4]| {
+            got.iter().zip(expected).all(|(&g, e)| (g as i32 - e as i32).abs() <= 2)
+        };
+        // Opaque cyan destination, opaque yellow source, both covering the whole canvas.
+        let solid = [
+            (SourceOver, [255, 255, 0, 255]),
+            (DestinationOver, [0, 255, 255, 255]),
+            (SourceIn, [255, 255, 0, 255]),
+            (DestinationIn, [0, 255, 255, 255]),
+            (SourceOut, [0, 0, 0, 0]),
+            (DestinationOut, [0, 0, 0, 0]),
+            (SourceAtop, [255, 255, 0, 255]),
+            (DestinationAtop, [0, 255, 255, 255]),
+            (Copy, [255, 255, 0, 255]),
+            (Xor, [0, 0, 0, 0]),
+            (Lighter, [255, 255, 255, 255]),
+        ];
+        for (op, expected) in solid {
+            let mut canvas = CairoCanvas::image(10, 5).unwrap();
+            canvas.set_fill_style(Paint::Color("rgba(0, 255, 255, 1.0)".into())).unwrap();
+            canvas.fill_rect(0.0, 0.0, 10.0, 5.0).unwrap();
+            canvas.set_global_composite_operation(op.clone()).unwrap();
+            canvas.set_fill_style(Paint::Color("rgba(255, 255, 0, 1.0)".into())).unwrap();
+            canvas.fill_rect(0.0, 0.0, 10.0, 5.0).unwrap();
+            let got = pixel(&canvas, 5, 2);
+            assert!(close(&got, expected), "solid {op:?}: got {got:?}, expected {expected:?}");
+        }
+
+        // The source lands entirely off the canvas, so the operators that clear what the
+        // drawing does not cover leave the whole canvas transparent.
+        for op in [Copy, SourceIn, DestinationIn, SourceOut, DestinationAtop] {
+            let mut canvas = CairoCanvas::image(10, 5).unwrap();
+            canvas.set_fill_style(Paint::Color("rgba(0, 255, 255, 0.5)".into())).unwrap();
+            canvas.fill_rect(0.0, 0.0, 10.0, 5.0).unwrap();
+            canvas.set_global_composite_operation(op.clone()).unwrap();
+            canvas.set_fill_style(Paint::Color("rgba(0, 0, 255, 0.75)".into())).unwrap();
+            canvas.translate(0.0, 2.5).unwrap();
+            canvas.fill_rect(0.0, 5.0, 10.0, 5.0).unwrap();
+            let got = pixel(&canvas, 5, 2);
+            assert!(close(&got, [0, 0, 0, 0]), "uncovered {op:?}: got {got:?}");
+        }
+    }
+
+    #[test]
+    fn unbounded_operators_apply_to_images_and_strokes() {
+        let img = ImageData { width: 1, height: 1, data: vec![0, 255, 0, 255] };
+        let mut canvas = CairoCanvas::image(3, 1).unwrap();
+        canvas.fill_rect(0.0, 0.0, 3.0, 1.0).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::Copy).unwrap();
+        canvas.draw_image(&img, 1.0, 0.0).unwrap();
+        let row = canvas.get_image_data(0, 0, 3, 1).unwrap().data;
+        assert_eq!(row, vec![0, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0, 0]);
+
+        let mut canvas = CairoCanvas::image(8, 8).unwrap();
+        canvas.fill_rect(0.0, 0.0, 8.0, 8.0).unwrap();
+        canvas.set_global_composite_operation(CompositeOperation::SourceIn).unwrap();
+        canvas.set_stroke_style(Paint::Color("#f00".into())).unwrap();
+        canvas.set_line_width(2.0).unwrap();
+        canvas.stroke_rect(2.0, 2.0, 4.0, 4.0).unwrap();
+        assert_eq!(pixel(&canvas, 2, 4), vec![255, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 4, 4), vec![0, 0, 0, 0]);
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 0]);
+    }
+
+    /// Calls every trait method with `e` wherever a number goes, plus empty, mismatched and
+    /// oversized images and strings containing NUL. Results are ignored: only panics matter.
+    fn exercise_edge_cases(canvas: &mut CairoCanvas, e: f64) {
+        let empty = ImageData { width: 0, height: 0, data: Vec::new() };
+        let mismatched = ImageData { width: 2, height: 2, data: vec![1] };
+        let wide = ImageData { width: u32::MAX, height: 0, data: Vec::new() };
+        let pixel = ImageData { width: 1, height: 1, data: vec![255; 4] };
+
+        let _ = canvas.save();
+        let _ = canvas.set_global_alpha(e);
+        let _ = canvas.set_global_composite_operation(CompositeOperation::Copy);
+        let _ = canvas.set_image_smoothing_enabled(false);
+        let _ = canvas.set_image_smoothing_quality(ImageSmoothingQuality::High);
+        let _ = canvas.scale(e, e);
+        let _ = canvas.rotate(e);
+        let _ = canvas.translate(e, e);
+        let _ = canvas.transform(e, e, e, e, e, e);
+        let _ = canvas.get_transform();
+        let _ = canvas.set_transform(e, 0.0, 0.0, e, e, e);
+        let _ = canvas.set_transform_matrix(&Matrix2D::new(e, e, e, e, e, e));
+        let _ = canvas.reset_transform();
+        let _ = canvas.set_shadow_offset_x(e);
+        let _ = canvas.set_shadow_offset_y(e);
+        let _ = canvas.set_shadow_blur(e);
+        let _ = canvas.set_shadow_color("\0not a color".into());
+        let _ = canvas.set_line_width(e);
+        let _ = canvas.set_line_cap(LineCap::Round);
+        let _ = canvas.line_cap();
+        let _ = canvas.set_line_join(LineJoin::Bevel);
+        let _ = canvas.line_join();
+        let _ = canvas.set_miter_limit(e);
+        let _ = canvas.set_line_dash(vec![e, 1.0, e]);
+        let _ = canvas.set_line_dash_offset(e);
+        let _ = canvas.line_dash();
+
+        if let Ok(mut grad) = canvas.create_linear_gradient(e, e, e, e) {
+            let _ = grad.add_color_stop(e, "red");
+            let _ = grad.add_color_stop(0.5, "\0");
+            let _ = canvas.set_fill_style(Paint::Gradient(grad));
+        }
+        let _ = canvas.fill_rect(e, e, e, e);
+        if let Ok(grad) = canvas.create_radial_gradient(e, e, e, e, e, e) {
+            let _ = canvas.set_stroke_style(Paint::Gradient(grad));
+        }
+        let _ = canvas.stroke_rect(e, e, e, e);
+        if let Ok(grad) = canvas.create_conic_gradient(e, e, e) {
+            let _ = canvas.set_fill_style(Paint::Gradient(grad));
+        }
+        let _ = canvas.fill_rect(0.0, 0.0, 4.0, 4.0);
+        for image in [&empty, &mismatched, &wide, &pixel] {
+            for repetition in [
+                PatternRepetition::Repeat,
+                PatternRepetition::RepeatX,
+                PatternRepetition::RepeatY,
+                PatternRepetition::NoRepeat,
+            ] {
+                if let Ok(pattern) = canvas.create_pattern(image, repetition) {
+                    let _ = canvas.set_fill_style(Paint::Pattern(pattern));
+                    let _ = canvas.fill_rect(0.0, 0.0, 4.0, 4.0);
+                }
+            }
+        }
+        let _ = canvas.clear_rect(e, e, e, e);
+
+        let _ = canvas.begin_path();
+        let _ = canvas.move_to(e, e);
+        let _ = canvas.line_to(e, e);
+        let _ = canvas.bezier_curve_to(e, e, e, e, e, e);
+        let _ = canvas.quadratic_curve_to(e, e, e, e);
+        let _ = canvas.arc(e, e, e, e, e, true);
+        let _ = canvas.arc_to(e, e, e, e, e);
+        let _ = canvas.ellipse(e, e, e, e, e, e, e, false);
+        let _ = canvas.rect(e, e, e, e);
+        let _ = canvas.round_rect(e, e, e, e, &[]);
+        let _ = canvas.round_rect(0.0, 0.0, 4.0, 4.0, &[e, e, e, e, e]);
+        let _ = canvas.close_path();
+        let _ = canvas.fill(FillRule::EvenOdd);
+        let _ = canvas.stroke();
+        let _ = canvas.is_point_in_path(e, e, HitOptions::default());
+        let _ = canvas.is_point_in_stroke(e, e);
+        let _ = canvas.clip(FillRule::NonZero);
+
+        let _ = canvas.set_font("\0px \0".into());
+        let _ = canvas.set_text_align(TextAlign::Center);
+        let _ = canvas.set_text_baseline(TextBaseline::Bottom);
+        let _ = canvas.set_direction(Direction::Rtl);
+        let _ = canvas.fill_text("a\0b", e, e, Some(e));
+        let _ = canvas.stroke_text("\0", e, e, None);
+        let _ = canvas.measure_text("x\0");
+        let _ = canvas.set_font(String::new());
+        let _ = canvas.fill_text("", e, e, None);
+
+        let _ = canvas.create_image_data(0, 0);
+        let _ = canvas.create_image_data(u32::MAX, u32::MAX);
+        let _ = canvas.get_image_data(u32::MAX, u32::MAX, 1, 1);
+        let _ = canvas.get_image_data(0, 0, u32::MAX, u32::MAX);
+        for image in [&empty, &mismatched, &wide, &pixel] {
+            let _ = canvas.put_image_data(image, e, e);
+            let _ = canvas.put_image_data_dirty(image, e, e, u32::MAX, u32::MAX, u32::MAX, u32::MAX);
+            let _ = canvas.put_image_data_dirty(image, e, e, 0, 0, u32::MAX, u32::MAX);
+            let _ = canvas.draw_image(image, e, e);
+            let _ = canvas.draw_image_scaled(image, e, e, e, e);
+            let _ = canvas.draw_image_subrect(image, e, e, e, e, e, e, e, e);
+        }
+
+        let _ = canvas.begin_group(Some("\0"), Some(""), &[("", "\0")]);
+        let _ = canvas.set_element_id("\0");
+        let _ = canvas.set_title("");
+        let _ = canvas.set_desc("\0");
+        let _ = canvas.set_data_attribute("\0", "\0");
+        let _ = canvas.end_group();
+        let _ = canvas.end_group();
+        let _ = canvas.write_png(&mut Vec::new());
+
+        let _ = canvas.restore();
+        let _ = canvas.restore();
+        let _ = canvas.reset();
+        let _ = canvas.set_size(e, e);
+    }
+
+    #[test]
+    fn edge_case_inputs_never_panic() {
+        let edges = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.0, -1.0, 1e300];
+        for e in edges {
+            let outcome = std::panic::catch_unwind(|| {
+                let mut image = CairoCanvas::image(8, 8).unwrap();
+                exercise_edge_cases(&mut image, e);
+                let mut pdf = CairoCanvas::pdf(Vec::new(), 8.0, 8.0).unwrap();
+                exercise_edge_cases(&mut pdf, e);
+                let _ = pdf.finish::<Vec<u8>>();
+                for format in [Format::A8, Format::Rgb24] {
+                    let surface = ImageSurface::create(format, 8, 8).unwrap();
+                    let mut canvas = CairoCanvas::new(Context::new(&surface).unwrap());
+                    exercise_edge_cases(&mut canvas, e);
+                }
+            });
+            assert!(outcome.is_ok(), "panicked with edge value {e}");
+        }
+    }
+
+    #[test]
+    fn unusable_image_sources_are_typed_errors() {
+        struct Undecoded;
+        impl CanvasImageSource for Undecoded {
+            fn width(&self) -> u32 {
+                1
+            }
+            fn height(&self) -> u32 {
+                1
+            }
+            fn data_rgba(&self) -> Option<&[u8]> {
+                None
+            }
+        }
+
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        let mismatched = ImageData { width: 2, height: 2, data: vec![1] };
+        assert!(matches!(canvas.draw_image(&Undecoded, 0.0, 0.0), Err(LignumError::Type(_))));
+        assert!(matches!(canvas.draw_image(&mismatched, 0.0, 0.0), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn invalid_arguments_are_ignored() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        validate::assert_ignores_invalid_arguments(&mut canvas);
+        canvas.fill(FillRule::NonZero).unwrap();
+        assert!(canvas.get_image_data(0, 0, 4, 4).unwrap().data.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn odd_line_dash_is_repeated() {
+        let mut canvas = CairoCanvas::image(8, 2).unwrap();
+        canvas.set_line_dash(vec![2.0]).unwrap();
+        assert_eq!(canvas.line_dash().unwrap(), vec![2.0, 2.0]);
+        canvas.set_line_width(2.0).unwrap();
+        canvas.move_to(0.0, 1.0).unwrap();
+        canvas.line_to(8.0, 1.0).unwrap();
+        canvas.stroke().unwrap();
+        let alphas: Vec<u8> = (0..8).map(|x| pixel(&canvas, x, 0)[3]).collect();
+        assert_eq!(alphas, vec![255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn degenerate_input_keeps_the_context_usable() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        canvas.save().unwrap();
+        canvas.scale(0.0, 1.0).unwrap();
+        assert_eq!(canvas.get_transform().unwrap(), Matrix2D::new(0.0, 0.0, 0.0, 1.0, 0.0, 0.0));
+        canvas.fill_rect(0.0, 0.0, 4.0, 4.0).unwrap();
+        assert_eq!(pixel(&canvas, 1, 1), vec![0, 0, 0, 0]);
+        canvas.restore().unwrap();
+
+        canvas.set_line_dash(vec![0.0, 0.0]).unwrap();
+        assert_eq!(canvas.line_dash().unwrap(), vec![0.0, 0.0]);
+        canvas.set_line_width(2.0).unwrap();
+        canvas.ellipse(2.0, 2.0, 0.0, 2.0, 0.0, 0.0, std::f64::consts::TAU, false).unwrap();
+        canvas.stroke().unwrap();
+        assert_eq!(pixel(&canvas, 2, 2), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 0]);
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        assert_eq!(pixel(&canvas, 0, 0), vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn tiny_scales_still_draw() {
+        let mut canvas = CairoCanvas::image(4, 4).unwrap();
+        canvas.scale(1e-7, 1e-7).unwrap();
+        assert_eq!(canvas.get_transform().unwrap(), Matrix2D::new(1e-7, 0.0, 0.0, 1e-7, 0.0, 0.0));
+        canvas.fill_rect(0.0, 0.0, 2e7, 2e7).unwrap();
+        assert_eq!(pixel(&canvas, 1, 1), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&canvas, 3, 3), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fill_then_stroke_draws_both() {
+        let (mut surface, mut canvas) = image_canvas(20, 20);
+        canvas.rect(5.0, 5.0, 10.0, 10.0).unwrap();
+        canvas.set_fill_style(Paint::Color("#ff0000".into())).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+        // Rectangle helpers draw on their own path and must not disturb this one.
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        canvas.set_stroke_style(Paint::Color("#0000ff".into())).unwrap();
+        canvas.set_line_width(2.0).unwrap();
+        canvas.stroke().unwrap();
+        drop(canvas);
+        assert_eq!(rgba_at(&mut surface, 10, 10), [255, 0, 0, 255]);
+        assert_eq!(rgba_at(&mut surface, 5, 10), [0, 0, 255, 255]);
+        assert_eq!(alpha_at(&mut surface, 2, 10), 0);
+    }
+
+    #[test]
+    fn clip_keeps_the_path_for_fill() {
+        let (mut surface, mut canvas) = image_canvas(20, 20);
+        canvas.rect(0.0, 0.0, 10.0, 20.0).unwrap();
+        canvas.clip(FillRule::NonZero).unwrap();
+        canvas.fill(FillRule::NonZero).unwrap();
+        drop(canvas);
+        assert_eq!(alpha_at(&mut surface, 5, 5), 255);
+        assert_eq!(alpha_at(&mut surface, 15, 5), 0);
+    }
+
+    #[test]
+    fn restore_brings_back_canvas_side_state() {
+        let (_surface, mut canvas) = image_canvas(4, 4);
+        canvas.save().unwrap();
+        canvas.set_fill_style(Paint::Color("red".into())).unwrap();
+        canvas.set_global_alpha(0.25).unwrap();
+        canvas.set_font("12px serif".into()).unwrap();
+        canvas.restore().unwrap();
+        assert!(matches!(canvas.fill_style().unwrap(), Paint::Color(c) if c == "#000000"));
+        assert_eq!(canvas.global_alpha().unwrap(), 1.0);
+        assert_eq!(canvas.font().unwrap(), "16px Sans");
+
+        // An unbalanced restore is ignored rather than surfacing Cairo's error.
+        canvas.restore().unwrap();
+        canvas.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+    }
+
+    #[test]
+    fn reset_clears_pixels_stack_and_state() {
+        let (mut surface, mut canvas) = image_canvas(4, 4);
+        canvas.fill_rect(0.0, 0.0, 4.0, 4.0).unwrap();
+        canvas.save().unwrap();
+        canvas.set_line_width(5.0).unwrap();
+        canvas.set_global_alpha(0.5).unwrap();
+        canvas.translate(1.0, 1.0).unwrap();
+        canvas.save().unwrap();
+        canvas.reset().unwrap();
+
+        assert!(canvas.stack.is_empty());
+        assert_eq!(canvas.global_alpha().unwrap(), 1.0);
+        assert_eq!(canvas.line_width().unwrap(), 1.0);
+        assert_eq!(canvas.get_transform().unwrap(), Matrix2D::IDENTITY);
+        canvas.restore().unwrap();
+        drop(canvas);
+        assert_eq!(alpha_at(&mut surface, 2, 2), 0);
+    }
+
+    #[test]
+    fn conic_gradient_sweeps_clockwise() {
+        let (mut surface, mut canvas) = image_canvas(16, 16);
+        let mut grad = canvas.create_conic_gradient(0.0, 8.0, 8.0).unwrap();
+        grad.add_color_stop(0.0, "red").unwrap();
+        grad.add_color_stop(0.5, "red").unwrap();
+        grad.add_color_stop(0.5, "blue").unwrap();
+        grad.add_color_stop(1.0, "blue").unwrap();
+        canvas.set_fill_style(Paint::Gradient(grad)).unwrap();
+        canvas.fill_rect(0.0, 0.0, 16.0, 16.0).unwrap();
+        drop(canvas);
+
+        // Just below the +x axis is the start of the sweep; above-left is past the midpoint.
+        assert_eq!(rgba_at(&mut surface, 12, 10), [255, 0, 0, 255]);
+        assert_eq!(rgba_at(&mut surface, 3, 3), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn pattern_repeat_x_stays_in_first_row() {
+        let (mut surface, mut canvas) = image_canvas(8, 8);
+        let img = ImageData {
+            width: 2,
+            height: 2,
+            data: vec![255; 2 * 2 * 4],
+        };
+        let pattern = canvas.create_pattern(&img, PatternRepetition::RepeatX).unwrap();
+        canvas.set_fill_style(Paint::Pattern(pattern)).unwrap();
+        canvas.fill_rect(0.0, 0.0, 8.0, 8.0).unwrap();
+        drop(canvas);
+
+        assert_eq!(alpha_at(&mut surface, 6, 1), 255);
+        assert_eq!(alpha_at(&mut surface, 6, 4), 0);
+    }
+
+    #[test]
+    fn pattern_honors_transform() {
+        let (mut surface, mut canvas) = image_canvas(8, 8);
+        let img = ImageData {
+            width: 2,
+            height: 2,
+            data: vec![255; 2 * 2 * 4],
+        };
+        let mut pattern = canvas.create_pattern(&img, PatternRepetition::NoRepeat).unwrap();
+        pattern.set_transform(Matrix2D::new(1.0, 0.0, 0.0, 1.0, 4.0, 4.0));
+        canvas.set_fill_style(Paint::Pattern(pattern)).unwrap();
+        canvas.fill_rect(0.0, 0.0, 8.0, 8.0).unwrap();
+        drop(canvas);
+
+        assert_eq!(alpha_at(&mut surface, 1, 1), 0);
+        assert_eq!(alpha_at(&mut surface, 5, 5), 255);
+    }
+}
@@ -1,5 +1,12 @@
 use crate::api::*;
+use crate::api::{image_data_len, validate_size};
 use crate::error::Result;
+use crate::flatten;
+use crate::stroker::{self, StrokeStyle};
+use crate::validate;
+
+/// How far flattened curves may stray from the true path when hit testing, in device pixels.
+const HIT_TOLERANCE: f64 = 0.05;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PathCommand {
@@ -184,6 +191,29 @@ pub enum DrawOp {
         h: f64,
         state: Snapshot,
     },
+    BeginGroup {
+        id: Option<String>,
+        class: Option<String>,
+        attributes: Vec<(String, String)>,
+    },
+    EndGroup,
+    SetElementId {
+        id: String,
+    },
+    SetTitle {
+        title: String,
+    },
+    SetDesc {
+        desc: String,
+    },
+    SetDataAttribute {
+        name: String,
+        value: String,
+    },
+    Resize {
+        width: f64,
+        height: f64,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -242,6 +272,8 @@ impl Default for RecorderState {
 }
 
 pub struct RecordingCanvas {
+    width: f64,
+    height: f64,
     ops: Vec<DrawOp>,
     state: RecorderState,
     stack: Vec<RecorderState>,
@@ -251,8 +283,11 @@ pub struct RecordingCanvas {
 }
 
 impl RecordingCanvas {
+    /// Creates a canvas of the HTML default size, 300 x 150.
     pub fn new() -> Self {
         Self {
+            width: 300.0,
+            height: 150.0,
             ops: Vec::new(),
             state: RecorderState::default(),
             stack: Vec::new(),
@@ -262,6 +297,17 @@ impl RecordingCanvas {
         }
     }
 
+    /// Creates a canvas of the given size. Fails with `IndexSize` for a negative or
+    /// non-finite size, as [`CanvasSize::set_size`] does.
+    pub fn with_size(width: f64, height: f64) -> Result<Self> {
+        validate_size(width, height)?;
+        Ok(Self {
+            width,
+            height,
+            ..Self::new()
+        })
+    }
+
     pub fn ops(&self) -> &[DrawOp] {
         &self.ops
     }
@@ -308,29 +354,18 @@ impl RecordingCanvas {
         self.current_point = Some((x, y));
     }
 
-    fn multiply_transform(&mut self, m: [f64; 6]) {
-        let [a, b, c, d, e, f] = self.state.transform;
-        let [na, nb, nc, nd, ne, nf] = m;
-        self.state.transform = [
-            a * na + c * nb,
-            b * na + d * nb,
-            a * nc + c * nd,
-            b * nc + d * nd,
-            a * ne + c * nf + e,
-            b * ne + d * nf + f,
-        ];
+    fn multiply_transform(&mut self, m: Matrix2D) {
+        self.state.transform = Matrix2D::from_array(self.state.transform).multiply(&m).to_array();
     }
 
     fn push_path(&mut self, cmd: PathCommand) {
         self.current_path.push(cmd);
     }
 
-    fn consume_path(&mut self) -> RecordedPath {
-        let path = RecordedPath::new(self.current_path.clone());
-        self.current_path.clear();
-        self.current_point = None;
-        self.subpath_start = None;
-        path
+    /// Copies the current path for a draw op. Like Canvas, filling, stroking and
+    /// clipping leave the path in place; only `begin_path` clears it.
+    fn path_snapshot(&self) -> RecordedPath {
+        RecordedPath::new(self.current_path.clone())
     }
 
     fn record_op(&mut self, op: DrawOp) {
@@ -344,6 +379,25 @@ impl Default for RecordingCanvas {
     }
 }
 
+impl CanvasSize for RecordingCanvas {
+    fn width(&self) -> Result<f64> {
+        Ok(self.width)
+    }
+
+    fn height(&self) -> Result<f64> {
+        Ok(self.height)
+    }
+
+    fn set_size(&mut self, width: f64, height: f64) -> Result<()> {
+        validate_size(width, height)?;
+        self.width = width;
+        self.height = height;
+        self.reset()?;
+        self.record_op(DrawOp::Resize { width, height });
+        Ok(())
+    }
+}
+
 impl CanvasState for RecordingCanvas {
     fn save(&mut self) -> Result<()> {
         self.stack.push(self.state.clone());
@@ -359,6 +413,7 @@ impl CanvasState for RecordingCanvas {
 
     fn reset(&mut self) -> Result<()> {
         self.state = RecorderState::default();
+        self.stack.clear();
         self.current_path.clear();
         self.current_point = None;
         self.subpath_start = None;
@@ -366,6 +421,9 @@ impl CanvasState for RecordingCanvas {
     }
 
     fn set_global_alpha(&mut self, value: f64) -> Result<()> {
+        if !validate::unit_interval(value) {
+            return Ok(());
+        }
         self.state.global_alpha = value;
         Ok(())
     }
@@ -404,28 +462,43 @@ impl CanvasState for RecordingCanvas {
 
 impl CanvasTransforms for RecordingCanvas {
     fn scale(&mut self, x: f64, y: f64) -> Result<()> {
-        self.multiply_transform([x, 0.0, 0.0, y, 0.0, 0.0]);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(x, 0.0, 0.0, y, 0.0, 0.0));
         Ok(())
     }
 
     fn rotate(&mut self, radians: f64) -> Result<()> {
+        if !validate::finite(&[radians]) {
+            return Ok(());
+        }
         let cos = radians.cos();
         let sin = radians.sin();
-        self.multiply_transform([cos, sin, -sin, cos, 0.0, 0.0]);
+        self.multiply_transform(Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0));
         Ok(())
     }
 
     fn translate(&mut self, x: f64, y: f64) -> Result<()> {
-        self.multiply_transform([1.0, 0.0, 0.0, 1.0, x, y]);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(1.0, 0.0, 0.0, 1.0, x, y));
         Ok(())
     }
 
     fn transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
-        self.multiply_transform([a, b, c, d, e, f]);
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(a, b, c, d, e, f));
         Ok(())
     }
 
     fn set_transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
         self.state.transform = [a, b, c, d, e, f];
         Ok(())
     }
@@ -434,10 +507,25 @@ impl CanvasTransforms for RecordingCanvas {
         self.state.transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
         Ok(())
     }
+
+    fn get_transform(&self) -> Result<Matrix2D> {
+        Ok(Matrix2D::from_array(self.state.transform))
+    }
+
+    fn set_transform_matrix(&mut self, matrix: &Matrix2D) -> Result<()> {
+        if !matrix.is_finite() {
+            return Ok(());
+        }
+        self.state.transform = matrix.to_array();
+        Ok(())
+    }
 }
 
 impl CanvasCompositing for RecordingCanvas {
     fn set_shadow_offset_x(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.shadow_offset_x = value;
         Ok(())
     }
@@ -447,6 +535,9 @@ impl CanvasCompositing for RecordingCanvas {
     }
 
     fn set_shadow_offset_y(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.shadow_offset_y = value;
         Ok(())
     }
@@ -456,6 +547,9 @@ impl CanvasCompositing for RecordingCanvas {
     }
 
     fn set_shadow_blur(&mut self, value: f64) -> Result<()> {
+        if !validate::non_negative(value) {
+            return Ok(());
+        }
         self.state.shadow_blur = value;
         Ok(())
     }
@@ -476,6 +570,9 @@ impl CanvasCompositing for RecordingCanvas {
 
 impl CanvasLineStyles for RecordingCanvas {
     fn set_line_width(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.state.line_width = value;
         Ok(())
     }
@@ -503,6 +600,9 @@ impl CanvasLineStyles for RecordingCanvas {
     }
 
     fn set_miter_limit(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.state.miter_limit = value;
         Ok(())
     }
@@ -512,7 +612,10 @@ impl CanvasLineStyles for RecordingCanvas {
     }
 
     fn set_line_dash(&mut self, segments: Vec<f64>) -> Result<()> {
-        self.state.line_dash = segments;
+        if !validate::line_dash(&segments) {
+            return Ok(());
+        }
+        self.state.line_dash = validate::normalize_line_dash(segments);
         Ok(())
     }
 
@@ -521,6 +624,9 @@ impl CanvasLineStyles for RecordingCanvas {
     }
 
     fn set_line_dash_offset(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.line_dash_offset = value;
         Ok(())
     }
@@ -550,6 +656,7 @@ impl CanvasFillStrokeStyles for RecordingCanvas {
     }
 
     fn create_linear_gradient(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) -> Result<CanvasGradient> {
+        validate::gradient_args(&[x0, y0, x1, y1])?;
         Ok(CanvasGradient {
             kind: GradientKind::Linear { x0, y0, x1, y1 },
             stops: Vec::new(),
@@ -565,6 +672,8 @@ impl CanvasFillStrokeStyles for RecordingCanvas {
         y1: f64,
         r1: f64,
     ) -> Result<CanvasGradient> {
+        validate::gradient_args(&[x0, y0, r0, x1, y1, r1])?;
+        validate::radii(&[r0, r1])?;
         Ok(CanvasGradient {
             kind: GradientKind::Radial {
                 x0,
@@ -578,20 +687,28 @@ impl CanvasFillStrokeStyles for RecordingCanvas {
         })
     }
 
+    fn create_conic_gradient(&mut self, start_angle: f64, x: f64, y: f64) -> Result<CanvasGradient> {
+        validate::gradient_args(&[start_angle, x, y])?;
+        Ok(CanvasGradient {
+            kind: GradientKind::Conic { start_angle, x, y },
+            stops: Vec::new(),
+        })
+    }
+
     fn create_pattern(
         &mut self,
-        _image: &dyn CanvasImageSource,
+        image: &dyn CanvasImageSource,
         repetition: PatternRepetition,
     ) -> Result<CanvasPattern> {
-        Ok(CanvasPattern {
-            repetition,
-            transform: None,
-        })
+        CanvasPattern::new(image, repetition)
     }
 }
 
 impl CanvasRectangles for RecordingCanvas {
     fn clear_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
         let op = DrawOp::ClearRect {
             x,
             y,
@@ -604,6 +721,9 @@ impl CanvasRectangles for RecordingCanvas {
     }
 
     fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
         let op = DrawOp::FillRect {
             x,
             y,
@@ -616,6 +736,9 @@ impl CanvasRectangles for RecordingCanvas {
     }
 
     fn stroke_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
         let op = DrawOp::StrokeRect {
             x,
             y,
@@ -645,6 +768,9 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn move_to(&mut self, x: f64, y: f64) -> Result<()> {
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
         self.push_path(PathCommand::MoveTo { x, y });
         self.subpath_start = Some((x, y));
         self.set_current_point(x, y);
@@ -652,6 +778,9 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn line_to(&mut self, x: f64, y: f64) -> Result<()> {
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
         if self.current_point.is_none() {
             self.move_to(0.0, 0.0)?;
         }
@@ -669,6 +798,9 @@ impl CanvasPaths for RecordingCanvas {
         x: f64,
         y: f64,
     ) -> Result<()> {
+        if !validate::finite(&[cp1x, cp1y, cp2x, cp2y, x, y]) {
+            return Ok(());
+        }
         self.ensure_subpath()?;
         self.push_path(PathCommand::BezierCurveTo {
             cp1x,
@@ -683,6 +815,9 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) -> Result<()> {
+        if !validate::finite(&[cpx, cpy, x, y]) {
+            return Ok(());
+        }
         self.ensure_subpath()?;
         self.push_path(PathCommand::QuadraticCurveTo { cpx, cpy, x, y });
         self.set_current_point(x, y);
@@ -698,6 +833,10 @@ impl CanvasPaths for RecordingCanvas {
         end_angle: f64,
         ccw: bool,
     ) -> Result<()> {
+        if !validate::finite(&[x, y, radius, start_angle, end_angle]) {
+            return Ok(());
+        }
+        validate::radii(&[radius])?;
         // Record the arc; approximate the new current point at the end of the arc.
         self.ensure_subpath()?;
         self.push_path(PathCommand::Arc {
@@ -715,6 +854,10 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn arc_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, radius: f64) -> Result<()> {
+        if !validate::finite(&[x1, y1, x2, y2, radius]) {
+            return Ok(());
+        }
+        validate::radii(&[radius])?;
         if self.current_point.is_none() {
             self.move_to(x1, y1)?;
         }
@@ -740,6 +883,10 @@ impl CanvasPaths for RecordingCanvas {
         end_angle: f64,
         ccw: bool,
     ) -> Result<()> {
+        if !validate::finite(&[x, y, radius_x, radius_y, rotation, start_angle, end_angle]) {
+            return Ok(());
+        }
+        validate::radii(&[radius_x, radius_y])?;
         self.ensure_subpath()?;
         self.push_path(PathCommand::Ellipse {
             x,
@@ -762,6 +909,9 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
         self.push_path(PathCommand::Rect { x, y, w, h });
         self.subpath_start = Some((x, y));
         self.set_current_point(x, y);
@@ -769,29 +919,12 @@ impl CanvasPaths for RecordingCanvas {
     }
 
     fn round_rect(&mut self, x: f64, y: f64, w: f64, h: f64, radii: &[f64]) -> Result<()> {
-        let mut corner = [0.0; 4];
-        match radii.len() {
-            0 => {}
-            1 => corner.fill(radii[0]),
-            2 => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[0];
-                corner[3] = radii[1];
-            }
-            3 => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[2];
-                corner[3] = radii[1];
-            }
-            _ => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[2];
-                corner[3] = radii[3];
-            }
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
         }
+        let Some(corner) = validate::round_rect_radii(radii)? else {
+            return Ok(());
+        };
         self.push_path(PathCommand::RoundRect {
             x,
             y,
@@ -808,7 +941,7 @@ impl CanvasPaths for RecordingCanvas {
         if self.current_path.is_empty() {
             return Ok(());
         }
-        let path = self.consume_path();
+        let path = self.path_snapshot();
         let op = DrawOp::FillPath {
             path,
             state: self.snapshot(),
@@ -822,7 +955,7 @@ impl CanvasPaths for RecordingCanvas {
         if self.current_path.is_empty() {
             return Ok(());
         }
-        let path = self.consume_path();
+        let path = self.path_snapshot();
         let op = DrawOp::StrokePath {
             path,
             state: self.snapshot(),
@@ -835,7 +968,7 @@ impl CanvasPaths for RecordingCanvas {
         if self.current_path.is_empty() {
             return Ok(());
         }
-        let path = self.consume_path();
+        let path = self.path_snapshot();
         let clip_state = ClipState {
             path: path.clone(),
             rule: fill_rule.clone(),
@@ -855,8 +988,27 @@ impl CanvasPaths for RecordingCanvas {
         Ok(false)
     }
 
-    fn is_point_in_stroke(&self, _x: f64, _y: f64) -> Result<bool> {
-        Ok(false)
+    fn is_point_in_stroke(&self, x: f64, y: f64) -> Result<bool> {
+        if !validate::finite(&[x, y]) {
+            return Ok(false);
+        }
+        // The path and line settings are in user space, so test the point there.
+        let transform = Matrix2D::from_array(self.state.transform);
+        let Some(inverse) = transform.invert() else {
+            return Ok(false);
+        };
+        let style = StrokeStyle {
+            line_width: self.state.line_width,
+            line_cap: self.state.line_cap.clone(),
+            line_join: self.state.line_join.clone(),
+            miter_limit: self.state.miter_limit,
+            line_dash: self.state.line_dash.clone(),
+            line_dash_offset: self.state.line_dash_offset,
+        };
+        let tolerance = HIT_TOLERANCE / transform.determinant().abs().sqrt();
+        let outline = stroker::stroke_path(&self.path_snapshot(), &style, tolerance);
+        let point = inverse.transform_point(x, y);
+        Ok(flatten::contains(&flatten::flatten(&outline, tolerance), point, FillRule::NonZero))
     }
 }
 
@@ -898,6 +1050,9 @@ impl CanvasText for RecordingCanvas {
     }
 
     fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: Option<f64>) -> Result<()> {
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
         let op = DrawOp::FillText {
             text: text.to_string(),
             x,
@@ -910,6 +1065,9 @@ impl CanvasText for RecordingCanvas {
     }
 
     fn stroke_text(&mut self, text: &str, x: f64, y: f64, max_width: Option<f64>) -> Result<()> {
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
         let op = DrawOp::StrokeText {
             text: text.to_string(),
             x,
@@ -933,7 +1091,7 @@ impl CanvasImageData for RecordingCanvas {
         Ok(ImageData {
             width,
             height,
-            data: vec![0; (width * height * 4) as usize],
+            data: vec![0; image_data_len(width, height)?],
         })
     }
 
@@ -942,11 +1100,14 @@ impl CanvasImageData for RecordingCanvas {
         Ok(ImageData {
             width: sw,
             height: sh,
-            data: vec![0; (sw * sh * 4) as usize],
+            data: vec![0; image_data_len(sw, sh)?],
         })
     }
 
     fn put_image_data(&mut self, data: &ImageData, dx: f64, dy: f64) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
         let op = DrawOp::PutImageData {
             data: data.clone(),
             dx,
@@ -967,6 +1128,9 @@ impl CanvasImageData for RecordingCanvas {
         dirty_width: u32,
         dirty_height: u32,
     ) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
         let op = DrawOp::PutImageDataDirty {
             data: data.clone(),
             dx,
@@ -984,6 +1148,9 @@ impl CanvasImageData for RecordingCanvas {
 
 impl CanvasDrawImage for RecordingCanvas {
     fn draw_image(&mut self, image: &dyn CanvasImageSource, dx: f64, dy: f64) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
         let op = DrawOp::DrawImage {
             source_width: image.width(),
             source_height: image.height(),
@@ -1003,6 +1170,9 @@ impl CanvasDrawImage for RecordingCanvas {
         dw: f64,
         dh: f64,
     ) -> Result<()> {
+        if !validate::finite(&[dx, dy, dw, dh]) {
+            return Ok(());
+        }
         let op = DrawOp::DrawImageScaled {
             source_width: image.width(),
             source_height: image.height(),
@@ -1028,6 +1198,9 @@ impl CanvasDrawImage for RecordingCanvas {
         dw: f64,
         dh: f64,
     ) -> Result<()> {
+        if !validate::finite(&[sx, sy, sw, sh, dx, dy, dw, dh]) {
+            return Ok(());
+        }
         let op = DrawOp::DrawImageSubrect {
             source_width: image.width(),
             source_height: image.height(),
@@ -1048,9 +1221,55 @@ impl CanvasDrawImage for RecordingCanvas {
 
 impl CanvasRenderingContext2D for RecordingCanvas {}
 
+impl CanvasDocument for RecordingCanvas {
+    fn begin_group(&mut self, id: Option<&str>, class: Option<&str>, attributes: &[(&str, &str)]) -> Result<()> {
+        let op = DrawOp::BeginGroup {
+            id: id.map(str::to_string),
+            class: class.map(str::to_string),
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+        self.record_op(op);
+        Ok(())
+    }
+
+    fn end_group(&mut self) -> Result<()> {
+        self.record_op(DrawOp::EndGroup);
+        Ok(())
+    }
+
+    fn set_element_id(&mut self, id: &str) -> Result<()> {
+        self.record_op(DrawOp::SetElementId { id: id.to_string() });
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.record_op(DrawOp::SetTitle {
+            title: title.to_string(),
+        });
+        Ok(())
+    }
+
+    fn set_desc(&mut self, desc: &str) -> Result<()> {
+        self.record_op(DrawOp::SetDesc { desc: desc.to_string() });
+        Ok(())
+    }
+
+    fn set_data_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        self.record_op(DrawOp::SetDataAttribute {
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::LignumError;
 
     fn assert_almost_eq(a: f64, b: f64) {
         assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
@@ -1076,6 +1295,95 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fill_then_stroke_share_the_path() {
+        let mut c = RecordingCanvas::new();
+        c.rect(1.0, 2.0, 3.0, 4.0).unwrap();
+        c.clip(FillRule::NonZero).unwrap();
+        c.fill(FillRule::NonZero).unwrap();
+        c.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        c.stroke().unwrap();
+        let ops = c.ops();
+        assert_eq!(ops.len(), 4);
+        let (DrawOp::Clip { path: clipped, .. }, DrawOp::FillPath { path: filled, .. }, DrawOp::StrokePath { path: stroked, .. }) =
+            (&ops[0], &ops[1], &ops[3])
+        else {
+            panic!("unexpected ops: {:?}", ops);
+        };
+        assert!(!filled.commands.is_empty());
+        assert_eq!(clipped, filled);
+        assert_eq!(filled, stroked);
+
+        c.begin_path().unwrap();
+        c.stroke().unwrap();
+        assert_eq!(c.ops().len(), 4);
+    }
+
+    #[test]
+    fn invalid_arguments_are_ignored() {
+        let mut c = RecordingCanvas::new();
+        validate::assert_ignores_invalid_arguments(&mut c);
+        c.fill(FillRule::NonZero).unwrap();
+        assert!(c.ops().is_empty());
+    }
+
+    #[test]
+    fn is_point_in_stroke_uses_the_stroke_outline() {
+        let mut c = RecordingCanvas::new();
+        c.move_to(0.0, 0.0).unwrap();
+        c.line_to(10.0, 0.0).unwrap();
+        assert!(c.is_point_in_stroke(5.0, 0.4).unwrap());
+        assert!(!c.is_point_in_stroke(5.0, 0.6).unwrap());
+
+        c.set_line_width(4.0).unwrap();
+        c.set_line_cap(LineCap::Round).unwrap();
+        assert!(c.is_point_in_stroke(11.9, 0.0).unwrap());
+        // The round caps of the first dash reach x = 4; the next dash starts at 8.
+        c.set_line_dash(vec![2.0, 6.0]).unwrap();
+        assert!(c.is_point_in_stroke(3.9, 0.0).unwrap());
+        assert!(!c.is_point_in_stroke(5.0, 0.0).unwrap());
+
+        c.set_line_dash(vec![]).unwrap();
+        c.scale(2.0, 2.0).unwrap();
+        assert!(c.is_point_in_stroke(20.0, 3.9).unwrap());
+        assert!(!c.is_point_in_stroke(20.0, 4.1).unwrap());
+    }
+
+    #[test]
+    fn is_point_in_stroke_handles_tiny_scales() {
+        let mut c = RecordingCanvas::new();
+        c.scale(1e-7, 1e-7).unwrap();
+        c.move_to(0.0, 0.0).unwrap();
+        c.line_to(1e8, 0.0).unwrap();
+        assert!(c.is_point_in_stroke(5.0, 0.0).unwrap());
+        assert!(!c.is_point_in_stroke(5.0, 1.0).unwrap());
+    }
+
+    #[test]
+    fn odd_line_dash_is_repeated() {
+        let mut c = RecordingCanvas::new();
+        c.set_line_dash(vec![5.0]).unwrap();
+        assert_eq!(c.line_dash().unwrap(), vec![5.0, 5.0]);
+        c.rect(0.0, 0.0, 10.0, 10.0).unwrap();
+        c.stroke().unwrap();
+        match &c.ops()[0] {
+            DrawOp::StrokePath { state, .. } => assert_eq!(state.line_dash, vec![5.0, 5.0]),
+            other => panic!("unexpected op {other:?}"),
+        }
+    }
+
+    #[test]
+    fn conic_gradient_interpolates_around_the_circle() {
+        let mut c = RecordingCanvas::new();
+        let mut grad = c.create_conic_gradient(1.5, 10.0, 20.0).unwrap();
+        assert_eq!(grad.kind, GradientKind::Conic { start_angle: 1.5, x: 10.0, y: 20.0 });
+        grad.add_color_stop(0.0, "black").unwrap();
+        grad.add_color_stop(1.0, "white").unwrap();
+        let mid = grad.color_at(0.5);
+        assert_almost_eq(mid.r, 0.5);
+        assert_almost_eq(mid.a, 1.0);
+    }
+
     #[test]
     fn records_clip_and_fill_path() {
         let mut c = RecordingCanvas::new();
@@ -1111,6 +1419,111 @@ mod tests {
         }
     }
 
+    #[test]
+    fn color_stops_are_validated() {
+        let mut c = RecordingCanvas::new();
+        let mut grad = c.create_linear_gradient(0.0, 0.0, 1.0, 0.0).unwrap();
+        grad.add_color_stop(0.5, "rgba(0, 0, 255, 0.5)").unwrap();
+        assert!(matches!(grad.add_color_stop(1.5, "red"), Err(LignumError::IndexSize(_))));
+        assert!(matches!(grad.add_color_stop(f64::NAN, "red"), Err(LignumError::Type(_))));
+        assert!(matches!(grad.add_color_stop(0.0, "nope"), Err(LignumError::Syntax(_))));
+        assert_eq!(grad.stops.len(), 1);
+    }
+
+    #[test]
+    fn records_document_markers_in_order() {
+        let mut c = RecordingCanvas::new();
+        c.begin_group(Some("axis"), None, &[("role", "img")]).unwrap();
+        c.set_data_attribute("series", "3").unwrap();
+        c.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        c.end_group().unwrap();
+
+        let ops = c.ops();
+        assert_eq!(ops.len(), 4);
+        assert_eq!(
+            ops[0],
+            DrawOp::BeginGroup {
+                id: Some("axis".into()),
+                class: None,
+                attributes: vec![("role".into(), "img".into())],
+            }
+        );
+        assert!(matches!(&ops[1], DrawOp::SetDataAttribute { name, value } if name == "series" && value == "3"));
+        assert!(matches!(ops[2], DrawOp::FillRect { .. }));
+        assert_eq!(ops[3], DrawOp::EndGroup);
+    }
+
+    #[test]
+    fn resize_is_recorded_and_resets_state() {
+        let mut c = RecordingCanvas::new();
+        assert_eq!((c.width().unwrap(), c.height().unwrap()), (300.0, 150.0));
+        c.translate(5.0, 5.0).unwrap();
+        c.save().unwrap();
+        c.set_line_width(4.0).unwrap();
+        c.move_to(1.0, 1.0).unwrap();
+
+        c.set_size(64.0, 32.0).unwrap();
+        assert_eq!((c.width().unwrap(), c.height().unwrap()), (64.0, 32.0));
+        assert_eq!(c.ops(), &[DrawOp::Resize { width: 64.0, height: 32.0 }]);
+        assert_eq!(c.line_width().unwrap(), 1.0);
+        // The path and the saved state are gone too.
+        c.restore().unwrap();
+        c.stroke().unwrap();
+        c.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        assert_eq!(c.ops().len(), 2);
+        match &c.ops()[1] {
+            DrawOp::FillRect { state, .. } => {
+                assert_eq!(state.transform, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+            }
+            _ => panic!("unexpected op"),
+        }
+        assert!(matches!(c.set_size(-1.0, 1.0), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn with_size_follows_the_set_size_rules() {
+        let c = RecordingCanvas::with_size(64.0, 32.0).unwrap();
+        assert_eq!((c.width().unwrap(), c.height().unwrap()), (64.0, 32.0));
+        assert!(c.ops().is_empty());
+        for (w, h) in [(-1.0, 1.0), (f64::NAN, 1.0), (1.0, f64::INFINITY)] {
+            assert!(matches!(RecordingCanvas::with_size(w, h), Err(LignumError::IndexSize(_))));
+        }
+    }
+
+    #[test]
+    fn reset_empties_the_stack_and_path() {
+        let mut c = RecordingCanvas::new();
+        c.translate(5.0, 5.0).unwrap();
+        c.save().unwrap();
+        c.rect(0.0, 0.0, 2.0, 2.0).unwrap();
+        c.reset().unwrap();
+
+        c.restore().unwrap();
+        c.fill(FillRule::NonZero).unwrap();
+        assert!(c.ops().is_empty());
+        c.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        match &c.ops()[0] {
+            DrawOp::FillRect { state, .. } => {
+                assert_eq!(state.transform, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+            }
+            _ => panic!("unexpected op"),
+        }
+    }
+
+    #[test]
+    fn get_transform_reflects_the_current_matrix() {
+        let mut c = RecordingCanvas::new();
+        c.translate(10.0, 0.0).unwrap();
+        c.scale(2.0, 2.0).unwrap();
+        assert_eq!(c.get_transform().unwrap(), Matrix2D::new(2.0, 0.0, 0.0, 2.0, 10.0, 0.0));
+
+        c.save().unwrap();
+        c.set_transform_matrix(&Matrix2D::new(1.0, 0.0, 0.0, 1.0, 3.0, 4.0)).unwrap();
+        assert_eq!(c.get_transform().unwrap().transform_point(1.0, 1.0), (4.0, 5.0));
+        c.restore().unwrap();
+        assert_eq!(c.get_transform().unwrap().e, 10.0);
+    }
+
     #[test]
     fn records_transforms() {
         let mut c = RecordingCanvas::new();
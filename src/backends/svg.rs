@@ -1,102 +1,643 @@
 //! SVG backend using a streaming XML writer.
 //! This is a skeleton; implement Canvas traits to emit SVG elements.
 
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::Write;
+use std::path::PathBuf;
 
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use png::{ColorType, Encoder as PngEncoder};
 use quick_xml::Writer;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use sha2::{Digest, Sha256};
 
 use crate::api::{
-    CanvasDrawImage, CanvasFillStrokeStyles, CanvasGradient, CanvasImageData, CanvasImageSource,
-    CanvasLineStyles, CanvasPaths, CanvasRectangles, CanvasRenderingContext2D, CanvasState,
-    CanvasText, CanvasTransforms, CompositeOperation, Direction, FillRule, GradientKind,
-    HitOptions, ImageData, ImageSmoothingQuality, LineCap, LineJoin, Paint, PatternRepetition,
-    TextAlign, TextBaseline, TextMetrics,
+    CanvasDocument, CanvasDrawImage, CanvasFillStrokeStyles, CanvasGradient, CanvasImageData,
+    CanvasImageSource, CanvasLineStyles, CanvasPaths, CanvasRectangles, CanvasRenderingContext2D,
+    CanvasSize, CanvasState, CanvasText, CanvasTransforms, CompositeOperation, Direction, FillRule,
+    GradientKind, HitOptions, ImageData, ImageSmoothingQuality, LineCap, LineJoin, Matrix2D,
+    Paint, PatternRepetition, TextAlign, TextBaseline, TextMetrics, crop_rgba, image_data_len,
+    validate_size,
 };
+use crate::color::{Rgba, parse_color};
 use crate::error::{LignumError, Result};
+use crate::flatten;
+use crate::validate;
+
+/// Renders a complete SVG document to RGBA pixels, which lets
+/// [`SvgCanvas::get_image_data`] read back what has been drawn so far.
+pub trait SvgRasterizer {
+    /// Rasterizes `svg` into a `width` x `height` image.
+    fn rasterize(&self, svg: &[u8], width: u32, height: u32) -> Result<ImageData>;
+}
+
+/// Receives the distinct images an [`SvgCanvas`] draws and decides how the document
+/// links to them, e.g. by writing them next to the SVG file.
+pub trait SvgImageSink {
+    /// Stores a PNG-encoded image and returns the href the document should use for it.
+    /// Called once per distinct image.
+    fn store(&mut self, png: &[u8], width: u32, height: u32) -> Result<String>;
+}
+
+/// Writes each image as a PNG named after the SHA-256 digest of its bytes into a
+/// directory, linking to it as `href_prefix` followed by the file name. The names do not
+/// depend on the sink or the Rust release, so sinks sharing a directory never overwrite
+/// each other's images, and a file already there under the same name is reused.
+pub struct DirectoryImageSink {
+    dir: PathBuf,
+    href_prefix: String,
+}
+
+impl DirectoryImageSink {
+    /// Creates a sink writing into `dir`, which is created on first use. For a document
+    /// saved next to `dir`, an `href_prefix` such as `"img/"` keeps links relative.
+    pub fn new(dir: impl Into<PathBuf>, href_prefix: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            href_prefix: href_prefix.into(),
+        }
+    }
+}
+
+impl SvgImageSink for DirectoryImageSink {
+    fn store(&mut self, png: &[u8], _width: u32, _height: u32) -> Result<String> {
+        std::fs::create_dir_all(&self.dir)?;
+        let hex: String = digest(png).iter().map(|byte| format!("{byte:02x}")).collect();
+        let name = format!("{hex}.png");
+        let path = self.dir.join(&name);
+        if !path.exists() {
+            std::fs::write(path, png)?;
+        }
+        Ok(format!("{}{}", self.href_prefix, name))
+    }
+}
+
+/// The SHA-256 digest of `data`. Telling images apart by it avoids keeping a copy of
+/// every image, and no colliding inputs are known.
+fn digest(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Passes output through to the caller's sink, keeping a copy when a rasterizer needs
+/// to see the document.
+struct TeeWriter<W> {
+    inner: W,
+    copy: Option<Vec<u8>>,
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if let Some(copy) = &mut self.copy {
+            copy.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output settings for [`SvgCanvas::with_options`]. The defaults favour readable output;
+/// [`SvgOptions::compact`] favours small files.
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+    indent: bool,
+    precision: Option<usize>,
+    relative_paths: bool,
+    omit_defaults: bool,
+    id_prefix: String,
+    units: SvgUnit,
+    view_box: Option<[f64; 4]>,
+    preserve_aspect_ratio: Option<String>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            indent: true,
+            precision: None,
+            relative_paths: false,
+            omit_defaults: false,
+            id_prefix: String::new(),
+            units: SvgUnit::Px,
+            view_box: None,
+            preserve_aspect_ratio: None,
+        }
+    }
+}
+
+/// Unit of the root element's `width` and `height`. One canvas unit maps to one of these
+/// unless a custom viewBox is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SvgUnit {
+    Px,
+    Mm,
+    Cm,
+    In,
+    Pt,
+}
+
+impl SvgUnit {
+    fn suffix(self) -> &'static str {
+        match self {
+            SvgUnit::Px => "",
+            SvgUnit::Mm => "mm",
+            SvgUnit::Cm => "cm",
+            SvgUnit::In => "in",
+            SvgUnit::Pt => "pt",
+        }
+    }
+}
+
+impl SvgOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No indentation, three decimal places, relative path commands and no attributes
+    /// that merely restate SVG defaults.
+    pub fn compact() -> Self {
+        Self::new()
+            .indent(false)
+            .precision(3)
+            .relative_path_commands(true)
+            .omit_default_attributes(true)
+    }
+
+    /// Puts every element on its own indented line. On by default.
+    pub fn indent(mut self, indent: bool) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Rounds numbers to at most `digits` decimal places, dropping trailing zeros. By
+    /// default numbers are written exactly.
+    pub fn precision(mut self, digits: usize) -> Self {
+        self.precision = Some(digits);
+        self
+    }
+
+    /// Writes path data with relative commands (`m`, `l`, `h`, `v`, ...), which are
+    /// usually shorter. Off by default.
+    pub fn relative_path_commands(mut self, relative: bool) -> Self {
+        self.relative_paths = relative;
+        self
+    }
+
+    /// Leaves out attributes whose value is the SVG default, such as
+    /// `fill-rule="nonzero"` or `stroke-width="1"`. Off by default.
+    pub fn omit_default_attributes(mut self, omit: bool) -> Self {
+        self.omit_defaults = omit;
+        self
+    }
+
+    /// Prepends `prefix` to the ids of generated definitions (gradients, patterns, clip
+    /// paths, images), so that several canvases can share one host document.
+    pub fn id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefix = prefix.into();
+        self
+    }
+
+    /// Writes the root `width` and `height` in `units`. Pixels by default.
+    pub fn units(mut self, units: SvgUnit) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Shows the user-space rectangle at (`x`, `y`) of `width` x `height` instead of the
+    /// whole canvas.
+    pub fn view_box(mut self, x: f64, y: f64, width: f64, height: f64) -> Self {
+        self.view_box = Some([x, y, width, height]);
+        self
+    }
+
+    /// Sets the root `preserveAspectRatio`, e.g. `"xMidYMid meet"` or `"none"`.
+    pub fn preserve_aspect_ratio(mut self, value: impl Into<String>) -> Self {
+        self.preserve_aspect_ratio = Some(value.into());
+        self
+    }
+
+    /// Rounds `v` to the configured precision.
+    fn round(&self, v: f64) -> f64 {
+        match self.precision {
+            Some(digits) => {
+                let scale = 10f64.powi(digits.min(15) as i32);
+                (v * scale).round() / scale
+            }
+            None => v,
+        }
+    }
+
+    fn num(&self, v: f64) -> String {
+        let Some(digits) = self.precision else {
+            return v.to_string();
+        };
+        let mut s = format!("{v:.digits$}");
+        if s.contains('.') {
+            s.truncate(s.trim_end_matches('0').trim_end_matches('.').len());
+        }
+        if s == "-0" {
+            s.remove(0);
+        }
+        s
+    }
+
+    /// Pushes an attribute, unless it restates the SVG `default` and such attributes are
+    /// being omitted.
+    fn push_attr(&self, elem: &mut BytesStart<'_>, name: &str, value: &str, default: &str) {
+        if !self.omit_defaults || value != default {
+            elem.push_attribute((name, value));
+        }
+    }
+}
+
+/// Gzip-compresses everything written through it, for `.svgz` files.
+#[cfg(feature = "svgz")]
+pub struct SvgzWriter<W: Write> {
+    encoder: flate2::write::GzEncoder<W>,
+}
+
+#[cfg(feature = "svgz")]
+impl<W: Write> SvgzWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            encoder: flate2::write::GzEncoder::new(inner, flate2::Compression::best()),
+        }
+    }
+
+    /// Writes the gzip trailer and returns the inner writer. Pass the writer returned by
+    /// [`SvgCanvas::finish`] here; dropping it instead leaves the file truncated.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.encoder.finish()
+    }
+}
+
+#[cfg(feature = "svgz")]
+impl<W: Write> Write for SvgzWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// One command of the path being built, kept in user coordinates until the path is
+/// written so that the output options decide its form.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PathSegment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CubicTo(f64, f64, f64, f64, f64, f64),
+    QuadTo(f64, f64, f64, f64),
+    /// Elliptical arc ending at `(x, y)`, with `rotation` in degrees as SVG takes it.
+    ArcTo {
+        rx: f64,
+        ry: f64,
+        rotation: f64,
+        large: bool,
+        sweep: bool,
+        x: f64,
+        y: f64,
+    },
+    Close,
+}
+
+impl PathSegment {
+    /// Maps the segment through `m`. The image of an ellipse is another ellipse, whose
+    /// radii and rotation come from the singular value decomposition of `m` applied to
+    /// the original axes.
+    fn transformed(self, m: &Matrix2D) -> PathSegment {
+        let p = |x: f64, y: f64| m.transform_point(x, y);
+        match self {
+            PathSegment::MoveTo(x, y) => {
+                let (x, y) = p(x, y);
+                PathSegment::MoveTo(x, y)
+            }
+            PathSegment::LineTo(x, y) => {
+                let (x, y) = p(x, y);
+                PathSegment::LineTo(x, y)
+            }
+            PathSegment::CubicTo(x1, y1, x2, y2, x, y) => {
+                let ((x1, y1), (x2, y2), (x, y)) = (p(x1, y1), p(x2, y2), p(x, y));
+                PathSegment::CubicTo(x1, y1, x2, y2, x, y)
+            }
+            PathSegment::QuadTo(x1, y1, x, y) => {
+                let ((x1, y1), (x, y)) = (p(x1, y1), p(x, y));
+                PathSegment::QuadTo(x1, y1, x, y)
+            }
+            PathSegment::ArcTo {
+                rx,
+                ry,
+                rotation,
+                large,
+                sweep,
+                x,
+                y,
+            } => {
+                let [a, b, c, d, _, _] = m.to_array();
+                let (sin, cos) = rotation.to_radians().sin_cos();
+                // The mapped axes as the columns of [m00 m01; m10 m11].
+                let (m00, m01) = (rx * (a * cos + c * sin), ry * (c * cos - a * sin));
+                let (m10, m11) = (rx * (b * cos + d * sin), ry * (d * cos - b * sin));
+                let (e, f) = ((m00 + m11) / 2.0, (m00 - m11) / 2.0);
+                let (g, h) = ((m10 + m01) / 2.0, (m10 - m01) / 2.0);
+                let (q, r) = (e.hypot(h), f.hypot(g));
+                let angle = (g.atan2(f) + h.atan2(e)) / 2.0;
+                let (x, y) = p(x, y);
+                PathSegment::ArcTo {
+                    rx: q + r,
+                    ry: (q - r).abs(),
+                    rotation: angle.to_degrees(),
+                    large,
+                    // A mirroring transform reverses the direction of travel.
+                    sweep: sweep == (m.determinant() >= 0.0),
+                    x,
+                    y,
+                }
+            }
+            PathSegment::Close => PathSegment::Close,
+        }
+    }
+}
+
+/// Metadata waiting for the next element drawn, set through [`CanvasDocument`].
+#[derive(Default)]
+struct ElementMeta {
+    id: Option<String>,
+    title: Option<String>,
+    desc: Option<String>,
+    data: Vec<(String, String)>,
+}
 
 /// Minimal SVG canvas wrapper around `quick_xml::Writer`.
 pub struct SvgCanvas<W: Write> {
-    writer: Writer<W>,
-    open_root: bool,
-    #[allow(dead_code)]
+    writer: Writer<TeeWriter<W>>,
+    rasterizer: Option<Box<dyn SvgRasterizer>>,
+    options: SvgOptions,
+    root: Root,
+    /// Whether the declaration and root element have been written. They wait for the
+    /// first output so that the size can change until then.
+    started: bool,
     width: f64,
-    #[allow(dead_code)]
     height: f64,
-    current_path: String,
+    /// User-space rectangle the document shows.
+    view_box: [f64; 4],
+    current_path: Vec<PathSegment>,
+    /// Transform in effect when the current path was started; path data is emitted in
+    /// that space.
+    path_transform: Matrix2D,
     current_point: Option<(f64, f64)>,
     subpath_start: Option<(f64, f64)>,
-    clip_counter: usize,
     state: SvgState,
     stack: Vec<SvgState>,
-    gradient_counter: usize,
-    pattern_counter: usize,
+    /// Number of `<g>` elements opened by `begin_group` and not yet closed.
+    open_groups: usize,
+    pending_meta: ElementMeta,
+    /// Definition currently being written, later interned by [`Self::intern_def`].
+    pending_def: Vec<Event<'static>>,
+    /// Interned definitions with their ids, emitted once in a single `<defs>` when the
+    /// document ends.
+    defs: Vec<(String, Vec<Event<'static>>)>,
+    /// Content hash to indices into `defs`, so identical definitions share one id.
+    def_index: HashMap<u64, Vec<usize>>,
+    def_counters: HashMap<&'static str, usize>,
+    /// Where image pixels go; inlined as data URIs when unset.
+    image_sink: Option<Box<dyn SvgImageSink>>,
+    /// Sizes and pixel digests of images already stored, mapped to their definition ids,
+    /// so repeated draws skip PNG encoding entirely.
+    image_ids: HashMap<(u32, u32, [u8; 32]), String>,
 }
 
 impl<W: Write> SvgCanvas<W> {
     /// Create a new SVG canvas that writes into the provided sink, emitting the root `<svg>`.
     /// Width/height are expressed in CSS pixels; a matching `viewBox` is set.
     pub fn new(inner: W, width: f64, height: f64) -> Result<Self> {
-        let mut writer = Writer::new_with_indent(inner, b' ', 2);
-        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
-
-        let width_attr = width.to_string();
-        let height_attr = height.to_string();
-        let view_box_attr = format!("0 0 {} {}", width, height);
-
-        let mut start = BytesStart::new("svg");
-        start.push_attribute(("xmlns", "http://www.w3.org/2000/svg"));
-        start.push_attribute(("version", "1.1"));
-        start.push_attribute(("width", width_attr.as_str()));
-        start.push_attribute(("height", height_attr.as_str()));
-        start.push_attribute(("viewBox", view_box_attr.as_str()));
-        writer.write_event(Event::Start(start))?;
+        Self::create(inner, width, height, SvgOptions::default(), None, Root::Document)
+    }
+
+    /// Like [`SvgCanvas::new`], formatting the output as `options` describe.
+    pub fn with_options(inner: W, width: f64, height: f64, options: SvgOptions) -> Result<Self> {
+        Self::create(inner, width, height, options, None, Root::Document)
+    }
+
+    /// Like [`SvgCanvas::new`], additionally keeping a copy of the output so that
+    /// `get_image_data` can rasterize the document drawn so far.
+    pub fn with_rasterizer(
+        inner: W,
+        width: f64,
+        height: f64,
+        rasterizer: impl SvgRasterizer + 'static,
+    ) -> Result<Self> {
+        let rasterizer: Box<dyn SvgRasterizer> = Box::new(rasterizer);
+        Self::create(inner, width, height, SvgOptions::default(), Some(rasterizer), Root::Document)
+    }
+
+    /// Writes an `<svg>` element without an XML declaration, for embedding in an HTML page
+    /// or an outer SVG document.
+    pub fn fragment(inner: W, width: f64, height: f64, options: SvgOptions) -> Result<Self> {
+        Self::create(inner, width, height, options, None, Root::Element)
+    }
+
+    /// Writes the drawing without any root element, followed by its `<defs>`, for
+    /// streaming straight into an enclosing `<svg>` or `<g>`. Use
+    /// [`SvgOptions::id_prefix`] to keep the definition ids unique there.
+    pub fn fragment_content(inner: W, width: f64, height: f64, options: SvgOptions) -> Result<Self> {
+        Self::create(inner, width, height, options, None, Root::None)
+    }
+
+    fn create(
+        inner: W,
+        width: f64,
+        height: f64,
+        options: SvgOptions,
+        rasterizer: Option<Box<dyn SvgRasterizer>>,
+        root: Root,
+    ) -> Result<Self> {
+        let copy = rasterizer.as_ref().map(|_| Vec::new());
+        let tee = TeeWriter { inner, copy };
+        let writer = if options.indent {
+            Writer::new_with_indent(tee, b' ', 2)
+        } else {
+            Writer::new(tee)
+        };
+        let view_box = options.view_box.unwrap_or([0.0, 0.0, width, height]);
 
         Ok(Self {
             writer,
-            open_root: true,
+            rasterizer,
+            options,
+            root,
+            started: false,
             width,
             height,
-            current_path: String::new(),
+            view_box,
+            current_path: Vec::new(),
+            path_transform: Matrix2D::IDENTITY,
             current_point: None,
             subpath_start: None,
-            clip_counter: 0,
             state: SvgState::default(),
             stack: Vec::new(),
-            gradient_counter: 0,
-            pattern_counter: 0,
+            open_groups: 0,
+            pending_meta: ElementMeta::default(),
+            pending_def: Vec::new(),
+            defs: Vec::new(),
+            def_index: HashMap::new(),
+            def_counters: HashMap::new(),
+            image_sink: None,
+            image_ids: HashMap::new(),
         })
     }
 
+    /// Hands every distinct image to `sink` and links to the href it returns, instead of
+    /// inlining the pixels as a base64 data URI.
+    pub fn set_image_sink(&mut self, sink: impl SvgImageSink + 'static) {
+        self.image_sink = Some(Box::new(sink));
+        // Images stored so far point at the previous store.
+        self.image_ids.clear();
+    }
+
     /// Finish the document, closing the root element and returning the inner writer.
     pub fn finish(mut self) -> Result<W> {
-        if self.open_root {
+        self.start_document()?;
+        for _ in 0..self.open_groups {
+            self.writer.write_event(Event::End(BytesEnd::new("g")))?;
+        }
+        if !self.defs.is_empty() {
+            // References may point forward, so collecting every definition at the end
+            // lets each one be written exactly once.
+            self.writer
+                .write_event(Event::Start(BytesStart::new("defs")))?;
+            for (id, def) in &self.defs {
+                write_def_events(&mut self.writer, id, def)?;
+            }
+            self.writer.write_event(Event::End(BytesEnd::new("defs")))?;
+        }
+        if self.root != Root::None {
             self.writer.write_event(Event::End(BytesEnd::new("svg")))?;
-            self.open_root = false;
         }
-        Ok(self.writer.into_inner())
+        Ok(self.writer.into_inner().inner)
+    }
+
+    /// Writes the XML declaration and root element, once, ahead of the first output.
+    fn start_document(&mut self) -> Result<()> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        if self.root == Root::Document {
+            self.writer
+                .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        }
+        if self.root != Root::None {
+            let start = root_element(&self.options, self.width, self.height, self.view_box);
+            self.writer.write_event(Event::Start(start))?;
+        }
+        Ok(())
     }
 
     fn not_supported(op: &'static str) -> LignumError {
-        LignumError::Other(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            format!("SVG backend does not yet implement {op}"),
-        )))
+        LignumError::NotSupported(format!("SVG backend does not implement {op}"))
     }
 
+    /// Writes a drawn element, attaching any metadata set for it.
     fn write_empty(&mut self, elem: BytesStart<'_>) -> Result<()> {
-        self.writer.write_event(Event::Empty(elem))?;
+        self.start_document()?;
+        if self.pending_meta.title.is_none() && self.pending_meta.desc.is_none() {
+            let elem = self.take_meta_attrs(elem);
+            self.writer.write_event(Event::Empty(elem))?;
+            return Ok(());
+        }
+        let name = String::from_utf8_lossy(elem.name().as_ref()).into_owned();
+        self.write_start(elem)?;
+        self.writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+
+    /// Opens a drawn element, attaching any metadata set for it. Titles and descriptions
+    /// become its first children.
+    fn write_start(&mut self, elem: BytesStart<'_>) -> Result<()> {
+        self.start_document()?;
+        let title = self.pending_meta.title.take();
+        let desc = self.pending_meta.desc.take();
+        let elem = self.take_meta_attrs(elem);
+        self.writer.write_event(Event::Start(elem))?;
+        for (tag, text) in [("title", title), ("desc", desc)] {
+            if let Some(text) = text {
+                self.writer.write_event(Event::Start(BytesStart::new(tag)))?;
+                self.writer.write_event(Event::Text(BytesText::new(&text)))?;
+                self.writer.write_event(Event::End(BytesEnd::new(tag)))?;
+            }
+        }
         Ok(())
     }
 
-    fn paint_to_str(&mut self, paint: &Paint) -> Result<String> {
+    /// Moves the pending id and `data-*` attributes onto `elem`.
+    fn take_meta_attrs<'a>(&mut self, elem: BytesStart<'a>) -> BytesStart<'a> {
+        let meta = std::mem::take(&mut self.pending_meta);
+        let mut elem = match &meta.id {
+            Some(id) => with_id(&elem, id),
+            None => elem,
+        };
+        for (name, value) in &meta.data {
+            let name_attr = format!("data-{name}");
+            elem.push_attribute((name_attr.as_str(), value.as_str()));
+        }
+        elem
+    }
+
+    /// Appends an event to the definition being built. Its outermost element is written
+    /// without an `id`; one is assigned when the definition is interned.
+    fn write_def(&mut self, event: Event<'_>) {
+        self.pending_def.push(event.into_owned());
+    }
+
+    /// Finishes the pending definition and returns its id, reusing the id of an identical
+    /// definition written earlier.
+    fn intern_def(&mut self, prefix: &'static str) -> String {
+        let def = std::mem::take(&mut self.pending_def);
+        let mut hasher = DefaultHasher::new();
+        for event in &def {
+            event[..].hash(&mut hasher);
+        }
+        let candidates = self.def_index.entry(hasher.finish()).or_default();
+        if let Some(&index) = candidates.iter().find(|&&index| self.defs[index].1 == def) {
+            return self.defs[index].0.clone();
+        }
+
+        let counter = self.def_counters.entry(prefix).or_insert(0);
+        let id = format!("{}{prefix}{counter}", self.options.id_prefix);
+        *counter += 1;
+        candidates.push(self.defs.len());
+        self.defs.push((id.clone(), def));
+        id
+    }
+
+    /// Resolves a paint for an element drawn with `element_transform`. Gradients and
+    /// patterns live in the coordinate space current at fill time, which may differ from
+    /// the element's own (e.g. when a path was built before the transform changed).
+    fn paint_to_str(&mut self, paint: &Paint, element_transform: Matrix2D) -> Result<String> {
         match paint {
             Paint::Color(c) => Ok(c.clone()),
-            Paint::Gradient(g) => self.gradient_paint(g),
-            Paint::Pattern(p) => self.pattern_paint(p),
+            Paint::Gradient(g) => self.gradient_paint(g, element_transform),
+            Paint::Pattern(p) => self.pattern_paint(p, element_transform),
+        }
+    }
+
+    /// Maps paint space (the transform at fill time) into the element's user space, or
+    /// `None` when the two coincide.
+    fn paint_space(&self, element_transform: Matrix2D) -> Option<Matrix2D> {
+        let space = element_transform.invert()?.multiply(&self.state.transform);
+        let identity = Matrix2D::IDENTITY.to_array();
+        if space.to_array().iter().zip(identity).all(|(v, i)| (v - i).abs() < 1e-12) {
+            None
+        } else {
+            Some(space)
         }
     }
 
@@ -106,32 +647,45 @@ impl<W: Write> SvgCanvas<W> {
         }
     }
 
-    fn gradient_paint(&mut self, gradient: &CanvasGradient) -> Result<String> {
-        let id = format!("grad{}", self.gradient_counter);
-        self.gradient_counter += 1;
-        self.write_gradient_def(&id, gradient)?;
-        Ok(format!("url(#{})", id))
-    }
+    fn gradient_paint(&mut self, gradient: &CanvasGradient, element_transform: Matrix2D) -> Result<String> {
+        // A gradient without stops, or whose start and end coincide, paints nothing.
+        let degenerate = match gradient.kind {
+            GradientKind::Linear { x0, y0, x1, y1 } => x0 == x1 && y0 == y1,
+            GradientKind::Radial {
+                x0,
+                y0,
+                r0,
+                x1,
+                y1,
+                r1,
+            } => x0 == x1 && y0 == y1 && r0 == r1,
+            GradientKind::Conic { .. } => false,
+        };
+        if gradient.stops.is_empty() || degenerate {
+            return Ok("none".to_string());
+        }
 
-    fn write_gradient_def(&mut self, id: &str, gradient: &CanvasGradient) -> Result<()> {
-        self.writer
-            .write_event(Event::Start(BytesStart::new("defs")))?;
+        let space = self.paint_space(element_transform);
+        self.write_gradient_def(gradient, space)?;
+        Ok(format!("url(#{})", self.intern_def("grad")))
+    }
 
-        match &gradient.kind {
+    fn write_gradient_def(&mut self, gradient: &CanvasGradient, space: Option<Matrix2D>) -> Result<()> {
+        let mut reversed = false;
+        let (mut elem, end_tag) = match &gradient.kind {
             GradientKind::Linear { x0, y0, x1, y1 } => {
                 let mut elem = BytesStart::new("linearGradient");
-                elem.push_attribute(("id", id));
-                let x1_attr = x0.to_string();
-                let y1_attr = y0.to_string();
-                let x2_attr = x1.to_string();
-                let y2_attr = y1.to_string();
+                let x1_attr = self.options.num(*x0);
+                let y1_attr = self.options.num(*y0);
+                let x2_attr = self.options.num(*x1);
+                let y2_attr = self.options.num(*y1);
                 elem.push_attribute(("x1", x1_attr.as_str()));
                 elem.push_attribute(("y1", y1_attr.as_str()));
                 elem.push_attribute(("x2", x2_attr.as_str()));
                 elem.push_attribute(("y2", y2_attr.as_str()));
-                self.writer.write_event(Event::Start(elem))?;
+                (elem, "linearGradient")
             }
-            GradientKind::Radial {
+            &GradientKind::Radial {
                 x0,
                 y0,
                 r0,
@@ -139,99 +693,384 @@ impl<W: Write> SvgCanvas<W> {
                 y1,
                 r1,
             } => {
+                let (r0, r1) = (r0.max(0.0), r1.max(0.0));
+                let dist = (x1 - x0).hypot(y1 - y0);
+                let (start, end) = if dist + r0 <= r1 {
+                    ((x0, y0, r0), (x1, y1, r1))
+                } else if dist + r1 <= r0 {
+                    // The end circle sits inside the start circle. The circles are nested,
+                    // so swapping them and mirroring the stops paints the same image with
+                    // the focal circle inside, which every SVG renderer supports.
+                    reversed = true;
+                    ((x1, y1, r1), (x0, y0, r0))
+                } else {
+                    // Neither circle contains the other: Canvas paints a cone that SVG
+                    // 1.1 renderers cannot express once the focal point leaves the end
+                    // circle.
+                    return self.write_radial_cone_def(gradient, (x0, y0, r0), (x1, y1, r1), space);
+                };
                 let mut elem = BytesStart::new("radialGradient");
-                elem.push_attribute(("id", id));
-                let cx_attr = x1.to_string();
-                let cy_attr = y1.to_string();
-                let r_attr = r1.to_string();
-                let fx_attr = x0.to_string();
-                let fy_attr = y0.to_string();
-                let fr_attr = r0.to_string();
+                let cx_attr = self.options.num(end.0);
+                let cy_attr = self.options.num(end.1);
+                let r_attr = self.options.num(end.2);
+                let fx_attr = self.options.num(start.0);
+                let fy_attr = self.options.num(start.1);
+                let fr_attr = self.options.num(start.2);
                 elem.push_attribute(("cx", cx_attr.as_str()));
                 elem.push_attribute(("cy", cy_attr.as_str()));
                 elem.push_attribute(("r", r_attr.as_str()));
                 elem.push_attribute(("fx", fx_attr.as_str()));
                 elem.push_attribute(("fy", fy_attr.as_str()));
                 elem.push_attribute(("fr", fr_attr.as_str()));
-                self.writer.write_event(Event::Start(elem))?;
+                (elem, "radialGradient")
+            }
+            GradientKind::Conic { start_angle, x, y } => {
+                return self.write_conic_def(gradient, *start_angle, *x, *y, space);
+            }
+        };
+        elem.push_attribute(("gradientUnits", "userSpaceOnUse"));
+        if let Some(m) = space {
+            let transform_attr = matrix_attr(m, &self.options);
+            elem.push_attribute(("gradientTransform", transform_attr.as_str()));
+        }
+        self.options.push_attr(&mut elem, "color-interpolation", "sRGB", "sRGB");
+        self.write_def(Event::Start(elem));
+
+        // Canvas sorts stops by offset, keeping insertion order among equal offsets.
+        let mut stops: Vec<_> = gradient
+            .stops
+            .iter()
+            .map(|stop| (stop.offset.clamp(0.0, 1.0), stop.color.as_str()))
+            .collect();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if reversed {
+            stops.reverse();
+            for stop in &mut stops {
+                stop.0 = 1.0 - stop.0;
             }
         }
-
-        for stop in &gradient.stops {
+        for (offset, color) in stops {
             let mut stop_elem = BytesStart::new("stop");
-            let offset_attr = stop.offset.to_string();
+            let offset_attr = self.options.num(offset);
             stop_elem.push_attribute(("offset", offset_attr.as_str()));
-            stop_elem.push_attribute(("stop-color", stop.color.as_str()));
-            self.writer.write_event(Event::Empty(stop_elem))?;
+            // SVG 1.1 has no alpha in stop-color, so translucent stops are split.
+            let rgba = parse_color(color).unwrap_or(Rgba::BLACK);
+            if rgba.a < 1.0 {
+                let color_attr = rgba.to_hex();
+                let opacity_attr = self.options.num(rgba.a);
+                stop_elem.push_attribute(("stop-color", color_attr.as_str()));
+                stop_elem.push_attribute(("stop-opacity", opacity_attr.as_str()));
+            } else {
+                stop_elem.push_attribute(("stop-color", color));
+            }
+            self.write_def(Event::Empty(stop_elem));
         }
 
-        let end_tag = match gradient.kind {
-            GradientKind::Linear { .. } => "linearGradient",
-            GradientKind::Radial { .. } => "radialGradient",
-        };
-        self.writer
-            .write_event(Event::End(BytesEnd::new(end_tag)))?;
-        self.writer.write_event(Event::End(BytesEnd::new("defs")))?;
+        self.write_def(Event::End(BytesEnd::new(end_tag)));
         Ok(())
     }
 
-    fn pattern_paint(&mut self, pattern: &crate::api::CanvasPattern) -> Result<String> {
-        let id = format!("pat{}", self.pattern_counter);
-        self.pattern_counter += 1;
-        self.write_pattern_def(&id, pattern)?;
-        Ok(format!("url(#{})", id))
-    }
-
-    fn write_pattern_def(&mut self, id: &str, pattern: &crate::api::CanvasPattern) -> Result<()> {
-        self.writer
-            .write_event(Event::Start(BytesStart::new("defs")))?;
+    /// SVG has no conic gradient, so approximate one with a single-tile pattern covering
+    /// the canvas, filled with pie slices around the centre. Each span between stops gets
+    /// one slice per visible color step, so solid spans need a single slice.
+    fn write_conic_def(
+        &mut self,
+        gradient: &CanvasGradient,
+        start_angle: f64,
+        cx: f64,
+        cy: f64,
+        space: Option<Matrix2D>,
+    ) -> Result<()> {
+        // At most one slice per degree, and never more than half a turn per slice so the
+        // arc flags stay unambiguous.
+        const MAX_WEDGES: f64 = 360.0;
+        let tau = std::f64::consts::TAU;
+
+        let (min_x, min_y, max_x, max_y) = self.canvas_bounds_in(None);
+        let radius = [(min_x, min_y), (max_x, min_y), (min_x, max_y), (max_x, max_y)]
+            .iter()
+            .map(|(x, y)| (x - cx).hypot(y - cy))
+            .fold(1.0, f64::max)
+            + 1.0;
+        let tile_x = (cx - radius).floor();
+        let tile_y = (cy - radius).floor();
+        let tile_size = (2.0 * radius).ceil() + 2.0;
 
         let mut elem = BytesStart::new("pattern");
-        elem.push_attribute(("id", id));
-        // Use a 1x1 tile; without image data we cannot scale to source size.
-        elem.push_attribute(("width", "1"));
-        elem.push_attribute(("height", "1"));
+        let x_attr = self.options.num(tile_x);
+        let y_attr = self.options.num(tile_y);
+        let size_attr = self.options.num(tile_size);
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
+        elem.push_attribute(("width", size_attr.as_str()));
+        elem.push_attribute(("height", size_attr.as_str()));
         elem.push_attribute(("patternUnits", "userSpaceOnUse"));
-        match pattern.repetition {
-            PatternRepetition::Repeat | PatternRepetition::RepeatX | PatternRepetition::RepeatY => {
+        if let Some(m) = space {
+            let transform_attr = matrix_attr(m, &self.options);
+            elem.push_attribute(("patternTransform", transform_attr.as_str()));
+        }
+        self.write_def(Event::Start(elem));
+
+        let stops = gradient.prepare();
+        let mut bounds: Vec<f64> = stops.stops().iter().map(|&(offset, _)| offset).collect();
+        bounds.extend([0.0, 1.0]);
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+        let mut wedges = Vec::new();
+        for pair in bounds.windows(2) {
+            let (o0, o1) = (pair[0], pair[1]);
+            let span = o1 - o0;
+            // Sample just inside the span so each side of a hard stop gets its own color.
+            let eps = span * 1e-6;
+            let (c0, c1) = (stops.color_at(o0 + eps), stops.color_at(o1 - eps));
+            let change = [c1.r - c0.r, c1.g - c0.g, c1.b - c0.b, c1.a - c0.a]
+                .iter()
+                .fold(0.0, |max: f64, d| max.max(d.abs()));
+            let count = (change * 255.0)
+                .ceil()
+                .min((span * MAX_WEDGES).ceil())
+                .max((span * 2.0).ceil())
+                .max(1.0) as usize;
+            for i in 0..count {
+                let t0 = o0 + span * i as f64 / count as f64;
+                let t1 = o0 + span * (i + 1) as f64 / count as f64;
+                wedges.push((t0, t1, stops.color_at((t0 + t1) / 2.0)));
             }
-            PatternRepetition::NoRepeat => {
-                // Still emit a pattern; consumers can treat it as single-tile.
+        }
+
+        // Pattern content is positioned relative to the tile origin.
+        let (ox, oy) = (cx - tile_x, cy - tile_y);
+        for (t0, t1, color) in wedges {
+            // Overlap neighbours slightly so anti-aliasing leaves no seams.
+            let a0 = start_angle + t0 * tau - 0.002;
+            let a1 = start_angle + t1 * tau + 0.002;
+            let r = self.options.num(radius);
+            let d = format!(
+                "M{} {} L{} {} A{r} {r} 0 0 1 {} {} Z",
+                self.options.num(ox),
+                self.options.num(oy),
+                self.options.num(ox + radius * a0.cos()),
+                self.options.num(oy + radius * a0.sin()),
+                self.options.num(ox + radius * a1.cos()),
+                self.options.num(oy + radius * a1.sin())
+            );
+            let mut wedge = BytesStart::new("path");
+            wedge.push_attribute(("d", d.as_str()));
+            let fill_attr = color.to_hex();
+            wedge.push_attribute(("fill", fill_attr.as_str()));
+            if color.a < 1.0 {
+                let opacity_attr = self.options.num(color.a);
+                wedge.push_attribute(("fill-opacity", opacity_attr.as_str()));
             }
+            self.write_def(Event::Empty(wedge));
+        }
+
+        self.write_def(Event::End(BytesEnd::new("pattern")));
+        Ok(())
+    }
+
+    /// Renders a radial gradient whose circles overlap into a cone the way Canvas
+    /// specifies: circles for every ω with r(ω) > 0, painted from the largest ω down so
+    /// that the smallest ω wins. Consecutive circles are joined into their convex hull,
+    /// which is exactly the area they sweep. Translucent bands blend where they overlap
+    /// slightly; opaque gradients are exact.
+    fn write_radial_cone_def(
+        &mut self,
+        gradient: &CanvasGradient,
+        start: (f64, f64, f64),
+        end: (f64, f64, f64),
+        space: Option<Matrix2D>,
+    ) -> Result<()> {
+        const BANDS: usize = 256;
+
+        let (min_x, min_y, max_x, max_y) = self.canvas_bounds_in(None);
+        let (tile_x, tile_y) = (min_x.floor() - 1.0, min_y.floor() - 1.0);
+        let tile_w = max_x.ceil() + 1.0 - tile_x;
+        let tile_h = max_y.ceil() + 1.0 - tile_y;
+
+        let circle_at = |w: f64| {
+            (
+                start.0 + w * (end.0 - start.0),
+                start.1 + w * (end.1 - start.1),
+                (start.2 + w * (end.2 - start.2)).max(0.0),
+            )
+        };
+
+        // Beyond ±reach the circles no longer touch the canvas; the radius also has to
+        // stay non-negative.
+        let dist = (end.0 - start.0).hypot(end.1 - start.1);
+        let dr = end.2 - start.2;
+        let far = [(min_x, min_y), (max_x, min_y), (min_x, max_y), (max_x, max_y)]
+            .iter()
+            .map(|(x, y)| (x - start.0).hypot(y - start.1))
+            .fold(0.0, f64::max);
+        let reach = ((far + start.2) / (dist - dr.abs()) + 1.0).min(1e6);
+        let hi = if dr < 0.0 { reach.min(-start.2 / dr) } else { reach };
+        let lo = if dr > 0.0 { (-reach).max(-start.2 / dr) } else { -reach };
+
+        let mut offsets: Vec<f64> = (0..=BANDS).map(|i| i as f64 / BANDS as f64).collect();
+        offsets.extend(gradient.stops.iter().map(|stop| stop.offset.clamp(0.0, 1.0)));
+        offsets.sort_by(f64::total_cmp);
+        offsets.dedup();
+
+        // Bands from the largest ω down: the end pad, the stop range, the start pad.
+        let stops = gradient.prepare();
+        let mut bands = Vec::new();
+        if hi > 1.0 {
+            bands.push((1.0, hi, stops.color_at(1.0)));
+        }
+        for pair in offsets.windows(2).rev() {
+            let color = stops.color_at((pair[0] + pair[1]) / 2.0);
+            bands.push((pair[0], pair[1], color));
         }
-        if let Some(m) = pattern.transform {
-            let [a, b, c, d, e, f] = m;
-            let transform_attr = format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f);
+        if lo < 0.0 {
+            bands.push((lo, 0.0, stops.color_at(0.0)));
+        }
+        let mut elem = BytesStart::new("pattern");
+        let x_attr = self.options.num(tile_x);
+        let y_attr = self.options.num(tile_y);
+        let w_attr = self.options.num(tile_w);
+        let h_attr = self.options.num(tile_h);
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
+        elem.push_attribute(("width", w_attr.as_str()));
+        elem.push_attribute(("height", h_attr.as_str()));
+        elem.push_attribute(("patternUnits", "userSpaceOnUse"));
+        if let Some(m) = space {
+            let transform_attr = matrix_attr(m, &self.options);
             elem.push_attribute(("patternTransform", transform_attr.as_str()));
         }
-        self.writer.write_event(Event::Start(elem))?;
+        self.write_def(Event::Start(elem));
+
+        for (w0, w1, color) in bands {
+            if color.a <= 0.0 {
+                continue;
+            }
+            // Reach slightly into the band painted before to hide anti-aliasing seams.
+            let w1 = w1 + (w1 - w0) * 1e-3;
+            let (x0, y0, r0) = circle_at(w0);
+            let (x1, y1, r1) = circle_at(w1);
+            let d = circle_hull_path(
+                (x0 - tile_x, y0 - tile_y, r0),
+                (x1 - tile_x, y1 - tile_y, r1),
+                &self.options,
+            );
+            let mut band = BytesStart::new("path");
+            band.push_attribute(("d", d.as_str()));
+            let fill_attr = color.to_hex();
+            band.push_attribute(("fill", fill_attr.as_str()));
+            if color.a < 1.0 {
+                let opacity_attr = self.options.num(color.a);
+                band.push_attribute(("fill-opacity", opacity_attr.as_str()));
+            }
+            self.write_def(Event::Empty(band));
+        }
+
+        self.write_def(Event::End(BytesEnd::new("pattern")));
+        Ok(())
+    }
+
+    fn pattern_paint(&mut self, pattern: &crate::api::CanvasPattern, element_transform: Matrix2D) -> Result<String> {
+        let space = self.paint_space(element_transform);
+        self.write_pattern_def(pattern, space)?;
+        Ok(format!("url(#{})", self.intern_def("pat")))
+    }
 
-        // Placeholder transparent rect to keep the pattern valid.
-        let mut rect = BytesStart::new("rect");
-        rect.push_attribute(("width", "1"));
-        rect.push_attribute(("height", "1"));
-        rect.push_attribute(("fill", "rgba(0,0,0,0)"));
-        self.writer.write_event(Event::Empty(rect))?;
+    fn write_pattern_def(
+        &mut self,
+        pattern: &crate::api::CanvasPattern,
+        space: Option<Matrix2D>,
+    ) -> Result<()> {
+        let image_w = pattern.image.width as f64;
+        let image_h = pattern.image.height as f64;
+        let image_id = self.image_def(pattern.image.as_ref())?;
+
+        // SVG patterns always tile in both directions. For an axis that must not repeat,
+        // stretch the tile along that axis so it covers the whole canvas as seen from
+        // pattern space, leaving a single copy of the image inside it.
+        let (min_x, min_y, max_x, max_y) = self.canvas_bounds_in(pattern.transform);
+        let (tile_x, tile_w) = match pattern.repetition {
+            PatternRepetition::Repeat | PatternRepetition::RepeatX => (0.0, image_w),
+            PatternRepetition::RepeatY | PatternRepetition::NoRepeat => {
+                let lo = min_x.min(0.0).floor() - 1.0;
+                let hi = max_x.max(image_w).ceil() + 1.0;
+                (lo, hi - lo)
+            }
+        };
+        let (tile_y, tile_h) = match pattern.repetition {
+            PatternRepetition::Repeat | PatternRepetition::RepeatY => (0.0, image_h),
+            PatternRepetition::RepeatX | PatternRepetition::NoRepeat => {
+                let lo = min_y.min(0.0).floor() - 1.0;
+                let hi = max_y.max(image_h).ceil() + 1.0;
+                (lo, hi - lo)
+            }
+        };
 
-        self.writer
-            .write_event(Event::End(BytesEnd::new("pattern")))?;
-        self.writer.write_event(Event::End(BytesEnd::new("defs")))?;
+        let mut elem = BytesStart::new("pattern");
+        let x_attr = self.options.num(tile_x);
+        let y_attr = self.options.num(tile_y);
+        let w_attr = self.options.num(tile_w);
+        let h_attr = self.options.num(tile_h);
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
+        elem.push_attribute(("width", w_attr.as_str()));
+        elem.push_attribute(("height", h_attr.as_str()));
+        elem.push_attribute(("patternUnits", "userSpaceOnUse"));
+        let pattern_transform = match (space, pattern.transform) {
+            (Some(space), Some(m)) => Some(space.multiply(&m)),
+            (space, m) => space.or(m),
+        };
+        if let Some(m) = pattern_transform {
+            let transform_attr = matrix_attr(m, &self.options);
+            elem.push_attribute(("patternTransform", transform_attr.as_str()));
+        }
+        self.write_def(Event::Start(elem));
+
+        // Pattern content is positioned relative to the tile origin.
+        let mut image = BytesStart::new("use");
+        let href_attr = format!("#{}", image_id);
+        let ix_attr = self.options.num(0.0 - tile_x);
+        let iy_attr = self.options.num(0.0 - tile_y);
+        image.push_attribute(("href", href_attr.as_str()));
+        self.options.push_attr(&mut image, "x", ix_attr.as_str(), "0");
+        self.options.push_attr(&mut image, "y", iy_attr.as_str(), "0");
+        self.apply_image_rendering_attr(&mut image);
+        self.write_def(Event::Empty(image));
+
+        self.write_def(Event::End(BytesEnd::new("pattern")));
         Ok(())
     }
 
+    /// Bounding box of the canvas viewport expressed in the current user space, further
+    /// mapped through the inverse of `extra` when given.
+    fn canvas_bounds_in(&self, extra: Option<Matrix2D>) -> (f64, f64, f64, f64) {
+        let m = match extra {
+            Some(extra) => self.state.transform.multiply(&extra),
+            None => self.state.transform,
+        };
+        let [x, y, w, h] = self.view_box;
+        let Some(inv) = m.invert() else {
+            return (x, y, x + w, y + h);
+        };
+        let corners = [(x, y), (x + w, y), (x, y + h), (x + w, y + h)];
+        let mut bounds = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for (x, y) in corners {
+            let (ux, uy) = inv.transform_point(x, y);
+            bounds.0 = bounds.0.min(ux);
+            bounds.1 = bounds.1.min(uy);
+            bounds.2 = bounds.2.max(ux);
+            bounds.3 = bounds.3.max(uy);
+        }
+        bounds
+    }
+
         fn write_clip_path_def(
             &mut self,
-            id: &str,
             d: &str,
-            transform: Option<[f64; 6]>,
+            transform: Option<Matrix2D>,
             rule: FillRule,
         ) -> Result<()> {
-            self.writer
-                .write_event(Event::Start(BytesStart::new("defs")))?;
-
-            let mut clip = BytesStart::new("clipPath");
-            clip.push_attribute(("id", id));
-            self.writer.write_event(Event::Start(clip))?;
+            self.write_def(Event::Start(BytesStart::new("clipPath")));
 
             let mut path = BytesStart::new("path");
             path.push_attribute(("d", d));
@@ -242,117 +1081,205 @@ impl<W: Write> SvgCanvas<W> {
                     FillRule::EvenOdd => "evenodd",
                 },
             ));
-            if let Some([a, b, c, d_val, e, f]) = transform {
-                let transform_attr = format!("matrix({} {} {} {} {} {})", a, b, c, d_val, e, f);
-                path.push_attribute(("transform", transform_attr.as_str()));
+            if let Some(m) = transform {
+                push_transform_attr(&mut path, m, &self.options);
             }
-            self.writer.write_event(Event::Empty(path))?;
+            self.write_def(Event::Empty(path));
 
-            self.writer.write_event(Event::End(BytesEnd::new("clipPath")))?;
-            self.writer.write_event(Event::End(BytesEnd::new("defs")))?;
+            self.write_def(Event::End(BytesEnd::new("clipPath")));
             Ok(())
         }
 
-    fn encode_image_as_data_uri(&self, image: &dyn CanvasImageSource) -> Result<String> {
-        let width = image.width();
-        let height = image.height();
+    /// Stores `image` once as a definition at its natural size and returns its id, for
+    /// placement with `<use>`.
+    fn image_def(&mut self, image: &dyn CanvasImageSource) -> Result<String> {
         let data = image
             .data_rgba()
             .ok_or_else(|| Self::not_supported("image source lacks RGBA"))?;
+        let key = (image.width(), image.height(), digest(data));
+        if let Some(id) = self.image_ids.get(&key) {
+            return Ok(id.clone());
+        }
 
-        let mut png_bytes = Vec::new();
-        let mut encoder = PngEncoder::new(&mut png_bytes, width, height);
-        encoder.set_color(ColorType::Rgba);
-        let mut writer = encoder.write_header()?;
-        writer.write_image_data(data)?;
-        writer.finish()?;
-
-        let encoded = BASE64_STANDARD.encode(png_bytes);
-        Ok(format!("data:image/png;base64,{}", encoded))
+        let png = encode_png(image.width(), image.height(), data)?;
+        let href = match &mut self.image_sink {
+            Some(sink) => sink.store(&png, image.width(), image.height())?,
+            None => format!("data:image/png;base64,{}", BASE64_STANDARD.encode(png)),
+        };
+        let mut elem = BytesStart::new("image");
+        let w_attr = image.width().to_string();
+        let h_attr = image.height().to_string();
+        elem.push_attribute(("width", w_attr.as_str()));
+        elem.push_attribute(("height", h_attr.as_str()));
+        elem.push_attribute(("href", href.as_str()));
+        self.write_def(Event::Empty(elem));
+        let id = self.intern_def("img");
+        self.image_ids.insert(key, id.clone());
+        Ok(id)
     }
 
     fn flush_path_fill(&mut self, fill_rule: FillRule) -> Result<()> {
         if self.current_path.is_empty() {
             return Ok(());
         }
-        let fill_paint = self.state.fill_style.clone();
-        let fill = self.paint_to_str(&fill_paint)?;
-        let opacity_attr = self.state.global_alpha.to_string();
+        let d = path_data(&self.current_path, &self.options);
         let mut elem = BytesStart::new("path");
-        elem.push_attribute(("d", self.current_path.as_str()));
-        elem.push_attribute(("fill", fill.as_str()));
-        elem.push_attribute(("stroke", "none"));
-        elem.push_attribute((
-            "fill-rule",
-            match fill_rule {
-                FillRule::NonZero => "nonzero",
-                FillRule::EvenOdd => "evenodd",
-            },
-        ));
-        if self.state.global_alpha < 1.0 {
-            elem.push_attribute(("opacity", opacity_attr.as_str()));
-        }
-        self.apply_transform_attr(&mut elem);
+        elem.push_attribute(("d", d.as_str()));
+        self.apply_fill_attrs(&mut elem, self.path_transform)?;
+        let fill_rule_attr = match fill_rule {
+            FillRule::NonZero => "nonzero",
+            FillRule::EvenOdd => "evenodd",
+        };
+        self.options.push_attr(&mut elem, "fill-rule", fill_rule_attr, "nonzero");
+        self.apply_alpha_attr(&mut elem);
+        push_transform_attr(&mut elem, self.path_transform, &self.options);
         self.apply_clip_attr(&mut elem);
         self.write_empty(elem)
     }
 
     fn flush_path_stroke(&mut self) -> Result<()> {
-        if self.current_path.is_empty() {
+        // The line width and dashes are in the user space current when stroking.
+        if self.current_path.is_empty() || !self.rebase_path(self.state.transform) {
             return Ok(());
         }
-        let stroke_paint = self.state.stroke_style.clone();
-        let stroke = self.paint_to_str(&stroke_paint)?;
-        let stroke_width_attr = self.state.line_width.to_string();
-        let opacity_attr = self.state.global_alpha.to_string();
+        let d = path_data(&self.current_path, &self.options);
         let mut elem = BytesStart::new("path");
-        elem.push_attribute(("d", self.current_path.as_str()));
+        elem.push_attribute(("d", d.as_str()));
+        self.apply_stroke_attrs(&mut elem, self.path_transform)?;
+        self.apply_alpha_attr(&mut elem);
+        push_transform_attr(&mut elem, self.path_transform, &self.options);
+        self.apply_clip_attr(&mut elem);
+        self.write_empty(elem)
+    }
+
+    /// Fill paint for an element drawn with `element_transform`.
+    fn apply_fill_attrs(&mut self, elem: &mut BytesStart<'_>, element_transform: Matrix2D) -> Result<()> {
+        let fill_paint = self.state.fill_style.clone();
+        let fill = self.paint_to_str(&fill_paint, element_transform)?;
+        elem.push_attribute(("fill", fill.as_str()));
+        Ok(())
+    }
+
+    /// Stroke paint and every line style, so all stroked elements render alike.
+    fn apply_stroke_attrs(&mut self, elem: &mut BytesStart<'_>, element_transform: Matrix2D) -> Result<()> {
+        let stroke_paint = self.state.stroke_style.clone();
+        let stroke = self.paint_to_str(&stroke_paint, element_transform)?;
+        let stroke_width_attr = self.options.num(self.state.line_width);
         elem.push_attribute(("fill", "none"));
         elem.push_attribute(("stroke", stroke.as_str()));
-        elem.push_attribute(("stroke-width", stroke_width_attr.as_str()));
-        elem.push_attribute((
-            "stroke-linecap",
-            match self.state.line_cap {
-                LineCap::Butt => "butt",
-                LineCap::Round => "round",
-                LineCap::Square => "square",
-            },
-        ));
-        elem.push_attribute((
-            "stroke-linejoin",
-            match self.state.line_join {
-                LineJoin::Round => "round",
-                LineJoin::Bevel => "bevel",
-                LineJoin::Miter => "miter",
-            },
-        ));
-        if self.state.global_alpha < 1.0 {
-            elem.push_attribute(("opacity", opacity_attr.as_str()));
-        }
+        self.options.push_attr(elem, "stroke-width", stroke_width_attr.as_str(), "1");
+        let line_cap_attr = match self.state.line_cap {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        };
+        self.options.push_attr(elem, "stroke-linecap", line_cap_attr, "butt");
+        let line_join_attr = match self.state.line_join {
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+            LineJoin::Miter => "miter",
+        };
+        self.options.push_attr(elem, "stroke-linejoin", line_join_attr, "miter");
+        // SVG defaults to 4 where Canvas defaults to 10, so this is usually written.
+        let miter_limit_attr = self.options.num(self.state.miter_limit);
+        self.options.push_attr(elem, "stroke-miterlimit", miter_limit_attr.as_str(), "4");
         if !self.state.line_dash.is_empty() {
             let dash = self
                 .state
                 .line_dash
                 .iter()
-                .map(|v| v.to_string())
+                .map(|&v| self.options.num(v))
                 .collect::<Vec<_>>()
                 .join(" ");
             elem.push_attribute(("stroke-dasharray", dash.as_str()));
         }
         if self.state.line_dash_offset != 0.0 {
-            let dash_offset_attr = self.state.line_dash_offset.to_string();
+            let dash_offset_attr = self.options.num(self.state.line_dash_offset);
             elem.push_attribute(("stroke-dashoffset", dash_offset_attr.as_str()));
         }
+        Ok(())
+    }
+
+    fn apply_alpha_attr(&self, elem: &mut BytesStart<'_>) {
+        if self.state.global_alpha < 1.0 {
+            let opacity_attr = self.options.num(self.state.global_alpha);
+            elem.push_attribute(("opacity", opacity_attr.as_str()));
+        }
+    }
+
+    fn write_text(&mut self, text: &str, x: f64, y: f64, stroke: bool) -> Result<()> {
+        let mut elem = BytesStart::new("text");
+        let x_attr = self.options.num(x);
+        let y_attr = self.options.num(y);
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
+        if stroke {
+            self.apply_stroke_attrs(&mut elem, self.state.transform)?;
+        } else {
+            self.apply_fill_attrs(&mut elem, self.state.transform)?;
+        }
+        elem.push_attribute(("font", self.state.font.as_str()));
+        elem.push_attribute((
+            "text-anchor",
+            match self.state.text_align {
+                TextAlign::Left | TextAlign::Start => "start",
+                TextAlign::Center => "middle",
+                TextAlign::Right | TextAlign::End => "end",
+            },
+        ));
+        elem.push_attribute((
+            "dominant-baseline",
+            match self.state.text_baseline {
+                TextBaseline::Top => "text-before-edge",
+                TextBaseline::Hanging => "hanging",
+                TextBaseline::Middle => "middle",
+                TextBaseline::Alphabetic => "alphabetic",
+                TextBaseline::Ideographic => "ideographic",
+                TextBaseline::Bottom => "text-after-edge",
+            },
+        ));
+        self.apply_alpha_attr(&mut elem);
         self.apply_transform_attr(&mut elem);
         self.apply_clip_attr(&mut elem);
-        self.write_empty(elem)
+        self.write_start(elem)?;
+        self.writer.write_event(Event::Text(BytesText::new(text)))?;
+        self.writer.write_event(Event::End(BytesEnd::new("text")))?;
+        Ok(())
+    }
+
+    /// Adds a segment given in the current user space. The path stays in the space it was
+    /// started in, so a segment added after the transform changed is mapped into it;
+    /// Canvas fixes every point with the transform current when it is added.
+    fn push_path(&mut self, segment: PathSegment) {
+        let mut segment = segment;
+        if self.current_path.is_empty() {
+            self.path_transform = self.state.transform;
+        } else if self.state.transform != self.path_transform {
+            if self.path_transform.invert().is_none() {
+                self.rebase_path(Matrix2D::IDENTITY);
+            }
+            if let Some(inverse) = self.path_transform.invert() {
+                segment = segment.transformed(&inverse.multiply(&self.state.transform));
+            }
+        }
+        self.current_path.push(segment);
     }
 
-    fn push_path(&mut self, cmd: &str) {
-        if !self.current_path.is_empty() {
-            self.current_path.push(' ');
+    /// Re-expresses the current path in the user space of `target`. Returns false, leaving
+    /// the path alone, when `target` cannot be inverted.
+    fn rebase_path(&mut self, target: Matrix2D) -> bool {
+        if target == self.path_transform {
+            return true;
         }
-        self.current_path.push_str(cmd);
+        let Some(inverse) = target.invert() else {
+            return false;
+        };
+        let m = inverse.multiply(&self.path_transform);
+        for segment in &mut self.current_path {
+            *segment = segment.transformed(&m);
+        }
+        self.path_transform = target;
+        true
     }
 
     fn set_current_point(&mut self, x: f64, y: f64) {
@@ -366,11 +1293,30 @@ impl<W: Write> SvgCanvas<W> {
         Ok(())
     }
 
+    /// Joins the current point to (`x`, `y`) with a line, or starts a subpath there when
+    /// there is none, as arcs do before they begin.
+    fn connect_to(&mut self, x: f64, y: f64) -> Result<()> {
+        match self.current_point {
+            Some((px, py)) => {
+                if (px - x).abs() > 1e-9 || (py - y).abs() > 1e-9 {
+                    self.line_to(x, y)?;
+                }
+                Ok(())
+            }
+            None => self.move_to(x, y),
+        }
+    }
+
+    /// Appends the part of the ellipse around (`cx`, `cy`), rotated by `rotation` radians,
+    /// from `start_angle` to `end_angle`. The current point must already be at its start.
+    #[allow(clippy::too_many_arguments)]
     fn append_arc_segments(
         &mut self,
         cx: f64,
         cy: f64,
-        radius: f64,
+        rx: f64,
+        ry: f64,
+        rotation: f64,
         start_angle: f64,
         end_angle: f64,
         ccw: bool,
@@ -391,6 +1337,33 @@ impl<W: Write> SvgCanvas<W> {
             return Ok(());
         }
 
+        let (sin_r, cos_r) = rotation.sin_cos();
+        let point = |angle: f64| {
+            let (sin_a, cos_a) = angle.sin_cos();
+            (
+                cx + rx * cos_a * cos_r - ry * sin_a * sin_r,
+                cy + rx * cos_a * sin_r + ry * sin_a * cos_r,
+            )
+        };
+        if rx == 0.0 || ry == 0.0 {
+            // A flat ellipse is a line traced back and forth, turning at quarter turns,
+            // which SVG's arc command would skip straight across.
+            let quarter = std::f64::consts::FRAC_PI_2;
+            let end = start_angle + delta;
+            let mut angle = start_angle;
+            while (end - angle) * delta.signum() > 1e-12 {
+                angle = if delta > 0.0 {
+                    (((angle / quarter).floor() + 1.0) * quarter).min(end)
+                } else {
+                    (((angle / quarter).ceil() - 1.0) * quarter).max(end)
+                };
+                let (x, y) = point(angle);
+                self.push_path(PathSegment::LineTo(x, y));
+                self.set_current_point(x, y);
+            }
+            return Ok(());
+        }
+
         let mut remaining = delta;
         let mut current_angle = start_angle;
         let max_step = std::f64::consts::PI; // keep segments <= 180deg to avoid degenerate arcs
@@ -403,19 +1376,16 @@ impl<W: Write> SvgCanvas<W> {
             };
 
             let next_angle = current_angle + step;
-            let end_x = cx + radius * next_angle.cos();
-            let end_y = cy + radius * next_angle.sin();
-            let large_arc = if step.abs() >= std::f64::consts::PI - 1e-9 {
-                1
-            } else {
-                0
-            };
-            let sweep_flag = if step >= 0.0 { 1 } else { 0 };
-
-            self.push_path(&format!(
-                "A {} {} 0 {} {} {} {}",
-                radius, radius, large_arc, sweep_flag, end_x, end_y
-            ));
+            let (end_x, end_y) = point(next_angle);
+            self.push_path(PathSegment::ArcTo {
+                rx,
+                ry,
+                rotation: rotation.to_degrees(),
+                large: step.abs() >= std::f64::consts::PI - 1e-9,
+                sweep: step >= 0.0,
+                x: end_x,
+                y: end_y,
+            });
             self.set_current_point(end_x, end_y);
 
             current_angle = next_angle;
@@ -425,26 +1395,261 @@ impl<W: Write> SvgCanvas<W> {
         Ok(())
     }
 
+    /// Hints how viewers should resample images. With smoothing disabled the SVG 1.1
+    /// keyword is kept as a fallback for renderers that ignore the CSS `pixelated` value.
+    fn apply_image_rendering_attr(&self, elem: &mut BytesStart<'_>) {
+        if !self.state.image_smoothing_enabled {
+            elem.push_attribute(("image-rendering", "optimizeSpeed"));
+            elem.push_attribute(("style", "image-rendering:pixelated"));
+            return;
+        }
+        match self.state.image_smoothing_quality {
+            // "low" is the Canvas default and "optimizeSpeed" means nearest-neighbour to
+            // some viewers, so only the high setting is worth a hint.
+            ImageSmoothingQuality::Low | ImageSmoothingQuality::Medium => {}
+            ImageSmoothingQuality::High => elem.push_attribute(("image-rendering", "optimizeQuality")),
+        }
+    }
+
     fn apply_transform_attr(&self, elem: &mut BytesStart<'_>) {
-        let [a, b, c, d, e, f] = self.state.transform;
-        if (a, b, c, d, e, f) != (1.0, 0.0, 0.0, 1.0, 0.0, 0.0) {
-            let transform_attr = format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f);
-            elem.push_attribute(("transform", transform_attr.as_str()));
+        push_transform_attr(elem, self.state.transform, &self.options);
+    }
+
+    fn multiply_transform(&mut self, m: Matrix2D) {
+        self.state.transform = self.state.transform.multiply(&m);
+    }
+}
+
+fn encode_png(width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>> {
+    let mut png_bytes = Vec::new();
+    let mut encoder = PngEncoder::new(&mut png_bytes, width, height);
+    encoder.set_color(ColorType::Rgba);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(png_bytes)
+}
+
+/// What encloses the drawing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Root {
+    /// XML declaration and `<svg>` root.
+    Document,
+    /// `<svg>` element only.
+    Element,
+    /// Nothing; the caller provides the enclosing element.
+    None,
+}
+
+fn root_element(options: &SvgOptions, width: f64, height: f64, view_box: [f64; 4]) -> BytesStart<'static> {
+    let unit = options.units.suffix();
+    let width_attr = format!("{}{unit}", options.num(width));
+    let height_attr = format!("{}{unit}", options.num(height));
+    let view_box_attr = view_box.map(|v| options.num(v)).join(" ");
+
+    let mut start = BytesStart::new("svg");
+    start.push_attribute(("xmlns", "http://www.w3.org/2000/svg"));
+    start.push_attribute(("version", "1.1"));
+    start.push_attribute(("width", width_attr.as_str()));
+    start.push_attribute(("height", height_attr.as_str()));
+    start.push_attribute(("viewBox", view_box_attr.as_str()));
+    if let Some(value) = &options.preserve_aspect_ratio {
+        start.push_attribute(("preserveAspectRatio", value.as_str()));
+    }
+    start
+}
+
+/// Writes an interned definition, giving its outermost element `id`.
+fn write_def_events<T: Write>(writer: &mut Writer<T>, id: &str, def: &[Event<'static>]) -> Result<()> {
+    for (i, event) in def.iter().enumerate() {
+        match event {
+            Event::Start(elem) if i == 0 => writer.write_event(Event::Start(with_id(elem, id)))?,
+            Event::Empty(elem) if i == 0 => writer.write_event(Event::Empty(with_id(elem, id)))?,
+            other => writer.write_event(other.borrow())?,
         }
     }
+    Ok(())
+}
+
+/// Copies `elem`, putting `id` in front of its attributes.
+fn with_id(elem: &BytesStart<'_>, id: &str) -> BytesStart<'static> {
+    let name = String::from_utf8_lossy(elem.name().as_ref()).into_owned();
+    let mut out = BytesStart::new(name);
+    out.push_attribute(("id", id));
+    out.extend_attributes(elem.attributes().flatten());
+    out
+}
+
+/// Whether `name` can be used as an XML attribute name.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+fn matrix_attr(m: Matrix2D, options: &SvgOptions) -> String {
+    let [a, b, c, d, e, f] = m.to_array().map(|v| options.num(v));
+    format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f)
+}
+
+fn push_transform_attr(elem: &mut BytesStart<'_>, m: Matrix2D, options: &SvgOptions) {
+    if m != Matrix2D::IDENTITY {
+        let transform_attr = matrix_attr(m, options);
+        elem.push_attribute(("transform", transform_attr.as_str()));
+    }
+}
 
-    fn multiply_transform(&mut self, m: [f64; 6]) {
-        let [a, b, c, d, e, f] = self.state.transform;
-        let [na, nb, nc, nd, ne, nf] = m;
-        self.state.transform = [
-            a * na + c * nb,
-            b * na + d * nb,
-            a * nc + c * nd,
-            b * nc + d * nd,
-            a * ne + c * nf + e,
-            b * ne + d * nf + f,
-        ];
+/// Path data for `segments`, absolute or relative as `options` ask. Relative offsets are
+/// taken between rounded positions so that rounding errors do not accumulate.
+fn path_data(segments: &[PathSegment], options: &SvgOptions) -> String {
+    let n = |v: f64| options.num(v);
+    if !options.relative_paths {
+        let parts: Vec<String> = segments
+            .iter()
+            .map(|&segment| match segment {
+                PathSegment::MoveTo(x, y) => format!("M {} {}", n(x), n(y)),
+                PathSegment::LineTo(x, y) => format!("L {} {}", n(x), n(y)),
+                PathSegment::CubicTo(x1, y1, x2, y2, x, y) => {
+                    format!("C {} {}, {} {}, {} {}", n(x1), n(y1), n(x2), n(y2), n(x), n(y))
+                }
+                PathSegment::QuadTo(x1, y1, x, y) => format!("Q {} {}, {} {}", n(x1), n(y1), n(x), n(y)),
+                PathSegment::ArcTo {
+                    rx,
+                    ry,
+                    rotation,
+                    large,
+                    sweep,
+                    x,
+                    y,
+                } => format!(
+                    "A {} {} {} {} {} {} {}",
+                    n(rx),
+                    n(ry),
+                    n(rotation),
+                    u8::from(large),
+                    u8::from(sweep),
+                    n(x),
+                    n(y)
+                ),
+                PathSegment::Close => "Z".to_string(),
+            })
+            .collect();
+        return parts.join(" ");
+    }
+
+    let mut parts = Vec::with_capacity(segments.len());
+    // Current point and subpath start, both already rounded.
+    let (mut cx, mut cy) = (0.0, 0.0);
+    let (mut sx, mut sy) = (0.0, 0.0);
+    for &segment in segments {
+        let (x, y) = match segment {
+            PathSegment::MoveTo(x, y)
+            | PathSegment::LineTo(x, y)
+            | PathSegment::CubicTo(_, _, _, _, x, y)
+            | PathSegment::QuadTo(_, _, x, y)
+            | PathSegment::ArcTo { x, y, .. } => (options.round(x), options.round(y)),
+            PathSegment::Close => (sx, sy),
+        };
+        let dx = |v: f64| n(options.round(v) - cx);
+        let dy = |v: f64| n(options.round(v) - cy);
+        parts.push(match segment {
+            PathSegment::MoveTo(..) => {
+                (sx, sy) = (x, y);
+                format!("m {} {}", n(x - cx), n(y - cy))
+            }
+            PathSegment::LineTo(..) if y == cy => format!("h {}", n(x - cx)),
+            PathSegment::LineTo(..) if x == cx => format!("v {}", n(y - cy)),
+            PathSegment::LineTo(..) => format!("l {} {}", n(x - cx), n(y - cy)),
+            PathSegment::CubicTo(x1, y1, x2, y2, ..) => format!(
+                "c {} {}, {} {}, {} {}",
+                dx(x1),
+                dy(y1),
+                dx(x2),
+                dy(y2),
+                n(x - cx),
+                n(y - cy)
+            ),
+            PathSegment::QuadTo(x1, y1, ..) => {
+                format!("q {} {}, {} {}", dx(x1), dy(y1), n(x - cx), n(y - cy))
+            }
+            PathSegment::ArcTo {
+                rx,
+                ry,
+                rotation,
+                large,
+                sweep,
+                ..
+            } => format!(
+                "a {} {} {} {} {} {} {}",
+                n(rx),
+                n(ry),
+                n(rotation),
+                u8::from(large),
+                u8::from(sweep),
+                n(x - cx),
+                n(y - cy)
+            ),
+            PathSegment::Close => "z".to_string(),
+        });
+        (cx, cy) = (x, y);
     }
+    parts.join(" ")
+}
+
+/// Path data for the convex hull of two circles, which is the area swept by the circles
+/// interpolated between them.
+fn circle_hull_path(c0: (f64, f64, f64), c1: (f64, f64, f64), options: &SvgOptions) -> String {
+    let n = |v: f64| options.num(v);
+    let (x0, y0, r0) = c0;
+    let (x1, y1, r1) = c1;
+    let dist = (x1 - x0).hypot(y1 - y0);
+    if dist <= (r0 - r1).abs() {
+        let (x, y, r) = if r0 >= r1 { c0 } else { c1 };
+        return format!(
+            "M{} {} A{} {} 0 1 0 {} {} A{} {} 0 1 0 {} {} Z",
+            n(x + r),
+            n(y),
+            n(r),
+            n(r),
+            n(x - r),
+            n(y),
+            n(r),
+            n(r),
+            n(x + r),
+            n(y)
+        );
+    }
+    // The outer tangents touch both circles at angles theta ± alpha.
+    let theta = (y1 - y0).atan2(x1 - x0);
+    let alpha = ((r0 - r1) / dist).clamp(-1.0, 1.0).acos();
+    let point = |x: f64, y: f64, r: f64, angle: f64| (x + r * angle.cos(), y + r * angle.sin());
+    let a0 = point(x0, y0, r0, theta + alpha);
+    let a1 = point(x1, y1, r1, theta + alpha);
+    let b1 = point(x1, y1, r1, theta - alpha);
+    let b0 = point(x0, y0, r0, theta - alpha);
+    let large1 = u8::from(alpha > std::f64::consts::FRAC_PI_2);
+    let large0 = u8::from(alpha < std::f64::consts::FRAC_PI_2);
+    format!(
+        "M{} {} L{} {} A{} {} 0 {} 0 {} {} L{} {} A{} {} 0 {} 0 {} {} Z",
+        n(a0.0),
+        n(a0.1),
+        n(a1.0),
+        n(a1.1),
+        n(r1),
+        n(r1),
+        large1,
+        n(b1.0),
+        n(b1.1),
+        n(b0.0),
+        n(b0.1),
+        n(r0),
+        n(r0),
+        large0,
+        n(a0.0),
+        n(a0.1)
+    )
 }
 
 #[derive(Clone)]
@@ -469,7 +1674,7 @@ struct SvgState {
     text_align: TextAlign,
     text_baseline: TextBaseline,
     direction: Direction,
-    transform: [f64; 6],
+    transform: Matrix2D,
     clip_path: Option<String>,
 }
 
@@ -496,12 +1701,35 @@ impl Default for SvgState {
             text_align: TextAlign::Start,
             text_baseline: TextBaseline::Alphabetic,
             direction: Direction::Inherit,
-            transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            transform: Matrix2D::IDENTITY,
             clip_path: None,
         }
     }
 }
 
+impl<W: Write> CanvasSize for SvgCanvas<W> {
+    fn width(&self) -> Result<f64> {
+        Ok(self.width)
+    }
+
+    fn height(&self) -> Result<f64> {
+        Ok(self.height)
+    }
+
+    /// Streamed output cannot be taken back, so the size can only change before anything
+    /// has been drawn.
+    fn set_size(&mut self, width: f64, height: f64) -> Result<()> {
+        validate_size(width, height)?;
+        if self.started {
+            return Err(Self::not_supported("resizing after drawing has started"));
+        }
+        self.width = width;
+        self.height = height;
+        self.view_box = self.options.view_box.unwrap_or([0.0, 0.0, width, height]);
+        self.reset()
+    }
+}
+
 impl<W: Write> CanvasState for SvgCanvas<W> {
     fn save(&mut self) -> Result<()> {
         self.stack.push(self.state.clone());
@@ -515,12 +1743,21 @@ impl<W: Write> CanvasState for SvgCanvas<W> {
         Ok(())
     }
 
+    /// Returns every property, the state stack and the path to their defaults. What has
+    /// already been written stays in the document.
     fn reset(&mut self) -> Result<()> {
         self.state = SvgState::default();
+        self.stack.clear();
+        self.current_path.clear();
+        self.current_point = None;
+        self.subpath_start = None;
         Ok(())
     }
 
     fn set_global_alpha(&mut self, value: f64) -> Result<()> {
+        if !validate::unit_interval(value) {
+            return Ok(());
+        }
         self.state.global_alpha = value;
         Ok(())
     }
@@ -559,39 +1796,65 @@ impl<W: Write> CanvasState for SvgCanvas<W> {
 
 impl<W: Write> CanvasTransforms for SvgCanvas<W> {
     fn scale(&mut self, x: f64, y: f64) -> Result<()> {
-        self.multiply_transform([x, 0.0, 0.0, y, 0.0, 0.0]);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(x, 0.0, 0.0, y, 0.0, 0.0));
         Ok(())
     }
 
     fn rotate(&mut self, radians: f64) -> Result<()> {
+        if !validate::finite(&[radians]) {
+            return Ok(());
+        }
         let (s, c) = radians.sin_cos();
-        self.multiply_transform([c, s, -s, c, 0.0, 0.0]);
+        self.multiply_transform(Matrix2D::new(c, s, -s, c, 0.0, 0.0));
         Ok(())
     }
 
     fn translate(&mut self, x: f64, y: f64) -> Result<()> {
-        self.multiply_transform([1.0, 0.0, 0.0, 1.0, x, y]);
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(1.0, 0.0, 0.0, 1.0, x, y));
         Ok(())
     }
 
     fn transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
-        self.multiply_transform([a, b, c, d, e, f]);
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
+        self.multiply_transform(Matrix2D::new(a, b, c, d, e, f));
         Ok(())
     }
 
     fn set_transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Result<()> {
-        self.state.transform = [a, b, c, d, e, f];
+        if !validate::finite(&[a, b, c, d, e, f]) {
+            return Ok(());
+        }
+        self.state.transform = Matrix2D::new(a, b, c, d, e, f);
         Ok(())
     }
 
     fn reset_transform(&mut self) -> Result<()> {
-        self.state.transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        self.state.transform = Matrix2D::IDENTITY;
         Ok(())
     }
+
+    fn get_transform(&self) -> Result<Matrix2D> {
+        Ok(self.state.transform)
+    }
+
+    fn set_transform_matrix(&mut self, matrix: &Matrix2D) -> Result<()> {
+        self.set_transform(matrix.a, matrix.b, matrix.c, matrix.d, matrix.e, matrix.f)
+    }
 }
 
 impl<W: Write> crate::api::CanvasCompositing for SvgCanvas<W> {
     fn set_shadow_offset_x(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.shadow_offset_x = value;
         Ok(())
     }
@@ -601,6 +1864,9 @@ impl<W: Write> crate::api::CanvasCompositing for SvgCanvas<W> {
     }
 
     fn set_shadow_offset_y(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.shadow_offset_y = value;
         Ok(())
     }
@@ -610,6 +1876,9 @@ impl<W: Write> crate::api::CanvasCompositing for SvgCanvas<W> {
     }
 
     fn set_shadow_blur(&mut self, value: f64) -> Result<()> {
+        if !validate::non_negative(value) {
+            return Ok(());
+        }
         self.state.shadow_blur = value;
         Ok(())
     }
@@ -630,6 +1899,9 @@ impl<W: Write> crate::api::CanvasCompositing for SvgCanvas<W> {
 
 impl<W: Write> CanvasLineStyles for SvgCanvas<W> {
     fn set_line_width(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.state.line_width = value;
         Ok(())
     }
@@ -657,6 +1929,9 @@ impl<W: Write> CanvasLineStyles for SvgCanvas<W> {
     }
 
     fn set_miter_limit(&mut self, value: f64) -> Result<()> {
+        if !validate::positive(value) {
+            return Ok(());
+        }
         self.state.miter_limit = value;
         Ok(())
     }
@@ -666,7 +1941,10 @@ impl<W: Write> CanvasLineStyles for SvgCanvas<W> {
     }
 
     fn set_line_dash(&mut self, segments: Vec<f64>) -> Result<()> {
-        self.state.line_dash = segments;
+        if !validate::line_dash(&segments) {
+            return Ok(());
+        }
+        self.state.line_dash = validate::normalize_line_dash(segments);
         Ok(())
     }
 
@@ -675,6 +1953,9 @@ impl<W: Write> CanvasLineStyles for SvgCanvas<W> {
     }
 
     fn set_line_dash_offset(&mut self, value: f64) -> Result<()> {
+        if !validate::finite(&[value]) {
+            return Ok(());
+        }
         self.state.line_dash_offset = value;
         Ok(())
     }
@@ -710,6 +1991,7 @@ impl<W: Write> CanvasFillStrokeStyles for SvgCanvas<W> {
         x1: f64,
         y1: f64,
     ) -> Result<crate::api::CanvasGradient> {
+        validate::gradient_args(&[x0, y0, x1, y1])?;
         Ok(crate::api::CanvasGradient {
             kind: GradientKind::Linear { x0, y0, x1, y1 },
             stops: Vec::new(),
@@ -725,6 +2007,8 @@ impl<W: Write> CanvasFillStrokeStyles for SvgCanvas<W> {
         y1: f64,
         r1: f64,
     ) -> Result<crate::api::CanvasGradient> {
+        validate::gradient_args(&[x0, y0, r0, x1, y1, r1])?;
+        validate::radii(&[r0, r1])?;
         Ok(crate::api::CanvasGradient {
             kind: GradientKind::Radial {
                 x0,
@@ -738,15 +2022,20 @@ impl<W: Write> CanvasFillStrokeStyles for SvgCanvas<W> {
         })
     }
 
+    fn create_conic_gradient(&mut self, start_angle: f64, x: f64, y: f64) -> Result<crate::api::CanvasGradient> {
+        validate::gradient_args(&[start_angle, x, y])?;
+        Ok(crate::api::CanvasGradient {
+            kind: GradientKind::Conic { start_angle, x, y },
+            stops: Vec::new(),
+        })
+    }
+
     fn create_pattern(
         &mut self,
-        _image: &dyn CanvasImageSource,
+        image: &dyn CanvasImageSource,
         repetition: PatternRepetition,
     ) -> Result<crate::api::CanvasPattern> {
-        Ok(crate::api::CanvasPattern {
-            repetition,
-            transform: None,
-        })
+        crate::api::CanvasPattern::new(image, repetition)
     }
 }
 
@@ -757,45 +2046,42 @@ impl<W: Write> CanvasRectangles for SvgCanvas<W> {
     }
 
     fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        let fill_paint = self.state.fill_style.clone();
-        let fill = self.paint_to_str(&fill_paint)?;
-        let x_attr = x.to_string();
-        let y_attr = y.to_string();
-        let w_attr = w.to_string();
-        let h_attr = h.to_string();
-        let opacity_attr = self.state.global_alpha.to_string();
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
+        let x_attr = self.options.num(x);
+        let y_attr = self.options.num(y);
+        let w_attr = self.options.num(w);
+        let h_attr = self.options.num(h);
 
         let mut elem = BytesStart::new("rect");
-        elem.push_attribute(("x", x_attr.as_str()));
-        elem.push_attribute(("y", y_attr.as_str()));
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
         elem.push_attribute(("width", w_attr.as_str()));
         elem.push_attribute(("height", h_attr.as_str()));
-        elem.push_attribute(("fill", fill.as_str()));
-        if self.state.global_alpha < 1.0 {
-            elem.push_attribute(("opacity", opacity_attr.as_str()));
-        }
+        self.apply_fill_attrs(&mut elem, self.state.transform)?;
+        self.apply_alpha_attr(&mut elem);
         self.apply_transform_attr(&mut elem);
         self.apply_clip_attr(&mut elem);
         self.write_empty(elem)
     }
 
     fn stroke_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        let stroke_paint = self.state.stroke_style.clone();
-        let stroke = self.paint_to_str(&stroke_paint)?;
-        let x_attr = x.to_string();
-        let y_attr = y.to_string();
-        let w_attr = w.to_string();
-        let h_attr = h.to_string();
-        let stroke_width_attr = self.state.line_width.to_string();
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
+        let x_attr = self.options.num(x);
+        let y_attr = self.options.num(y);
+        let w_attr = self.options.num(w);
+        let h_attr = self.options.num(h);
 
         let mut elem = BytesStart::new("rect");
-        elem.push_attribute(("x", x_attr.as_str()));
-        elem.push_attribute(("y", y_attr.as_str()));
+        self.options.push_attr(&mut elem, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", y_attr.as_str(), "0");
         elem.push_attribute(("width", w_attr.as_str()));
         elem.push_attribute(("height", h_attr.as_str()));
-        elem.push_attribute(("fill", "none"));
-        elem.push_attribute(("stroke", stroke.as_str()));
-        elem.push_attribute(("stroke-width", stroke_width_attr.as_str()));
+        self.apply_stroke_attrs(&mut elem, self.state.transform)?;
+        self.apply_alpha_attr(&mut elem);
         self.apply_transform_attr(&mut elem);
         self.apply_clip_attr(&mut elem);
         self.write_empty(elem)
@@ -811,7 +2097,7 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
     }
 
     fn close_path(&mut self) -> Result<()> {
-        self.push_path("Z");
+        self.push_path(PathSegment::Close);
         if let Some(start) = self.subpath_start {
             self.set_current_point(start.0, start.1);
         }
@@ -819,17 +2105,23 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
     }
 
     fn move_to(&mut self, x: f64, y: f64) -> Result<()> {
-        self.push_path(&format!("M {} {}", x, y));
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
+        self.push_path(PathSegment::MoveTo(x, y));
         self.subpath_start = Some((x, y));
         self.set_current_point(x, y);
         Ok(())
     }
 
     fn line_to(&mut self, x: f64, y: f64) -> Result<()> {
+        if !validate::finite(&[x, y]) {
+            return Ok(());
+        }
         if self.current_point.is_none() {
             self.move_to(0.0, 0.0)?;
         }
-        self.push_path(&format!("L {} {}", x, y));
+        self.push_path(PathSegment::LineTo(x, y));
         self.set_current_point(x, y);
         Ok(())
     }
@@ -843,18 +2135,21 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
         x: f64,
         y: f64,
     ) -> Result<()> {
+        if !validate::finite(&[cp1x, cp1y, cp2x, cp2y, x, y]) {
+            return Ok(());
+        }
         self.ensure_subpath()?;
-        self.push_path(&format!(
-            "C {} {}, {} {}, {} {}",
-            cp1x, cp1y, cp2x, cp2y, x, y
-        ));
+        self.push_path(PathSegment::CubicTo(cp1x, cp1y, cp2x, cp2y, x, y));
         self.set_current_point(x, y);
         Ok(())
     }
 
     fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) -> Result<()> {
+        if !validate::finite(&[cpx, cpy, x, y]) {
+            return Ok(());
+        }
         self.ensure_subpath()?;
-        self.push_path(&format!("Q {} {}, {} {}", cpx, cpy, x, y));
+        self.push_path(PathSegment::QuadTo(cpx, cpy, x, y));
         self.set_current_point(x, y);
         Ok(())
     }
@@ -868,28 +2163,24 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
         end_angle: f64,
         ccw: bool,
     ) -> Result<()> {
-        if radius <= 0.0 {
+        if !validate::finite(&[x, y, radius, start_angle, end_angle]) {
             return Ok(());
         }
-
-        let start_x = x + radius * start_angle.cos();
-        let start_y = y + radius * start_angle.sin();
-
-        match self.current_point {
-            Some((px, py)) => {
-                if (px - start_x).abs() > 1e-9 || (py - start_y).abs() > 1e-9 {
-                    self.line_to(start_x, start_y)?;
-                }
-            }
-            None => {
-                self.move_to(start_x, start_y)?;
-            }
+        validate::radii(&[radius])?;
+        if radius == 0.0 {
+            // The arc shrinks to its center, which still joins the path.
+            return self.connect_to(x, y);
         }
 
-        self.append_arc_segments(x, y, radius, start_angle, end_angle, ccw)
+        self.connect_to(x + radius * start_angle.cos(), y + radius * start_angle.sin())?;
+        self.append_arc_segments(x, y, radius, radius, 0.0, start_angle, end_angle, ccw)
     }
 
     fn arc_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, radius: f64) -> Result<()> {
+        if !validate::finite(&[x1, y1, x2, y2, radius]) {
+            return Ok(());
+        }
+        validate::radii(&[radius])?;
         let (x0, y0) = match self.current_point {
             Some(p) => p,
             None => {
@@ -941,7 +2232,7 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
         let end_ang = (tp2.1 - center.1).atan2(tp2.0 - center.0);
 
         self.line_to(tp1.0, tp1.1)?;
-        self.append_arc_segments(center.0, center.1, radius, start_ang, end_ang, cross < 0.0)
+        self.append_arc_segments(center.0, center.1, radius, radius, 0.0, start_ang, end_ang, cross < 0.0)
     }
 
     fn ellipse(
@@ -950,97 +2241,69 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
         y: f64,
         radius_x: f64,
         radius_y: f64,
-        _rotation: f64,
-        _start_angle: f64,
-        _end_angle: f64,
-        _ccw: bool,
+        rotation: f64,
+        start_angle: f64,
+        end_angle: f64,
+        ccw: bool,
     ) -> Result<()> {
-        // Approximate as a standalone ellipse element.
-        let mut elem = BytesStart::new("ellipse");
-        let cx_attr = x.to_string();
-        let cy_attr = y.to_string();
-        let rx_attr = radius_x.to_string();
-        let ry_attr = radius_y.to_string();
-        let fill_paint = self.state.fill_style.clone();
-        let fill = self.paint_to_str(&fill_paint)?;
-        elem.push_attribute(("cx", cx_attr.as_str()));
-        elem.push_attribute(("cy", cy_attr.as_str()));
-        elem.push_attribute(("rx", rx_attr.as_str()));
-        elem.push_attribute(("ry", ry_attr.as_str()));
-        elem.push_attribute(("fill", fill.as_str()));
-        self.apply_transform_attr(&mut elem);
-        self.apply_clip_attr(&mut elem);
-        self.write_empty(elem)
+        if !validate::finite(&[x, y, radius_x, radius_y, rotation, start_angle, end_angle]) {
+            return Ok(());
+        }
+        validate::radii(&[radius_x, radius_y])?;
+        let (sin_r, cos_r) = rotation.sin_cos();
+        let (sin_a, cos_a) = start_angle.sin_cos();
+        self.connect_to(
+            x + radius_x * cos_a * cos_r - radius_y * sin_a * sin_r,
+            y + radius_x * cos_a * sin_r + radius_y * sin_a * cos_r,
+        )?;
+        self.append_arc_segments(x, y, radius_x, radius_y, rotation, start_angle, end_angle, ccw)
     }
 
     fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<()> {
-        self.push_path(&format!("M {} {} h {} v {} h {} Z", x, y, w, h, -w));
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
+        }
+        self.push_path(PathSegment::MoveTo(x, y));
+        self.push_path(PathSegment::LineTo(x + w, y));
+        self.push_path(PathSegment::LineTo(x + w, y + h));
+        self.push_path(PathSegment::LineTo(x, y + h));
+        self.push_path(PathSegment::Close);
         self.subpath_start = Some((x, y));
         self.set_current_point(x, y);
         Ok(())
     }
 
     fn round_rect(&mut self, x: f64, y: f64, w: f64, h: f64, radii: &[f64]) -> Result<()> {
-        let mut corner = [0.0; 4];
-        match radii.len() {
-            0 => {}
-            1 => corner.fill(radii[0]),
-            2 => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[0];
-                corner[3] = radii[1];
-            }
-            3 => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[2];
-                corner[3] = radii[1];
-            }
-            _ => {
-                corner[0] = radii[0];
-                corner[1] = radii[1];
-                corner[2] = radii[2];
-                corner[3] = radii[3];
-            }
+        if !validate::finite(&[x, y, w, h]) {
+            return Ok(());
         }
-
-        let limit = (w.abs() / 2.0).min(h.abs() / 2.0);
-        for r in &mut corner {
-            if *r < 0.0 {
-                *r = 0.0;
+        let Some(radii) = validate::round_rect_radii(radii)? else {
+            return Ok(());
+        };
+        let corners = flatten::round_rect_corners(x, y, w, h, radii);
+
+        let (start_x, start_y) = corners[3].point(corners[3].to);
+        self.move_to(start_x, start_y)?;
+        for corner in &corners {
+            let (from, to) = (corner.point(corner.from), corner.point(corner.to));
+            if self.current_point != Some(from) {
+                self.line_to(from.0, from.1)?;
             }
-            if *r > limit {
-                *r = limit;
+            if corner.radius > 0.0 {
+                self.push_path(PathSegment::ArcTo {
+                    rx: corner.radius,
+                    ry: corner.radius,
+                    rotation: 0.0,
+                    large: false,
+                    sweep: corner.clockwise,
+                    x: to.0,
+                    y: to.1,
+                });
+                self.set_current_point(to.0, to.1);
             }
         }
-
-        let (tl, tr, br, bl) = (corner[0], corner[1], corner[2], corner[3]);
-        let right = x + w;
-        let bottom = y + h;
-
-        self.move_to(x + tl, y)?;
-        self.line_to(right - tr, y)?;
-        if tr > 0.0 {
-            self.push_path(&format!("A {} {} 0 0 1 {} {}", tr, tr, right, y + tr));
-            self.set_current_point(right, y + tr);
-        }
-        self.line_to(right, bottom - br)?;
-        if br > 0.0 {
-            self.push_path(&format!("A {} {} 0 0 1 {} {}", br, br, right - br, bottom));
-            self.set_current_point(right - br, bottom);
-        }
-        self.line_to(x + bl, bottom)?;
-        if bl > 0.0 {
-            self.push_path(&format!("A {} {} 0 0 1 {} {}", bl, bl, x, bottom - bl));
-            self.set_current_point(x, bottom - bl);
-        }
-        self.line_to(x, y + tl)?;
-        if tl > 0.0 {
-            self.push_path(&format!("A {} {} 0 0 1 {} {}", tl, tl, x + tl, y));
-            self.set_current_point(x + tl, y);
-        }
-        self.close_path()
+        self.close_path()?;
+        self.move_to(x, y)
     }
 
     fn fill(&mut self, fill_rule: FillRule) -> Result<()> {
@@ -1056,17 +2319,12 @@ impl<W: Write> CanvasPaths for SvgCanvas<W> {
             return Ok(());
         }
 
-        let id = format!("clip{}", self.clip_counter);
-        self.clip_counter += 1;
 
-        let transform = self.state.transform;
-        let path_d = self.current_path.clone();
-        self.write_clip_path_def(&id, path_d.as_str(), Some(transform), fill_rule)?;
+        let transform = self.path_transform;
+        let path_d = path_data(&self.current_path, &self.options);
+        self.write_clip_path_def(path_d.as_str(), Some(transform), fill_rule)?;
+        let id = self.intern_def("clip");
         self.state.clip_path = Some(format!("url(#{})", id));
-
-        self.current_path.clear();
-        self.current_point = None;
-        self.subpath_start = None;
         Ok(())
     }
 
@@ -1116,98 +2374,142 @@ impl<W: Write> CanvasText for SvgCanvas<W> {
         Ok(self.state.direction.clone())
     }
 
-    fn fill_text(&mut self, text: &str, x: f64, y: f64, _max_width: Option<f64>) -> Result<()> {
-        let mut elem = BytesStart::new("text");
-        let x_attr = x.to_string();
-        let y_attr = y.to_string();
-        let fill_paint = self.state.fill_style.clone();
-        let fill = self.paint_to_str(&fill_paint)?;
-        elem.push_attribute(("x", x_attr.as_str()));
-        elem.push_attribute(("y", y_attr.as_str()));
-        elem.push_attribute(("fill", fill.as_str()));
-        elem.push_attribute(("font", self.state.font.as_str()));
-        elem.push_attribute((
-            "text-anchor",
-            match self.state.text_align {
-                TextAlign::Left | TextAlign::Start => "start",
-                TextAlign::Center => "middle",
-                TextAlign::Right | TextAlign::End => "end",
-            },
-        ));
-        elem.push_attribute((
-            "dominant-baseline",
-            match self.state.text_baseline {
-                TextBaseline::Top => "text-before-edge",
-                TextBaseline::Hanging => "hanging",
-                TextBaseline::Middle => "middle",
-                TextBaseline::Alphabetic => "alphabetic",
-                TextBaseline::Ideographic => "ideographic",
-                TextBaseline::Bottom => "text-after-edge",
-            },
-        ));
-        self.apply_transform_attr(&mut elem);
-        self.apply_clip_attr(&mut elem);
-        self.writer.write_event(Event::Start(elem))?;
-        self.writer.write_event(Event::Text(BytesText::new(text)))?;
-        self.writer.write_event(Event::End(BytesEnd::new("text")))?;
-        Ok(())
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: Option<f64>) -> Result<()> {
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
+        self.write_text(text, x, y, false)
     }
 
     fn stroke_text(
         &mut self,
-        _text: &str,
-        _x: f64,
-        _y: f64,
-        _max_width: Option<f64>,
+        text: &str,
+        x: f64,
+        y: f64,
+        max_width: Option<f64>,
     ) -> Result<()> {
-        Err(Self::not_supported("stroke_text"))
+        if !(validate::finite(&[x, y]) && validate::max_width(max_width)) {
+            return Ok(());
+        }
+        self.write_text(text, x, y, true)
     }
 
     fn measure_text(&self, _text: &str) -> Result<TextMetrics> {
         Err(Self::not_supported("measure_text"))
     }
+
 }
 
 impl<W: Write> CanvasImageData for SvgCanvas<W> {
-    fn create_image_data(&mut self, _width: u32, _height: u32) -> Result<ImageData> {
-        Err(Self::not_supported("create_image_data"))
-    }
-
-    fn get_image_data(&self, _sx: u32, _sy: u32, _sw: u32, _sh: u32) -> Result<ImageData> {
-        Err(Self::not_supported("get_image_data"))
+    fn create_image_data(&mut self, width: u32, height: u32) -> Result<ImageData> {
+        if width == 0 || height == 0 {
+            return Err(LignumError::IndexSize("image data dimensions must be non-zero".into()));
+        }
+        Ok(ImageData {
+            width,
+            height,
+            data: vec![0; image_data_len(width, height)?],
+        })
     }
 
-    fn put_image_data(&mut self, _data: &ImageData, _dx: f64, _dy: f64) -> Result<()> {
-        Err(Self::not_supported("put_image_data"))
-    }
+    fn get_image_data(&self, sx: u32, sy: u32, sw: u32, sh: u32) -> Result<ImageData> {
+        if sw == 0 || sh == 0 {
+            return Err(LignumError::IndexSize("image data dimensions must be non-zero".into()));
+        }
+        // Reject a region too large to allocate before paying for the rasterization.
+        image_data_len(sw, sh)?;
+        let (Some(rasterizer), Some(copy)) = (&self.rasterizer, &self.writer.get_ref().copy) else {
+            return Err(Self::not_supported("get_image_data without a rasterizer"));
+        };
+
+        // Close the document as `finish` would, without disturbing the real output.
+        let mut document = Writer::new(copy.clone());
+        if !self.started {
+            let start = root_element(&self.options, self.width, self.height, self.view_box);
+            document.write_event(Event::Start(start))?;
+        }
+        for _ in 0..self.open_groups {
+            document.write_event(Event::End(BytesEnd::new("g")))?;
+        }
+        if !self.defs.is_empty() {
+            document.write_event(Event::Start(BytesStart::new("defs")))?;
+            for (id, def) in &self.defs {
+                write_def_events(&mut document, id, def)?;
+            }
+            document.write_event(Event::End(BytesEnd::new("defs")))?;
+        }
+        document.write_event(Event::End(BytesEnd::new("svg")))?;
+
+        let width = self.width.ceil().max(0.0) as u32;
+        let height = self.height.ceil().max(0.0) as u32;
+        let pixels = rasterizer.rasterize(&document.into_inner(), width, height)?;
+        if image_data_len(pixels.width, pixels.height).ok() != Some(pixels.data.len()) {
+            return Err(LignumError::Backend(
+                "rasterizer returned pixel data that does not match its dimensions".into(),
+            ));
+        }
+        pixels.sub_image(sx as i64, sy as i64, sw, sh)
+    }
+
+    fn put_image_data(&mut self, data: &ImageData, dx: f64, dy: f64) -> Result<()> {
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        if data.width == 0 || data.height == 0 {
+            return Ok(());
+        }
+        // putImageData ignores the transform, clip, global alpha and compositing, so the
+        // image is placed directly in canvas coordinates with none of them applied.
+        let image_id = self.image_def(data)?;
+        let mut elem = BytesStart::new("use");
+        let href_attr = format!("#{}", image_id);
+        let dx_attr = self.options.num(dx.trunc());
+        let dy_attr = self.options.num(dy.trunc());
+        elem.push_attribute(("href", href_attr.as_str()));
+        self.options.push_attr(&mut elem, "x", dx_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", dy_attr.as_str(), "0");
+        self.write_empty(elem)
+    }
 
     fn put_image_data_dirty(
         &mut self,
-        _data: &ImageData,
-        _dx: f64,
-        _dy: f64,
-        _dirty_x: u32,
-        _dirty_y: u32,
-        _dirty_width: u32,
-        _dirty_height: u32,
+        data: &ImageData,
+        dx: f64,
+        dy: f64,
+        dirty_x: u32,
+        dirty_y: u32,
+        dirty_width: u32,
+        dirty_height: u32,
     ) -> Result<()> {
-        Err(Self::not_supported("put_image_data_dirty"))
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        // Clamp the dirty rectangle to the image, as putImageData does.
+        let width = dirty_width.min(data.width.saturating_sub(dirty_x));
+        let height = dirty_height.min(data.height.saturating_sub(dirty_y));
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let region = data.sub_image(dirty_x as i64, dirty_y as i64, width, height)?;
+        self.put_image_data(&region, dx.trunc() + dirty_x as f64, dy.trunc() + dirty_y as f64)
     }
 }
 
 impl<W: Write> CanvasDrawImage for SvgCanvas<W> {
     fn draw_image(&mut self, image: &dyn CanvasImageSource, dx: f64, dy: f64) -> Result<()> {
-        let href = self.encode_image_as_data_uri(image)?;
-        let mut elem = BytesStart::new("image");
-        let w_attr = image.width().to_string();
-        let h_attr = image.height().to_string();
-        let dx_attr = dx.to_string();
-        let dy_attr = dy.to_string();
-        elem.push_attribute(("x", dx_attr.as_str()));
-        elem.push_attribute(("y", dy_attr.as_str()));
-        elem.push_attribute(("width", w_attr.as_str()));
-        elem.push_attribute(("height", h_attr.as_str()));
-        elem.push_attribute(("href", href.as_str()));
+        if !validate::finite(&[dx, dy]) {
+            return Ok(());
+        }
+        let image_id = self.image_def(image)?;
+        let mut elem = BytesStart::new("use");
+        let href_attr = format!("#{}", image_id);
+        let dx_attr = self.options.num(dx);
+        let dy_attr = self.options.num(dy);
+        elem.push_attribute(("href", href_attr.as_str()));
+        self.options.push_attr(&mut elem, "x", dx_attr.as_str(), "0");
+        self.options.push_attr(&mut elem, "y", dy_attr.as_str(), "0");
+        self.apply_image_rendering_attr(&mut elem);
+        self.apply_alpha_attr(&mut elem);
         self.apply_transform_attr(&mut elem);
         self.apply_clip_attr(&mut elem);
         self.write_empty(elem)
@@ -1221,55 +2523,208 @@ impl<W: Write> CanvasDrawImage for SvgCanvas<W> {
         dw: f64,
         dh: f64,
     ) -> Result<()> {
-        let href = self.encode_image_as_data_uri(image)?;
-        let mut elem = BytesStart::new("image");
-        let dx_attr = dx.to_string();
-        let dy_attr = dy.to_string();
-        let dw_attr = dw.to_string();
-        let dh_attr = dh.to_string();
-        elem.push_attribute(("x", dx_attr.as_str()));
-        elem.push_attribute(("y", dy_attr.as_str()));
-        elem.push_attribute(("width", dw_attr.as_str()));
-        elem.push_attribute(("height", dh_attr.as_str()));
-        elem.push_attribute(("href", href.as_str()));
-        elem.push_attribute(("preserveAspectRatio", "none"));
-        self.apply_transform_attr(&mut elem);
+        if !validate::finite(&[dx, dy, dw, dh]) {
+            return Ok(());
+        }
+        if image.width() == 0 || image.height() == 0 {
+            return Ok(());
+        }
+        let image_id = self.image_def(image)?;
+        let mut elem = BytesStart::new("use");
+        let href_attr = format!("#{}", image_id);
+        elem.push_attribute(("href", href_attr.as_str()));
+        self.apply_image_rendering_attr(&mut elem);
+        self.apply_alpha_attr(&mut elem);
+        // The shared image has its natural size; scale it into the destination rect.
+        let (sx, sy) = (dw / image.width() as f64, dh / image.height() as f64);
+        push_transform_attr(
+            &mut elem,
+            self.state.transform.multiply(&Matrix2D::new(sx, 0.0, 0.0, sy, dx, dy)),
+            &self.options,
+        );
         self.apply_clip_attr(&mut elem);
         self.write_empty(elem)
     }
 
     fn draw_image_subrect(
         &mut self,
-        _image: &dyn CanvasImageSource,
-        _sx: f64,
-        _sy: f64,
-        _sw: f64,
-        _sh: f64,
-        _dx: f64,
-        _dy: f64,
-        _dw: f64,
-        _dh: f64,
+        image: &dyn CanvasImageSource,
+        sx: f64,
+        sy: f64,
+        sw: f64,
+        sh: f64,
+        dx: f64,
+        dy: f64,
+        dw: f64,
+        dh: f64,
     ) -> Result<()> {
-        Err(Self::not_supported("draw_image_subrect"))
+        if !validate::finite(&[sx, sy, sw, sh, dx, dy, dw, dh]) {
+            return Ok(());
+        }
+        // Normalize negative sizes, then clip the source rectangle to the image, shrinking
+        // the destination proportionally, as drawImage does.
+        let (sx, sw) = if sw < 0.0 { (sx + sw, -sw) } else { (sx, sw) };
+        let (sy, sh) = if sh < 0.0 { (sy + sh, -sh) } else { (sy, sh) };
+        let (dx, dw) = if dw < 0.0 { (dx + dw, -dw) } else { (dx, dw) };
+        let (dy, dh) = if dh < 0.0 { (dy + dh, -dh) } else { (dy, dh) };
+        if sw == 0.0 || sh == 0.0 || dw == 0.0 || dh == 0.0 {
+            return Ok(());
+        }
+        let (scale_x, scale_y) = (dw / sw, dh / sh);
+        let (image_w, image_h) = (image.width() as f64, image.height() as f64);
+        let x0 = sx.max(0.0);
+        let y0 = sy.max(0.0);
+        let x1 = (sx + sw).min(image_w);
+        let y1 = (sy + sh).min(image_h);
+        if x1 <= x0 || y1 <= y0 {
+            return Ok(());
+        }
+        let (dx, dy) = (dx + (x0 - sx) * scale_x, dy + (y0 - sy) * scale_y);
+        let (dw, dh) = ((x1 - x0) * scale_x, (y1 - y0) * scale_y);
+        let (sx, sy, sw, sh) = (x0, y0, x1 - x0, y1 - y0);
+
+        // Embed only the pixels in use when the source is much larger than the subrect,
+        // so drawing from a sprite sheet doesn't repeat the whole sheet each time.
+        let (crop_x, crop_y) = (sx.floor(), sy.floor());
+        let crop_w = (sx + sw).ceil() - crop_x;
+        let crop_h = (sy + sh).ceil() - crop_y;
+        let (image_id, origin_x, origin_y) = match image.data_rgba() {
+            Some(data) if crop_w * crop_h * 4.0 <= image_w * image_h => {
+                let crop = crop_rgba(
+                    data,
+                    image.width(),
+                    image.height(),
+                    crop_x as i64,
+                    crop_y as i64,
+                    crop_w as u32,
+                    crop_h as u32,
+                )?;
+                (self.image_def(&crop)?, crop_x, crop_y)
+            }
+            _ => (self.image_def(image)?, 0.0, 0.0),
+        };
+
+        // A nested viewport shows just the source rectangle, stretched over the destination.
+        let mut group = BytesStart::new("g");
+        self.apply_alpha_attr(&mut group);
+        self.apply_transform_attr(&mut group);
+        self.apply_clip_attr(&mut group);
+        self.write_start(group)?;
+
+        let mut viewport = BytesStart::new("svg");
+        let x_attr = self.options.num(dx);
+        let y_attr = self.options.num(dy);
+        let w_attr = self.options.num(dw);
+        let h_attr = self.options.num(dh);
+        let view_box_attr = [sx - origin_x, sy - origin_y, sw, sh]
+            .map(|v| self.options.num(v))
+            .join(" ");
+        self.options.push_attr(&mut viewport, "x", x_attr.as_str(), "0");
+        self.options.push_attr(&mut viewport, "y", y_attr.as_str(), "0");
+        viewport.push_attribute(("width", w_attr.as_str()));
+        viewport.push_attribute(("height", h_attr.as_str()));
+        viewport.push_attribute(("viewBox", view_box_attr.as_str()));
+        viewport.push_attribute(("preserveAspectRatio", "none"));
+        self.writer.write_event(Event::Start(viewport))?;
+
+        let mut elem = BytesStart::new("use");
+        let href_attr = format!("#{}", image_id);
+        elem.push_attribute(("href", href_attr.as_str()));
+        self.apply_image_rendering_attr(&mut elem);
+        self.write_empty(elem)?;
+
+        self.writer.write_event(Event::End(BytesEnd::new("svg")))?;
+        self.writer.write_event(Event::End(BytesEnd::new("g")))?;
+        Ok(())
     }
 }
 
 impl<W: Write> CanvasRenderingContext2D for SvgCanvas<W> {}
 
+impl<W: Write> CanvasDocument for SvgCanvas<W> {
+    fn begin_group(&mut self, id: Option<&str>, class: Option<&str>, attributes: &[(&str, &str)]) -> Result<()> {
+        if let Some(&(name, _)) = attributes
+            .iter()
+            .find(|(name, _)| !is_xml_name(name) || matches!(*name, "id" | "class"))
+        {
+            return Err(LignumError::Syntax(format!("invalid group attribute name: {name:?}")));
+        }
+        if let Some(id) = id {
+            self.set_element_id(id)?;
+        }
+        let mut group = BytesStart::new("g");
+        if let Some(class) = class {
+            group.push_attribute(("class", class));
+        }
+        for &(name, value) in attributes {
+            group.push_attribute((name, value));
+        }
+        self.write_start(group)?;
+        self.open_groups += 1;
+        Ok(())
+    }
+
+    fn end_group(&mut self) -> Result<()> {
+        // Like restore() with an empty stack, an unmatched call is ignored.
+        if self.open_groups > 0 {
+            self.writer.write_event(Event::End(BytesEnd::new("g")))?;
+            self.open_groups -= 1;
+        }
+        Ok(())
+    }
+
+    fn set_element_id(&mut self, id: &str) -> Result<()> {
+        if id.is_empty() || id.contains(char::is_whitespace) {
+            return Err(LignumError::Syntax(format!("invalid element id: {id:?}")));
+        }
+        self.pending_meta.id = Some(id.to_string());
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.pending_meta.title = Some(title.to_string());
+        Ok(())
+    }
+
+    fn set_desc(&mut self, desc: &str) -> Result<()> {
+        self.pending_meta.desc = Some(desc.to_string());
+        Ok(())
+    }
+
+    fn set_data_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        if !is_xml_name(name) || name.contains(':') {
+            return Err(LignumError::Syntax(format!("invalid data attribute name: {name:?}")));
+        }
+        let data = &mut self.pending_meta.data;
+        match data.iter_mut().find(|(existing, _)| existing == name) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => data.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::api::{
-        CanvasDrawImage, CanvasFillStrokeStyles, CanvasRectangles, CanvasTransforms, ImageData,
-        Paint, PatternRepetition,
+        CanvasDrawImage, CanvasFillStrokeStyles, CanvasImageData, CanvasRectangles, CanvasTransforms,
+        ImageData, Paint, PatternRepetition,
     };
 
     fn svg_output<F>(f: F) -> String
+    where
+        F: FnOnce(&mut SvgCanvas<Vec<u8>>) -> Result<()>,
+    {
+        svg_output_with(SvgOptions::default(), f)
+    }
+
+    fn svg_output_with<F>(options: SvgOptions, f: F) -> String
     where
         F: FnOnce(&mut SvgCanvas<Vec<u8>>) -> Result<()>,
     {
         let buf = Vec::new();
-        let mut svg = SvgCanvas::new(buf, 100.0, 100.0).expect("create svg");
+        let mut svg = SvgCanvas::with_options(buf, 100.0, 100.0, options).expect("create svg");
         f(&mut svg).expect("draw operations");
         let out = svg.finish().expect("finish svg");
         String::from_utf8(out).expect("utf8")
@@ -1289,8 +2744,8 @@ mod tests {
     fn writes_linear_gradient_defs_and_usage() {
         let out = svg_output(|svg| {
             let mut grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0)?;
-            grad.add_color_stop(0.0, "red");
-            grad.add_color_stop(1.0, "blue");
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(1.0, "blue")?;
             svg.set_fill_style(Paint::Gradient(grad))?;
             svg.fill_rect(0.0, 0.0, 10.0, 10.0)
         });
@@ -1301,6 +2756,259 @@ mod tests {
         assert!(out.contains("fill=\"url(#grad0)\""));
     }
 
+    #[test]
+    fn gradients_use_user_space_and_srgb() {
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_radial_gradient(5.0, 5.0, 0.0, 5.0, 5.0, 10.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 10.0, 10.0)
+        });
+
+        assert!(out.contains("gradientUnits=\"userSpaceOnUse\""));
+        assert!(out.contains("color-interpolation=\"sRGB\""));
+        assert!(!out.contains("gradientTransform"));
+    }
+
+    #[test]
+    fn gradient_follows_transform_at_fill_time() {
+        let out = svg_output(|svg| {
+            svg.begin_path()?;
+            svg.rect(0.0, 0.0, 10.0, 10.0)?;
+            svg.translate(20.0, 0.0)?;
+            let mut grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(1.0, "blue")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill(FillRule::NonZero)
+        });
+
+        // The path keeps the space it was built in; the gradient moves with the fill.
+        assert!(out.contains("gradientTransform=\"matrix(1 0 0 1 20 0)\""));
+        let path = &out[out.find("<path d=\"M 0 0 L 10 0").unwrap()..];
+        let path = &path[..path.find("/>").unwrap()];
+        assert!(!path.contains("transform"));
+    }
+
+    #[test]
+    fn gradient_stops_are_sorted_and_split_alpha() {
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0)?;
+            grad.add_color_stop(1.0, "blue")?;
+            grad.add_color_stop(0.5, "lime")?;
+            grad.add_color_stop(0.5, "rgba(255, 0, 0, 0.5)")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 10.0, 10.0)
+        });
+
+        let lime = out.find("stop-color=\"lime\"").unwrap();
+        let red = out.find("<stop offset=\"0.5\" stop-color=\"#ff0000\" stop-opacity=\"0.5\"/>").unwrap();
+        let blue = out.find("stop-color=\"blue\"").unwrap();
+        assert!(lime < red && red < blue);
+    }
+
+    #[test]
+    fn degenerate_gradients_paint_nothing() {
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_linear_gradient(3.0, 3.0, 3.0, 3.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 10.0, 10.0)?;
+            let grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0)?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 10.0, 10.0)
+        });
+
+        assert_eq!(out.matches("fill=\"none\"").count(), 2);
+        assert!(!out.contains("<linearGradient"));
+    }
+
+    #[test]
+    fn radial_gradient_with_end_inside_start_is_mirrored() {
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_radial_gradient(50.0, 50.0, 40.0, 55.0, 50.0, 10.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(0.25, "lime")?;
+            grad.add_color_stop(1.0, "blue")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+
+        assert!(out.contains("cx=\"50\" cy=\"50\" r=\"40\" fx=\"55\" fy=\"50\" fr=\"10\""));
+        assert!(out.contains("<stop offset=\"0\" stop-color=\"blue\"/>"));
+        assert!(out.contains("<stop offset=\"0.75\" stop-color=\"lime\"/>"));
+        assert!(out.contains("<stop offset=\"1\" stop-color=\"red\"/>"));
+    }
+
+    #[test]
+    fn radial_gradient_cone_is_drawn_as_bands() {
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_radial_gradient(20.0, 50.0, 5.0, 80.0, 50.0, 10.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(1.0, "blue")?;
+            svg.set_fill_style(Paint::Gradient(grad))?;
+            svg.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+
+        assert!(!out.contains("<radialGradient"));
+        assert!(out.contains("<pattern id=\"grad0\""));
+        // 256 interpolated bands plus the pad on each side.
+        assert_eq!(out.matches("<path d=\"M").count(), 258);
+        assert!(out.contains("fill=\"url(#grad0)\""));
+    }
+
+    #[test]
+    fn writes_conic_gradient_as_wedge_pattern() {
+        let out = svg_output(|c| {
+            let mut grad = c.create_conic_gradient(0.0, 50.0, 50.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(1.0, "blue")?;
+            c.set_fill_style(Paint::Gradient(grad))?;
+            c.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+        assert!(out.contains("<pattern id=\"grad0\""));
+        assert!(out.contains("patternUnits=\"userSpaceOnUse\""));
+        // Red to blue changes by 255 steps; one slice each.
+        assert_eq!(out.matches("<path d=\"M").count(), 255);
+        // The first wedge sits at the start of the sweep, the last near its end.
+        let fills: Vec<&str> = out.split("fill=\"#").skip(1).map(|s| &s[..6]).collect();
+        let channel = |hex: &str, i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        let (first, last) = (fills[0], fills[fills.len() - 1]);
+        assert!(channel(first, 0) >= 254 && channel(first, 4) <= 1, "{first}");
+        assert!(channel(last, 0) <= 1 && channel(last, 4) >= 254, "{last}");
+        assert!(out.contains("fill=\"url(#grad0)\""));
+    }
+
+    #[test]
+    fn conic_gradient_slices_follow_the_stops() {
+        let out = svg_output(|c| {
+            let mut grad = c.create_conic_gradient(0.0, 50.0, 50.0)?;
+            grad.add_color_stop(0.5, "red")?;
+            grad.add_color_stop(0.5, "#00f")?;
+            c.set_fill_style(Paint::Gradient(grad))?;
+            c.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+        // Two solid halves, each a single pie slice bounded by an arc.
+        assert_eq!(out.matches("<path d=\"M").count(), 2);
+        assert_eq!(out.matches(" 0 0 1 ").count(), 2);
+        assert_eq!(out.matches("fill=\"#ff0000\"").count(), 1);
+        assert_eq!(out.matches("fill=\"#0000ff\"").count(), 1);
+
+        let out = svg_output(|c| {
+            let mut grad = c.create_conic_gradient(0.0, 50.0, 50.0)?;
+            grad.add_color_stop(0.0, "#000")?;
+            grad.add_color_stop(0.25, "#0a0000")?;
+            c.set_fill_style(Paint::Gradient(grad))?;
+            c.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+        // Ten color steps over the first quarter, then two slices for the solid pad as
+        // no slice spans more than half a turn.
+        assert_eq!(out.matches("<path d=\"M").count(), 12);
+    }
+
+    fn stroked_rect_with<F>(f: F) -> String
+    where
+        F: FnOnce(&mut SvgCanvas<Vec<u8>>) -> Result<()>,
+    {
+        svg_output(|svg| {
+            f(svg)?;
+            svg.stroke_rect(0.0, 0.0, 10.0, 10.0)?;
+            svg.begin_path()?;
+            svg.move_to(0.0, 0.0)?;
+            svg.line_to(10.0, 0.0)?;
+            svg.stroke()
+        })
+    }
+
+    /// Each style attribute must appear on both the stroked rect and the stroked path.
+    fn assert_on_rect_and_path(out: &str, attr: &str) {
+        let rect = &out[out.find("<rect").unwrap()..];
+        let rect = &rect[..rect.find("/>").unwrap()];
+        let path = &out[out.find("<path").unwrap()..];
+        let path = &path[..path.find("/>").unwrap()];
+        assert!(rect.contains(attr), "{attr} missing from {rect}");
+        assert!(path.contains(attr), "{attr} missing from {path}");
+    }
+
+    #[test]
+    fn stroke_style_line_width() {
+        let out = stroked_rect_with(|svg| svg.set_line_width(3.5));
+        assert_on_rect_and_path(&out, "stroke-width=\"3.5\"");
+    }
+
+    #[test]
+    fn stroke_style_line_cap() {
+        let out = stroked_rect_with(|svg| svg.set_line_cap(LineCap::Round));
+        assert_on_rect_and_path(&out, "stroke-linecap=\"round\"");
+    }
+
+    #[test]
+    fn stroke_style_line_join() {
+        let out = stroked_rect_with(|svg| svg.set_line_join(LineJoin::Bevel));
+        assert_on_rect_and_path(&out, "stroke-linejoin=\"bevel\"");
+    }
+
+    #[test]
+    fn stroke_style_miter_limit() {
+        let out = stroked_rect_with(|_| Ok(()));
+        assert_on_rect_and_path(&out, "stroke-miterlimit=\"10\"");
+        let out = stroked_rect_with(|svg| svg.set_miter_limit(2.5));
+        assert_on_rect_and_path(&out, "stroke-miterlimit=\"2.5\"");
+    }
+
+    #[test]
+    fn stroke_style_line_dash() {
+        let out = stroked_rect_with(|svg| svg.set_line_dash(vec![4.0, 2.0]));
+        assert_on_rect_and_path(&out, "stroke-dasharray=\"4 2\"");
+        let out = stroked_rect_with(|svg| svg.set_line_dash(vec![4.0, 2.0, 1.0]));
+        assert_on_rect_and_path(&out, "stroke-dasharray=\"4 2 1 4 2 1\"");
+    }
+
+    #[test]
+    fn stroke_style_line_dash_offset() {
+        let out = stroked_rect_with(|svg| svg.set_line_dash_offset(1.5));
+        assert_on_rect_and_path(&out, "stroke-dashoffset=\"1.5\"");
+    }
+
+    #[test]
+    fn stroke_style_global_alpha() {
+        let out = stroked_rect_with(|svg| svg.set_global_alpha(0.25));
+        assert_on_rect_and_path(&out, "opacity=\"0.25\"");
+    }
+
+    #[test]
+    fn global_alpha_applies_to_every_element() {
+        let img = ImageData {
+            width: 1,
+            height: 1,
+            data: vec![255, 0, 0, 255],
+        };
+        let out = svg_output(|svg| {
+            svg.set_global_alpha(0.5)?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.fill_text("hi", 0.0, 0.0, None)?;
+            svg.stroke_text("hi", 0.0, 0.0, None)?;
+            svg.draw_image(&img, 0.0, 0.0)?;
+            svg.draw_image_scaled(&img, 0.0, 0.0, 2.0, 2.0)?;
+            svg.draw_image_subrect(&img, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 2.0, 2.0)?;
+            svg.begin_path()?;
+            svg.rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.fill(FillRule::NonZero)
+        });
+
+        assert_eq!(out.matches("opacity=\"0.5\"").count(), 7);
+    }
+
+    #[test]
+    fn stroke_text_uses_stroke_style() {
+        let out = svg_output(|svg| {
+            svg.set_stroke_style(Paint::Color("blue".into()))?;
+            svg.stroke_text("hi", 1.0, 2.0, None)
+        });
+
+        assert!(out.contains("<text x=\"1\" y=\"2\" fill=\"none\" stroke=\"blue\" stroke-width=\"1\""));
+    }
+
     #[test]
     fn applies_transform_to_rect() {
         let out = svg_output(|svg| {
@@ -1312,18 +3020,104 @@ mod tests {
         assert!(out.contains("transform=\"matrix(1 0 0 1 5 6)\""));
     }
 
+    #[test]
+    fn transform_changes_inside_a_path_apply_per_point() {
+        let out = svg_output(|svg| {
+            svg.move_to(0.0, 0.0)?;
+            svg.translate(10.0, 0.0)?;
+            svg.line_to(0.0, 5.0)?;
+            svg.fill(FillRule::NonZero)
+        });
+        // Filling keeps the path in the space it started in.
+        assert!(out.contains("d=\"M 0 0 L 10 5\""));
+
+        let out = svg_output(|svg| {
+            svg.move_to(0.0, 0.0)?;
+            svg.line_to(10.0, 0.0)?;
+            svg.scale(2.0, 2.0)?;
+            svg.line_to(5.0, 5.0)?;
+            svg.stroke()
+        });
+        // Stroking moves the path into the current space, which scales the line width.
+        assert!(out.contains("d=\"M 0 0 L 5 0 L 5 5\""));
+        assert!(out.contains("transform=\"matrix(2 0 0 2 0 0)\""));
+
+        let out = svg_output(|svg| {
+            svg.move_to(0.0, 0.0)?;
+            svg.scale(2.0, 1.0)?;
+            svg.arc(0.0, 0.0, 5.0, 0.0, std::f64::consts::PI, false)?;
+            svg.scale(0.5, 1.0)?;
+            svg.stroke()
+        });
+        // A circle drawn under a non-uniform scale becomes an ellipse.
+        assert!(out.contains("L 10 0 A 10 5 0 1 1 -10 0"), "{out}");
+    }
+
     #[test]
     fn writes_pattern_defs_and_usage() {
+        let img = ImageData {
+            width: 2,
+            height: 3,
+            data: vec![255; 2 * 3 * 4],
+        };
         let out = svg_output(|svg| {
-            let pat = svg.create_pattern(&DummyImage, PatternRepetition::Repeat)?;
+            let pat = svg.create_pattern(&img, PatternRepetition::Repeat)?;
             svg.set_fill_style(Paint::Pattern(pat))?;
             svg.fill_rect(0.0, 0.0, 5.0, 5.0)
         });
 
-        assert!(out.contains("<pattern id=\"pat0\""));
+        assert!(out.contains("<pattern id=\"pat0\" x=\"0\" y=\"0\" width=\"2\" height=\"3\""));
+        assert!(out.contains("<use href=\"#img0\" x=\"0\" y=\"0\"/>"));
+        assert!(out.contains("<image id=\"img0\" width=\"2\" height=\"3\" href=\"data:image/png;base64,"));
         assert!(out.contains("fill=\"url(#pat0)\""));
     }
 
+    #[test]
+    fn pattern_tile_spans_canvas_on_non_repeating_axes() {
+        let img = ImageData {
+            width: 4,
+            height: 4,
+            data: vec![0; 4 * 4 * 4],
+        };
+        let out = svg_output(|svg| {
+            let pat = svg.create_pattern(&img, PatternRepetition::RepeatX)?;
+            svg.set_fill_style(Paint::Pattern(pat))?;
+            svg.fill_rect(0.0, 0.0, 100.0, 100.0)?;
+            let pat = svg.create_pattern(&img, PatternRepetition::NoRepeat)?;
+            svg.set_fill_style(Paint::Pattern(pat))?;
+            svg.fill_rect(0.0, 0.0, 100.0, 100.0)
+        });
+
+        assert!(out.contains("<pattern id=\"pat0\" x=\"0\" y=\"-1\" width=\"4\" height=\"102\""));
+        assert!(out.contains("<use href=\"#img0\" x=\"0\" y=\"1\"/>"));
+        assert!(out.contains("<pattern id=\"pat1\" x=\"-1\" y=\"-1\" width=\"102\" height=\"102\""));
+    }
+
+    #[test]
+    fn writes_pattern_transform() {
+        let img = ImageData {
+            width: 1,
+            height: 1,
+            data: vec![0, 0, 0, 255],
+        };
+        let out = svg_output(|svg| {
+            let mut pat = svg.create_pattern(&img, PatternRepetition::Repeat)?;
+            pat.set_transform(Matrix2D::new(2.0, 0.0, 0.0, 2.0, 3.0, 4.0));
+            pat.set_transform(Matrix2D::new(f64::NAN, 0.0, 0.0, 1.0, 0.0, 0.0));
+            svg.set_fill_style(Paint::Pattern(pat))?;
+            svg.fill_rect(0.0, 0.0, 5.0, 5.0)
+        });
+
+        assert!(out.contains("patternTransform=\"matrix(2 0 0 2 3 4)\""));
+    }
+
+    #[test]
+    fn create_pattern_requires_pixels() {
+        let buf = Vec::new();
+        let mut svg = SvgCanvas::new(buf, 10.0, 10.0).expect("create svg");
+        assert!(svg.create_pattern(&DummyImage, PatternRepetition::Repeat).is_err());
+    }
+
     #[test]
     fn writes_round_rect_path() {
         let out = svg_output(|svg| {
@@ -1332,7 +3126,20 @@ mod tests {
             svg.fill(FillRule::NonZero)
         });
 
-        assert!(out.contains("d=\"M 2 0 L 8 0 A 2 2 0 0 1 10 2 L 10 6 A 2 2 0 0 1 8 8 L 2 8 A 2 2 0 0 1 0 6 L 0 2 A 2 2 0 0 1 2 0 Z\""));
+        assert!(out.contains("d=\"M 2 0 L 8 0 A 2 2 0 0 1 10 2 L 10 6 A 2 2 0 0 1 8 8 L 2 8 A 2 2 0 0 1 0 6 L 0 2 A 2 2 0 0 1 2 0 Z"));
+    }
+
+    #[test]
+    fn round_rect_mirrors_and_scales_radii() {
+        let out = svg_output(|svg| {
+            svg.round_rect(10.0, 0.0, -10.0, 4.0, &[8.0, 0.0, 0.0, 0.0])?;
+            svg.fill(FillRule::NonZero)
+        });
+
+        // The negative width mirrors the rectangle: the first radius goes on the right, the
+        // outline runs counterclockwise from (x, y), and the 4-unit height halves the
+        // radius so the right-hand corners still fit.
+        assert!(out.contains("d=\"M 6 0 L 0 0 L 0 4 L 10 4 A 4 4 0 0 0 6 0 Z M 10 0\""));
     }
 
     #[test]
@@ -1344,9 +3151,590 @@ mod tests {
         };
         let out = svg_output(|svg| svg.draw_image(&img, 2.0, 3.0));
 
-        assert!(out.contains("<image"));
-        assert!(out.contains("x=\"2\" y=\"3\" width=\"1\" height=\"1\""));
-        assert!(out.contains("href=\"data:image/png;base64,"));
+        assert!(out.contains("<image id=\"img0\" width=\"1\" height=\"1\" href=\"data:image/png;base64,"));
+        assert!(out.contains("<use href=\"#img0\" x=\"2\" y=\"3\"/>"));
+    }
+
+    #[test]
+    fn draw_image_scaled_transforms_shared_image() {
+        let img = ImageData {
+            width: 2,
+            height: 2,
+            data: vec![255; 2 * 2 * 4],
+        };
+        let out = svg_output(|svg| {
+            svg.translate(1.0, 0.0)?;
+            svg.draw_image_scaled(&img, 2.0, 3.0, 4.0, 1.0)
+        });
+
+        assert!(out.contains("<use href=\"#img0\" transform=\"matrix(2 0 0 0.5 3 3)\"/>"));
+    }
+
+    #[test]
+    fn draw_image_subrect_uses_nested_viewport() {
+        let img = ImageData {
+            width: 4,
+            height: 2,
+            data: vec![255; 4 * 2 * 4],
+        };
+        let out = svg_output(|svg| {
+            svg.translate(1.0, 0.0)?;
+            svg.draw_image_subrect(&img, 1.0, 0.0, 2.0, 2.0, 10.0, 20.0, 4.0, 4.0)
+        });
+
+        assert!(out.contains("<g transform=\"matrix(1 0 0 1 1 0)\">"));
+        assert!(out.contains("<svg x=\"10\" y=\"20\" width=\"4\" height=\"4\" viewBox=\"1 0 2 2\" preserveAspectRatio=\"none\">"));
+        assert!(out.contains("<image id=\"img0\" width=\"4\" height=\"2\""));
+        assert!(out.contains("<use href=\"#img0\"/>"));
+    }
+
+    #[test]
+    fn draw_image_subrect_clips_source_and_crops_atlas() {
+        let img = ImageData {
+            width: 8,
+            height: 8,
+            data: vec![255; 8 * 8 * 4],
+        };
+        let out = svg_output(|svg| {
+            // Half of the source lies left of the image: only x in [0, 1.5) is drawn.
+            svg.draw_image_subrect(&img, -1.5, 2.5, 3.0, 1.0, 0.0, 0.0, 6.0, 2.0)?;
+            svg.draw_image_subrect(&img, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 6.0, 2.0)
+        });
+
+        assert!(out.contains("<svg x=\"3\" y=\"0\" width=\"3\" height=\"2\" viewBox=\"0 0.5 1.5 1\""));
+        assert!(out.contains("<image id=\"img0\" width=\"2\" height=\"2\""));
+        assert_eq!(out.matches("<use").count(), 1);
+    }
+
+    #[test]
+    fn image_rendering_follows_smoothing_state() {
+        let img = ImageData {
+            width: 1,
+            height: 1,
+            data: vec![255, 0, 0, 255],
+        };
+        let out = svg_output(|svg| {
+            svg.draw_image(&img, 0.0, 0.0)?;
+            svg.set_image_smoothing_quality(ImageSmoothingQuality::High)?;
+            svg.draw_image_scaled(&img, 0.0, 0.0, 4.0, 4.0)?;
+            svg.set_image_smoothing_enabled(false)?;
+            let pat = svg.create_pattern(&img, PatternRepetition::Repeat)?;
+            svg.set_fill_style(Paint::Pattern(pat))?;
+            svg.fill_rect(0.0, 0.0, 5.0, 5.0)
+        });
+
+        assert!(out.contains("<use href=\"#img0\" x=\"0\" y=\"0\"/>"));
+        assert!(out.contains("<use href=\"#img0\" image-rendering=\"optimizeQuality\" transform="));
+        assert!(out.contains("image-rendering=\"optimizeSpeed\" style=\"image-rendering:pixelated\"/>"));
+    }
+
+    #[test]
+    fn identical_defs_are_written_once() {
+        let img = ImageData {
+            width: 1,
+            height: 1,
+            data: vec![255, 0, 0, 255],
+        };
+        let out = svg_output(|svg| {
+            let mut grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0)?;
+            grad.add_color_stop(0.0, "red")?;
+            grad.add_color_stop(1.0, "blue")?;
+            for i in 0..3 {
+                svg.set_fill_style(Paint::Gradient(grad.clone()))?;
+                svg.fill_rect(i as f64, 0.0, 10.0, 10.0)?;
+                svg.draw_image(&img, i as f64, 0.0)?;
+            }
+            let mut other = svg.create_linear_gradient(0.0, 0.0, 0.0, 10.0)?;
+            other.add_color_stop(0.0, "red")?;
+            svg.set_fill_style(Paint::Gradient(other))?;
+            svg.fill_rect(0.0, 0.0, 10.0, 10.0)
+        });
+
+        assert_eq!(out.matches("<defs>").count(), 1);
+        assert_eq!(out.matches("<linearGradient").count(), 2);
+        assert_eq!(out.matches("fill=\"url(#grad0)\"").count(), 3);
+        assert_eq!(out.matches("fill=\"url(#grad1)\"").count(), 1);
+        assert_eq!(out.matches("<image").count(), 1);
+        assert_eq!(out.matches("<use href=\"#img0\"").count(), 3);
+    }
+
+    #[test]
+    fn fill_then_stroke_draws_both() {
+        let out = svg_output(|svg| {
+            svg.rect(1.0, 1.0, 4.0, 4.0)?;
+            svg.clip(FillRule::NonZero)?;
+            svg.fill(FillRule::NonZero)?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.stroke()
+        });
+
+        assert_eq!(out.matches("d=\"M 1 1 L 5 1 L 5 5 L 1 5 Z\"").count(), 3);
+        assert!(out.contains("stroke=\""));
+    }
+
+    #[test]
+    fn zero_radius_arc_still_reaches_its_center() {
+        let out = svg_output(|svg| {
+            svg.move_to(0.0, 0.0)?;
+            svg.arc(5.0, 5.0, 0.0, 0.0, 1.0, false)?;
+            svg.line_to(10.0, 0.0)?;
+            svg.stroke()?;
+            svg.begin_path()?;
+            svg.arc(5.0, 5.0, 0.0, 0.0, 1.0, false)?;
+            svg.line_to(10.0, 10.0)?;
+            svg.stroke()
+        });
+
+        assert!(out.contains("d=\"M 0 0 L 5 5 L 10 0\""), "{out}");
+        assert!(out.contains("d=\"M 5 5 L 10 10\""), "{out}");
+    }
+
+    #[test]
+    fn ellipse_is_a_path_segment_that_fills_and_strokes() {
+        let out = svg_output(|svg| {
+            svg.move_to(0.0, 0.0)?;
+            svg.ellipse(10.0, 10.0, 4.0, 2.0, std::f64::consts::FRAC_PI_2, 0.0, std::f64::consts::PI, true)?;
+            svg.fill(FillRule::NonZero)?;
+            svg.stroke()
+        });
+
+        // Rotated a quarter turn, the half ellipse runs from (10, 14) counterclockwise to
+        // (10, 6), drawn with the rotation in degrees as SVG takes it.
+        let d = "d=\"M 0 0 L 10 14 A 4 2 90 1 0 10 6\"";
+        assert_eq!(out.matches(d).count(), 2, "{out}");
+        assert!(out.contains("stroke=\""));
+        assert!(!out.contains("<ellipse"));
+
+        // A flat ellipse goes out and back along its one remaining axis.
+        let flat = svg_output_with(SvgOptions::new().precision(3), |svg| {
+            svg.ellipse(0.0, 0.0, 4.0, 0.0, 0.0, 0.0, std::f64::consts::TAU, false)?;
+            svg.stroke()
+        });
+        assert!(flat.contains("d=\"M 4 0 L 0 0 L -4 0 L 0 0 L 4 0\""), "{flat}");
+    }
+
+    #[test]
+    fn invalid_arguments_are_ignored() {
+        let out = svg_output(|svg| {
+            validate::assert_ignores_invalid_arguments(svg);
+            svg.fill(FillRule::NonZero)
+        });
+        assert!(!out.contains("<path"));
+        assert!(!out.contains("<text"));
+        assert!(!out.contains("<rect"));
+    }
+
+    #[test]
+    fn identical_clips_share_an_id() {
+        let out = svg_output(|svg| {
+            for _ in 0..2 {
+                svg.save()?;
+                svg.begin_path()?;
+                svg.rect(0.0, 0.0, 5.0, 5.0)?;
+                svg.clip(FillRule::NonZero)?;
+                svg.fill_rect(0.0, 0.0, 10.0, 10.0)?;
+                svg.restore()?;
+            }
+            Ok(())
+        });
+
+        assert_eq!(out.matches("<clipPath").count(), 1);
+        assert_eq!(out.matches("clip-path=\"url(#clip0)\"").count(), 2);
+    }
+
+    #[test]
+    fn create_image_data_is_blank() {
+        let mut svg = SvgCanvas::new(Vec::new(), 10.0, 10.0).expect("create svg");
+        let data = svg.create_image_data(2, 3).unwrap();
+        assert_eq!((data.width, data.height), (2, 3));
+        assert_eq!(data.data, vec![0; 24]);
+        assert!(matches!(svg.create_image_data(0, 3), Err(LignumError::IndexSize(_))));
+    }
+
+    #[test]
+    fn put_image_data_ignores_state() {
+        let img = ImageData {
+            width: 1,
+            height: 1,
+            data: vec![255, 0, 0, 255],
+        };
+        let out = svg_output(|svg| {
+            svg.translate(10.0, 10.0)?;
+            svg.set_global_alpha(0.5)?;
+            svg.begin_path()?;
+            svg.rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.clip(FillRule::NonZero)?;
+            svg.put_image_data(&img, 2.7, 3.0)
+        });
+
+        assert!(out.contains("<use href=\"#img0\" x=\"2\" y=\"3\"/>"));
+    }
+
+    #[test]
+    fn put_image_data_dirty_embeds_clamped_region() {
+        let img = ImageData {
+            width: 2,
+            height: 2,
+            data: vec![
+                1, 1, 1, 255, 2, 2, 2, 255, //
+                3, 3, 3, 255, 4, 4, 4, 255,
+            ],
+        };
+        let out = svg_output(|svg| {
+            svg.put_image_data_dirty(&img, 5.0, 6.0, 1, 0, 10, 10)?;
+            svg.put_image_data_dirty(&img, 5.0, 6.0, 2, 0, 1, 1)
+        });
+
+        assert!(out.contains("<image id=\"img0\" width=\"1\" height=\"2\""));
+        assert!(out.contains("<use href=\"#img0\" x=\"6\" y=\"6\"/>"));
+        assert_eq!(out.matches("<use").count(), 1);
+    }
+
+    #[test]
+    fn get_image_data_requires_rasterizer() {
+        let svg = SvgCanvas::new(Vec::new(), 10.0, 10.0).expect("create svg");
+        assert!(matches!(svg.get_image_data(0, 0, 1, 1), Err(LignumError::NotSupported(_))));
+    }
+
+    struct FakeRasterizer;
+    impl SvgRasterizer for FakeRasterizer {
+        fn rasterize(&self, svg: &[u8], width: u32, height: u32) -> Result<ImageData> {
+            let svg = std::str::from_utf8(svg).unwrap();
+            assert!(svg.trim_end().ends_with("</svg>"));
+            assert!(svg.contains("<defs>"));
+            // Fill every pixel with its x coordinate so crops are easy to check.
+            let data = (0..width * height).flat_map(|i| [(i % width) as u8, 0, 0, 255]).collect();
+            Ok(ImageData { width, height, data })
+        }
+    }
+
+    #[test]
+    fn get_image_data_rasterizes_document_so_far() {
+        let mut svg = SvgCanvas::with_rasterizer(Vec::new(), 4.0, 4.0, FakeRasterizer).expect("create svg");
+        let mut grad = svg.create_linear_gradient(0.0, 0.0, 4.0, 0.0).unwrap();
+        grad.add_color_stop(0.0, "red").unwrap();
+        svg.set_fill_style(Paint::Gradient(grad)).unwrap();
+        svg.fill_rect(0.0, 0.0, 4.0, 4.0).unwrap();
+
+        let pixels = svg.get_image_data(3, 0, 2, 1).unwrap();
+        assert_eq!(pixels.data, vec![3, 0, 0, 255, 0, 0, 0, 0]);
+        assert!(matches!(svg.get_image_data(0, 0, u32::MAX, u32::MAX), Err(LignumError::IndexSize(_))));
+
+        // Reading back must not disturb the real output.
+        let out = String::from_utf8(svg.finish().unwrap()).unwrap();
+        assert_eq!(out.matches("<defs>").count(), 1);
+        assert_eq!(out.matches("</svg>").count(), 1);
+    }
+
+    #[derive(Clone, Default)]
+    struct CollectingSink {
+        stored: std::rc::Rc<std::cell::RefCell<Vec<(u32, u32)>>>,
+    }
+
+    impl SvgImageSink for CollectingSink {
+        fn store(&mut self, png: &[u8], width: u32, height: u32) -> Result<String> {
+            assert!(png.starts_with(b"\x89PNG"));
+            let mut stored = self.stored.borrow_mut();
+            stored.push((width, height));
+            Ok(format!("img/{}.png", stored.len()))
+        }
+    }
+
+    #[test]
+    fn image_sink_receives_each_image_once() {
+        let logo = ImageData {
+            width: 2,
+            height: 1,
+            data: vec![255; 2 * 4],
+        };
+        let other = ImageData {
+            width: 2,
+            height: 1,
+            data: vec![0; 2 * 4],
+        };
+        let sink = CollectingSink::default();
+        let stored = sink.stored.clone();
+        let out = svg_output(|svg| {
+            svg.set_image_sink(sink);
+            for page in 0..50 {
+                svg.draw_image(&logo, 0.0, page as f64)?;
+            }
+            svg.draw_image(&other, 0.0, 0.0)
+        });
+
+        assert_eq!(*stored.borrow(), vec![(2, 1), (2, 1)]);
+        assert!(out.contains("<image id=\"img0\" width=\"2\" height=\"1\" href=\"img/1.png\"/>"));
+        assert!(out.contains("<image id=\"img1\" width=\"2\" height=\"1\" href=\"img/2.png\"/>"));
+        assert_eq!(out.matches("<use href=\"#img0\"").count(), 50);
+        assert!(!out.contains("base64"));
+    }
+
+    #[test]
+    fn directory_sink_names_files_by_content() {
+        let dir = std::env::temp_dir().join(format!("lignum-svg-sink-{}", std::process::id()));
+        let img = |red| ImageData {
+            width: 1,
+            height: 1,
+            data: vec![red, 2, 3, 255],
+        };
+        let href = |out: &str| {
+            let start = out.find("href=\"img/").unwrap() + 10;
+            out[start..start + out[start..].find('"').unwrap()].to_string()
+        };
+        let first = svg_output(|svg| {
+            svg.set_image_sink(DirectoryImageSink::new(&dir, "img/"));
+            svg.draw_image(&img(1), 0.0, 0.0)?;
+            svg.draw_image(&img(1), 1.0, 0.0)
+        });
+        // A second canvas sharing the directory must not clobber the first one's image.
+        let second = svg_output(|svg| {
+            svg.set_image_sink(DirectoryImageSink::new(&dir, "img/"));
+            svg.draw_image(&img(9), 0.0, 0.0)
+        });
+
+        let (a, b) = (href(&first), href(&second));
+        let written = std::fs::read(dir.join(&a)).unwrap();
+        let hex: String = digest(&written).iter().map(|byte| format!("{byte:02x}")).collect();
+        assert_eq!(a, format!("{hex}.png"));
+        assert_ne!(a, b);
+        assert_eq!(first.matches("href=\"img/").count(), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        assert!(written.starts_with(b"\x89PNG"));
+        assert!(std::fs::read(dir.join(&b)).unwrap().starts_with(b"\x89PNG"));
+        // File names must stay the same across builds, so pin the digest to SHA-256.
+        assert_eq!(
+            digest(b"abc")[..4],
+            [0xba, 0x78, 0x16, 0xbf],
+            "digest must be SHA-256"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn precision_rounds_numbers_and_drops_trailing_zeros() {
+        let out = svg_output_with(SvgOptions::new().precision(2), |svg| {
+            svg.translate(1.0 / 3.0, 0.0)?;
+            svg.fill_rect(0.1 + 0.2, 1.5, 10.0, -0.001)
+        });
+
+        assert!(out.contains("<rect x=\"0.3\" y=\"1.5\" width=\"10\" height=\"0\""));
+        assert!(out.contains("transform=\"matrix(1 0 0 1 0.33 0)\""));
+    }
+
+    #[test]
+    fn indentation_can_be_turned_off() {
+        let out = svg_output_with(SvgOptions::new().indent(false), |svg| svg.fill_rect(0.0, 0.0, 1.0, 1.0));
+
+        assert!(!out.contains("\n  <rect"));
+        assert!(out.contains("><rect "));
+    }
+
+    #[test]
+    fn relative_path_commands_use_rounded_positions() {
+        let out = svg_output_with(
+            SvgOptions::new().precision(2).relative_path_commands(true),
+            |svg| {
+                svg.move_to(10.0, 10.0)?;
+                svg.line_to(20.0, 10.0)?;
+                svg.line_to(20.0, 30.0)?;
+                svg.line_to(15.0, 35.0)?;
+                svg.close_path()?;
+                svg.move_to(1.0 / 3.0, 0.0)?;
+                svg.line_to(2.0 / 3.0, 1.0)?;
+                svg.bezier_curve_to(1.0, 1.0, 2.0, 2.0, 3.0, 3.0)?;
+                svg.fill(FillRule::NonZero)
+            },
+        );
+
+        assert!(out.contains("d=\"m 10 10 h 10 v 20 l -5 5 z m -9.67 -10 l 0.34 1 c 0.33 0, 1.33 1, 2.33 2\""));
+    }
+
+    #[test]
+    fn default_valued_attributes_can_be_omitted() {
+        let out = svg_output_with(SvgOptions::new().omit_default_attributes(true), |svg| {
+            svg.rect(0.0, 0.0, 5.0, 5.0)?;
+            svg.fill(FillRule::NonZero)?;
+            svg.stroke()?;
+            svg.fill_rect(0.0, 0.0, 5.0, 5.0)
+        });
+
+        assert!(!out.contains("fill-rule"));
+        assert!(!out.contains("stroke-width"));
+        assert!(!out.contains("stroke-linecap"));
+        assert!(!out.contains("stroke-linejoin"));
+        // Canvas and SVG disagree on the miter limit default.
+        assert!(out.contains("stroke-miterlimit=\"10\""));
+        assert!(out.contains("<rect width=\"5\" height=\"5\""));
+    }
+
+    #[test]
+    fn compact_output_is_smaller() {
+        let draw = |svg: &mut SvgCanvas<Vec<u8>>| {
+            svg.rotate(0.1)?;
+            svg.arc(50.0, 50.0, 20.0, 0.0, 5.0, false)?;
+            svg.stroke()
+        };
+        let full = svg_output(draw);
+        let compact = svg_output_with(SvgOptions::compact(), draw);
+
+        assert!(compact.len() < full.len());
+        assert!(!compact.contains('\n'));
+        assert!(compact.contains("transform=\"matrix(0.995 0.1 -0.1 0.995 0 0)\""));
+    }
+
+    #[cfg(feature = "svgz")]
+    #[test]
+    fn svgz_writer_round_trips() {
+        use std::io::Read;
+
+        let mut svg = SvgCanvas::new(SvgzWriter::new(Vec::new()), 10.0, 10.0).unwrap();
+        svg.fill_rect(0.0, 0.0, 10.0, 10.0).unwrap();
+        let gz = svg.finish().unwrap().finish().unwrap();
+        assert!(gz.starts_with(&[0x1f, 0x8b]));
+
+        let mut out = String::new();
+        flate2::read::GzDecoder::new(gz.as_slice())
+            .read_to_string(&mut out)
+            .unwrap();
+        assert!(out.starts_with("<?xml"));
+        assert!(out.ends_with("</svg>"));
+        assert!(out.contains("<rect "));
+    }
+
+    #[test]
+    fn groups_wrap_elements_and_close_at_finish() {
+        let out = svg_output_with(SvgOptions::new().indent(false), |svg| {
+            svg.begin_group(Some("axis"), Some("layer"), &[("aria-hidden", "true")])?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.begin_group(None, Some("series-3"), &[])?;
+            svg.fill_rect(1.0, 1.0, 1.0, 1.0)?;
+            svg.end_group()?;
+            svg.end_group()?;
+            svg.end_group()?;
+            svg.begin_group(Some("open"), None, &[])
+        });
+
+        assert!(out.contains("<g id=\"axis\" class=\"layer\" aria-hidden=\"true\"><rect "));
+        assert!(out.contains("<g class=\"series-3\"><rect x=\"1\""));
+        assert!(out.contains("/></g></g><g id=\"open\"></g></svg>"));
+    }
+
+    #[test]
+    fn metadata_applies_to_the_next_element_only() {
+        let out = svg_output_with(SvgOptions::new().indent(false), |svg| {
+            svg.set_element_id("bar-1")?;
+            svg.set_data_attribute("series", "3")?;
+            svg.set_data_attribute("series", "4")?;
+            svg.set_data_attribute("value", "a<b")?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.fill_rect(1.0, 0.0, 1.0, 1.0)
+        });
+
+        assert!(out.contains("<rect id=\"bar-1\" x=\"0\" y=\"0\" width=\"1\" height=\"1\" fill=\"#000\" data-series=\"4\" data-value=\"a&lt;b\"/>"));
+        assert!(out.contains("<rect x=\"1\" y=\"0\" width=\"1\" height=\"1\" fill=\"#000\"/>"));
+    }
+
+    #[test]
+    fn title_and_desc_become_children() {
+        let out = svg_output_with(SvgOptions::new().indent(false), |svg| {
+            svg.set_title("Revenue")?;
+            svg.set_desc("Bars & lines")?;
+            svg.begin_group(Some("chart"), None, &[])?;
+            svg.set_title("Label")?;
+            svg.fill_text("Q1", 0.0, 0.0, None)?;
+            svg.set_title("Dot")?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)?;
+            svg.end_group()
+        });
+
+        assert!(out.contains("<g id=\"chart\"><title>Revenue</title><desc>Bars &amp; lines</desc>"));
+        assert!(out.contains("><title>Label</title>Q1</text>"));
+        assert!(out.contains("fill=\"#000\"><title>Dot</title></rect></g>"));
+    }
+
+    #[test]
+    fn invalid_metadata_names_are_rejected() {
+        let mut svg = SvgCanvas::new(Vec::new(), 10.0, 10.0).unwrap();
+        assert!(matches!(svg.set_element_id("two words"), Err(LignumError::Syntax(_))));
+        assert!(matches!(svg.set_data_attribute("1st", "x"), Err(LignumError::Syntax(_))));
+        assert!(matches!(svg.begin_group(None, None, &[("a b", "x")]), Err(LignumError::Syntax(_))));
+        assert!(matches!(svg.begin_group(None, None, &[("id", "x")]), Err(LignumError::Syntax(_))));
+    }
+
+    #[test]
+    fn fragment_omits_the_xml_declaration() {
+        let mut svg = SvgCanvas::fragment(Vec::new(), 10.0, 20.0, SvgOptions::new()).unwrap();
+        svg.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let out = String::from_utf8(svg.finish().unwrap()).unwrap();
+
+        assert!(out.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(out.ends_with("</svg>"));
+    }
+
+    #[test]
+    fn fragment_content_prefixes_def_ids() {
+        let options = SvgOptions::new().indent(false).id_prefix("chart1-");
+        let mut svg = SvgCanvas::fragment_content(Vec::new(), 10.0, 10.0, options).unwrap();
+        svg.begin_group(Some("plot"), None, &[]).unwrap();
+        let mut grad = svg.create_linear_gradient(0.0, 0.0, 10.0, 0.0).unwrap();
+        grad.add_color_stop(0.0, "red").unwrap();
+        grad.add_color_stop(1.0, "blue").unwrap();
+        svg.set_fill_style(Paint::Gradient(grad)).unwrap();
+        svg.fill_rect(0.0, 0.0, 10.0, 10.0).unwrap();
+        let out = String::from_utf8(svg.finish().unwrap()).unwrap();
+
+        assert!(out.starts_with("<g id=\"plot\"><rect "));
+        assert!(out.contains("fill=\"url(#chart1-grad0)\""));
+        assert!(out.contains("</g><defs><linearGradient id=\"chart1-grad0\""));
+        assert!(out.ends_with("</defs>"));
+        assert!(!out.contains("<svg"));
+    }
+
+    #[test]
+    fn root_uses_units_view_box_and_aspect_ratio() {
+        let options = SvgOptions::new()
+            .units(SvgUnit::Mm)
+            .view_box(-5.0, -5.0, 110.0, 60.0)
+            .preserve_aspect_ratio("xMinYMin slice");
+        let mut svg = SvgCanvas::with_options(Vec::new(), 100.0, 50.0, options).unwrap();
+        svg.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        let out = String::from_utf8(svg.finish().unwrap()).unwrap();
+
+        assert!(out.contains(
+            "width=\"100mm\" height=\"50mm\" viewBox=\"-5 -5 110 60\" preserveAspectRatio=\"xMinYMin slice\""
+        ));
+    }
+
+    #[test]
+    fn reset_empties_the_stack_and_path() {
+        let out = svg_output(|svg| {
+            svg.translate(5.0, 5.0)?;
+            svg.save()?;
+            svg.rect(0.0, 0.0, 2.0, 2.0)?;
+            svg.reset()?;
+            svg.restore()?;
+            svg.fill(FillRule::NonZero)?;
+            svg.fill_rect(0.0, 0.0, 1.0, 1.0)
+        });
+
+        assert!(!out.contains("<path"));
+        assert!(!out.contains("transform"));
+        assert!(out.contains("<rect x=\"0\" y=\"0\" width=\"1\" height=\"1\""));
+    }
+
+    #[test]
+    fn size_can_change_until_drawing_starts() {
+        let mut svg = SvgCanvas::new(Vec::new(), 10.0, 10.0).unwrap();
+        svg.translate(5.0, 0.0).unwrap();
+        svg.set_size(40.0, 30.0).unwrap();
+        assert_eq!((svg.width().unwrap(), svg.height().unwrap()), (40.0, 30.0));
+        svg.fill_rect(0.0, 0.0, 1.0, 1.0).unwrap();
+        assert!(matches!(svg.set_size(1.0, 1.0), Err(LignumError::NotSupported(_))));
+        let out = String::from_utf8(svg.finish().unwrap()).unwrap();
+
+        assert!(out.contains("width=\"40\" height=\"30\" viewBox=\"0 0 40 30\""));
+        // Resizing reset the transform.
+        assert!(!out.contains("transform"));
     }
 
     struct DummyImage;
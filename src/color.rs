@@ -0,0 +1,374 @@
+//! CSS color parsing shared by the backends and by `CanvasGradient` validation.
+//! Supports hex notation, `rgb()`/`rgba()`, `hsl()`/`hsla()` (legacy comma and
+//! modern space/slash syntax), `transparent`, `currentcolor` and the CSS named colors.
+
+/// A color with straight (non-premultiplied) components in the range `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgba {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Rgba {
+    pub const TRANSPARENT: Rgba = Rgba::new(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Rgba = Rgba::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self { r, g, b, a }
+    }
+
+    fn from_u8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0,
+            a as f64 / 255.0,
+        )
+    }
+
+    /// Formats the color as `#rrggbb`, ignoring alpha.
+    pub fn to_hex(&self) -> String {
+        let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            channel(self.r),
+            channel(self.g),
+            channel(self.b)
+        )
+    }
+}
+
+/// Parses a CSS color string, returning `None` when it is not a valid color.
+/// `currentcolor` resolves to opaque black, as it does for canvas contexts.
+pub fn parse_color(input: &str) -> Option<Rgba> {
+    let c = input.trim().to_ascii_lowercase();
+    if let Some(hex) = c.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some(open) = c.find('(') {
+        let name = c[..open].trim();
+        let args = c[open + 1..].strip_suffix(')')?;
+        return match name {
+            "rgb" | "rgba" => parse_rgb_args(args),
+            "hsl" | "hsla" => parse_hsl_args(args),
+            _ => None,
+        };
+    }
+    match c.as_str() {
+        "transparent" => Some(Rgba::TRANSPARENT),
+        "currentcolor" => Some(Rgba::BLACK),
+        name => named_color(name).map(|[r, g, b]| Rgba::from_u8(r, g, b, 255)),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Rgba> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let nibble = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 => Some(Rgba::from_u8(nibble(0)?, nibble(1)?, nibble(2)?, 255)),
+        4 => Some(Rgba::from_u8(
+            nibble(0)?,
+            nibble(1)?,
+            nibble(2)?,
+            nibble(3)?,
+        )),
+        6 => Some(Rgba::from_u8(byte(0)?, byte(2)?, byte(4)?, 255)),
+        8 => Some(Rgba::from_u8(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+        _ => None,
+    }
+}
+
+/// Splits functional notation arguments into (components, optional alpha), accepting both
+/// `a, b, c[, alpha]` and `a b c[ / alpha]`.
+fn split_args(args: &str) -> Option<(Vec<&str>, Option<&str>)> {
+    if args.contains(',') {
+        let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+        return match parts.len() {
+            3 => Some((parts, None)),
+            4 => Some((parts[..3].to_vec(), Some(parts[3]))),
+            _ => None,
+        };
+    }
+    let (components, alpha) = match args.split_once('/') {
+        Some((components, alpha)) => (components, Some(alpha.trim())),
+        None => (args, None),
+    };
+    let parts: Vec<&str> = components.split_whitespace().collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    Some((parts, alpha))
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    let v: f64 = s.parse().ok()?;
+    v.is_finite().then_some(v)
+}
+
+fn parse_percentage(s: &str) -> Option<f64> {
+    parse_number(s.strip_suffix('%')?).map(|v| v / 100.0)
+}
+
+fn parse_alpha(s: Option<&str>) -> Option<f64> {
+    match s {
+        None => Some(1.0),
+        Some(s) => parse_percentage(s)
+            .or_else(|| parse_number(s))
+            .map(|v| v.clamp(0.0, 1.0)),
+    }
+}
+
+fn parse_rgb_args(args: &str) -> Option<Rgba> {
+    let (parts, alpha) = split_args(args)?;
+    let channel = |s: &str| {
+        parse_percentage(s)
+            .or_else(|| parse_number(s).map(|v| v / 255.0))
+            .map(|v| v.clamp(0.0, 1.0))
+    };
+    Some(Rgba::new(
+        channel(parts[0])?,
+        channel(parts[1])?,
+        channel(parts[2])?,
+        parse_alpha(alpha)?,
+    ))
+}
+
+fn parse_hsl_args(args: &str) -> Option<Rgba> {
+    let (parts, alpha) = split_args(args)?;
+    let hue = parts[0];
+    let degrees = if let Some(v) = hue.strip_suffix("deg") {
+        parse_number(v)?
+    } else if let Some(v) = hue.strip_suffix("grad") {
+        parse_number(v)? * 0.9
+    } else if let Some(v) = hue.strip_suffix("rad") {
+        parse_number(v)?.to_degrees()
+    } else if let Some(v) = hue.strip_suffix("turn") {
+        parse_number(v)? * 360.0
+    } else {
+        parse_number(hue)?
+    };
+    let s = parse_percentage(parts[1])?.clamp(0.0, 1.0);
+    let l = parse_percentage(parts[2])?.clamp(0.0, 1.0);
+    let (r, g, b) = hsl_to_rgb(degrees.rem_euclid(360.0) / 360.0, s, l);
+    Some(Rgba::new(r, g, b, parse_alpha(alpha)?))
+}
+
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> (f64, f64, f64) {
+    let q = if l <= 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+    let hue = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+    (hue(h + 1.0 / 3.0), hue(h), hue(h - 1.0 / 3.0))
+}
+
+fn named_color(name: &str) -> Option<[u8; 3]> {
+    let rgb = match name {
+        "aliceblue" => [240, 248, 255],
+        "antiquewhite" => [250, 235, 215],
+        "aqua" | "cyan" => [0, 255, 255],
+        "aquamarine" => [127, 255, 212],
+        "azure" => [240, 255, 255],
+        "beige" => [245, 245, 220],
+        "bisque" => [255, 228, 196],
+        "black" => [0, 0, 0],
+        "blanchedalmond" => [255, 235, 205],
+        "blue" => [0, 0, 255],
+        "blueviolet" => [138, 43, 226],
+        "brown" => [165, 42, 42],
+        "burlywood" => [222, 184, 135],
+        "cadetblue" => [95, 158, 160],
+        "chartreuse" => [127, 255, 0],
+        "chocolate" => [210, 105, 30],
+        "coral" => [255, 127, 80],
+        "cornflowerblue" => [100, 149, 237],
+        "cornsilk" => [255, 248, 220],
+        "crimson" => [220, 20, 60],
+        "darkblue" => [0, 0, 139],
+        "darkcyan" => [0, 139, 139],
+        "darkgoldenrod" => [184, 134, 11],
+        "darkgray" | "darkgrey" => [169, 169, 169],
+        "darkgreen" => [0, 100, 0],
+        "darkkhaki" => [189, 183, 107],
+        "darkmagenta" => [139, 0, 139],
+        "darkolivegreen" => [85, 107, 47],
+        "darkorange" => [255, 140, 0],
+        "darkorchid" => [153, 50, 204],
+        "darkred" => [139, 0, 0],
+        "darksalmon" => [233, 150, 122],
+        "darkseagreen" => [143, 188, 143],
+        "darkslateblue" => [72, 61, 139],
+        "darkslategray" | "darkslategrey" => [47, 79, 79],
+        "darkturquoise" => [0, 206, 209],
+        "darkviolet" => [148, 0, 211],
+        "deeppink" => [255, 20, 147],
+        "deepskyblue" => [0, 191, 255],
+        "dimgray" | "dimgrey" => [105, 105, 105],
+        "dodgerblue" => [30, 144, 255],
+        "firebrick" => [178, 34, 34],
+        "floralwhite" => [255, 250, 240],
+        "forestgreen" => [34, 139, 34],
+        "fuchsia" | "magenta" => [255, 0, 255],
+        "gainsboro" => [220, 220, 220],
+        "ghostwhite" => [248, 248, 255],
+        "gold" => [255, 215, 0],
+        "goldenrod" => [218, 165, 32],
+        "gray" | "grey" => [128, 128, 128],
+        "green" => [0, 128, 0],
+        "greenyellow" => [173, 255, 47],
+        "honeydew" => [240, 255, 240],
+        "hotpink" => [255, 105, 180],
+        "indianred" => [205, 92, 92],
+        "indigo" => [75, 0, 130],
+        "ivory" => [255, 255, 240],
+        "khaki" => [240, 230, 140],
+        "lavender" => [230, 230, 250],
+        "lavenderblush" => [255, 240, 245],
+        "lawngreen" => [124, 252, 0],
+        "lemonchiffon" => [255, 250, 205],
+        "lightblue" => [173, 216, 230],
+        "lightcoral" => [240, 128, 128],
+        "lightcyan" => [224, 255, 255],
+        "lightgoldenrodyellow" => [250, 250, 210],
+        "lightgray" | "lightgrey" => [211, 211, 211],
+        "lightgreen" => [144, 238, 144],
+        "lightpink" => [255, 182, 193],
+        "lightsalmon" => [255, 160, 122],
+        "lightseagreen" => [32, 178, 170],
+        "lightskyblue" => [135, 206, 250],
+        "lightslategray" | "lightslategrey" => [119, 136, 153],
+        "lightsteelblue" => [176, 196, 222],
+        "lightyellow" => [255, 255, 224],
+        "lime" => [0, 255, 0],
+        "limegreen" => [50, 205, 50],
+        "linen" => [250, 240, 230],
+        "maroon" => [128, 0, 0],
+        "mediumaquamarine" => [102, 205, 170],
+        "mediumblue" => [0, 0, 205],
+        "mediumorchid" => [186, 85, 211],
+        "mediumpurple" => [147, 112, 219],
+        "mediumseagreen" => [60, 179, 113],
+        "mediumslateblue" => [123, 104, 238],
+        "mediumspringgreen" => [0, 250, 154],
+        "mediumturquoise" => [72, 209, 204],
+        "mediumvioletred" => [199, 21, 133],
+        "midnightblue" => [25, 25, 112],
+        "mintcream" => [245, 255, 250],
+        "mistyrose" => [255, 228, 225],
+        "moccasin" => [255, 228, 181],
+        "navajowhite" => [255, 222, 173],
+        "navy" => [0, 0, 128],
+        "oldlace" => [253, 245, 230],
+        "olive" => [128, 128, 0],
+        "olivedrab" => [107, 142, 35],
+        "orange" => [255, 165, 0],
+        "orangered" => [255, 69, 0],
+        "orchid" => [218, 112, 214],
+        "palegoldenrod" => [238, 232, 170],
+        "palegreen" => [152, 251, 152],
+        "paleturquoise" => [175, 238, 238],
+        "palevioletred" => [219, 112, 147],
+        "papayawhip" => [255, 239, 213],
+        "peachpuff" => [255, 218, 185],
+        "peru" => [205, 133, 63],
+        "pink" => [255, 192, 203],
+        "plum" => [221, 160, 221],
+        "powderblue" => [176, 224, 230],
+        "purple" => [128, 0, 128],
+        "rebeccapurple" => [102, 51, 153],
+        "red" => [255, 0, 0],
+        "rosybrown" => [188, 143, 143],
+        "royalblue" => [65, 105, 225],
+        "saddlebrown" => [139, 69, 19],
+        "salmon" => [250, 128, 114],
+        "sandybrown" => [244, 164, 96],
+        "seagreen" => [46, 139, 87],
+        "seashell" => [255, 245, 238],
+        "sienna" => [160, 82, 45],
+        "silver" => [192, 192, 192],
+        "skyblue" => [135, 206, 235],
+        "slateblue" => [106, 90, 205],
+        "slategray" | "slategrey" => [112, 128, 144],
+        "snow" => [255, 250, 250],
+        "springgreen" => [0, 255, 127],
+        "steelblue" => [70, 130, 180],
+        "tan" => [210, 180, 140],
+        "teal" => [0, 128, 128],
+        "thistle" => [216, 191, 216],
+        "tomato" => [255, 99, 71],
+        "turquoise" => [64, 224, 208],
+        "violet" => [238, 130, 238],
+        "wheat" => [245, 222, 179],
+        "white" => [255, 255, 255],
+        "whitesmoke" => [245, 245, 245],
+        "yellow" => [255, 255, 0],
+        "yellowgreen" => [154, 205, 50],
+        _ => return None,
+    };
+    Some(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(input: &str, expected: (f64, f64, f64, f64)) {
+        let c = parse_color(input).unwrap_or_else(|| panic!("failed to parse {input}"));
+        let got = (c.r, c.g, c.b, c.a);
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+        assert!(
+            close(got.0, expected.0)
+                && close(got.1, expected.1)
+                && close(got.2, expected.2)
+                && close(got.3, expected.3),
+            "{input}: {got:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn parses_hex_forms() {
+        assert_color("#f00", (1.0, 0.0, 0.0, 1.0));
+        assert_color("#0f08", (0.0, 1.0, 0.0, 136.0 / 255.0));
+        assert_color("#0000FF", (0.0, 0.0, 1.0, 1.0));
+        assert_color("#00000080", (0.0, 0.0, 0.0, 128.0 / 255.0));
+        assert!(parse_color("#12345").is_none());
+        assert!(parse_color("#ééé").is_none());
+    }
+
+    #[test]
+    fn parses_functional_forms() {
+        assert_color("rgb(255, 0, 0)", (1.0, 0.0, 0.0, 1.0));
+        assert_color("rgba(0,0,255,0.5)", (0.0, 0.0, 1.0, 0.5));
+        assert_color("rgb(0 100% 0 / 25%)", (0.0, 1.0, 0.0, 0.25));
+        assert_color("hsl(120, 100%, 50%)", (0.0, 1.0, 0.0, 1.0));
+        assert_color("hsla(0.5turn 100% 50% / 0.5)", (0.0, 1.0, 1.0, 0.5));
+        assert!(parse_color("rgb(1, 2)").is_none());
+        assert!(parse_color("rgb(a, b, c)").is_none());
+    }
+
+    #[test]
+    fn parses_keywords() {
+        assert_color("Red", (1.0, 0.0, 0.0, 1.0));
+        assert_color("transparent", (0.0, 0.0, 0.0, 0.0));
+        assert_color("currentColor", (0.0, 0.0, 0.0, 1.0));
+        assert!(parse_color("not-a-color").is_none());
+    }
+}
@@ -0,0 +1,256 @@
+//! Applies a Canvas dash pattern (`setLineDash` plus `lineDashOffset`) to a path, for
+//! code that strokes paths itself instead of handing the pattern to a backend library.
+
+use crate::backends::recording::RecordedPath;
+use crate::flatten::{self, Polyline, distance};
+use crate::validate;
+
+/// Splits `path` into its dashes. Curves are flattened to within `tolerance` first.
+/// See [`dash_polylines`] for how the pattern is applied.
+pub fn dash_path(path: &RecordedPath, segments: &[f64], offset: f64, tolerance: f64) -> RecordedPath {
+    let polylines = flatten::flatten(path, tolerance);
+    flatten::to_path(&dash_polylines(&polylines, segments, offset))
+}
+
+/// Splits each polyline into the "on" runs of the dash pattern. The pattern restarts
+/// at `offset` for every subpath and an odd-length list is repeated, as in Canvas.
+/// A list that Canvas would reject, or one that sums to zero, leaves the input solid.
+///
+/// A zero-length dash becomes a two-point polyline at a single spot, so round and square
+/// caps still mark it. On a closed polyline, a dash running through the start point is
+/// kept in one piece, and a polyline that is never interrupted stays closed.
+pub fn dash_polylines(polylines: &[Polyline], segments: &[f64], offset: f64) -> Vec<Polyline> {
+    dashes(polylines, segments, offset).into_iter().map(|dash| dash.polyline).collect()
+}
+
+/// One piece of a dashed path.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Dash {
+    pub(crate) polyline: Polyline,
+    /// The unit direction the path runs in at a zero-length dash, which its points alone
+    /// cannot tell. `None` for longer dashes and for subpaths that never move.
+    pub(crate) direction: Option<(f64, f64)>,
+}
+
+/// Like [`dash_polylines`], but keeps the direction of zero-length dashes so a stroker
+/// can turn their square caps to follow the path.
+pub(crate) fn dashes(polylines: &[Polyline], segments: &[f64], offset: f64) -> Vec<Dash> {
+    let pattern = validate::normalize_line_dash(segments.to_vec());
+    let total: f64 = pattern.iter().sum();
+    if !validate::line_dash(&pattern) || !offset.is_finite() || total <= 0.0 || !total.is_finite() {
+        return polylines
+            .iter()
+            .map(|polyline| Dash {
+                polyline: polyline.clone(),
+                direction: None,
+            })
+            .collect();
+    }
+    let mut out = Vec::new();
+    for polyline in polylines {
+        dash_one(polyline, &pattern, offset.rem_euclid(total), &mut out);
+    }
+    out
+}
+
+/// Ends a dash, noting `direction` if the dash never got anywhere.
+fn push_dash(out: &mut Vec<Dash>, points: Vec<(f64, f64)>, direction: (f64, f64)) {
+    let zero_length = points.iter().all(|&p| p == points[0]);
+    out.push(Dash {
+        polyline: Polyline { points, closed: false },
+        direction: zero_length.then_some(direction),
+    });
+}
+
+fn dash_one(polyline: &Polyline, pattern: &[f64], phase: f64, out: &mut Vec<Dash>) {
+    let Some(&start) = polyline.points.first() else {
+        return;
+    };
+    // Find the dash the phase falls in. Landing exactly on the end of a dash keeps
+    // that dash, so a zero-length dash at the very start is still drawn.
+    let mut index = 0;
+    let mut phase = phase;
+    while phase > pattern[index] {
+        phase -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut remaining = pattern[index] - phase;
+    let starts_in_dash = index % 2 == 0 && remaining > 0.0;
+    let first = out.len();
+    let mut interrupted = false;
+    let mut current = (index % 2 == 0).then(|| vec![start]);
+    let mut direction = (1.0, 0.0);
+
+    for (a, b) in polyline.segments() {
+        let length = distance(a, b);
+        if length > 0.0 {
+            direction = ((b.0 - a.0) / length, (b.1 - a.1) / length);
+        }
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let t = travelled / length;
+            let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            match current.take() {
+                Some(mut points) => {
+                    if points.len() == 1 || points.last() != Some(&p) {
+                        points.push(p);
+                    }
+                    push_dash(out, points, direction);
+                    interrupted = true;
+                }
+                None => current = Some(vec![p]),
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - travelled;
+        if let Some(points) = &mut current {
+            points.push(b);
+        }
+    }
+
+    let Some(points) = current else {
+        // A zero-length dash due exactly at the end of an open subpath is still a dot.
+        let end = polyline.points[polyline.points.len() - 1];
+        if !polyline.closed && remaining == 0.0 && pattern[(index + 1) % pattern.len()] == 0.0 {
+            push_dash(out, vec![end, end], direction);
+        }
+        return;
+    };
+    if polyline.closed && !interrupted {
+        out.push(Dash {
+            polyline: polyline.clone(),
+            direction: None,
+        });
+    } else if polyline.closed && starts_in_dash && first < out.len() {
+        // The last dash runs through the start point into the first one; join them.
+        let mut joined = points;
+        joined.extend_from_slice(&out[first].polyline.points[1..]);
+        out[first].polyline.points = joined;
+    } else {
+        push_dash(out, points, direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::recording::PathCommand;
+
+    fn line(points: &[(f64, f64)], closed: bool) -> Polyline {
+        Polyline {
+            points: points.to_vec(),
+            closed,
+        }
+    }
+
+    fn open(points: &[(f64, f64)]) -> Polyline {
+        line(points, false)
+    }
+
+    #[test]
+    fn odd_pattern_is_repeated() {
+        let dashed = dash_polylines(&[open(&[(0.0, 0.0), (20.0, 0.0)])], &[5.0], 0.0);
+        assert_eq!(
+            dashed,
+            vec![open(&[(0.0, 0.0), (5.0, 0.0)]), open(&[(10.0, 0.0), (15.0, 0.0)])]
+        );
+        let dashed = dash_polylines(&[open(&[(0.0, 0.0), (12.0, 0.0)])], &[1.0, 2.0, 3.0], 0.0);
+        let starts: Vec<f64> = dashed.iter().map(|p| p.points[0].0).collect();
+        // Dashes 1, 3 and 2 long, separated by gaps of 2, 1 and 3.
+        assert_eq!(starts, vec![0.0, 3.0, 7.0]);
+    }
+
+    #[test]
+    fn offset_shifts_the_pattern_and_dashes_cross_vertices() {
+        let dashed = dash_polylines(&[open(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)])], &[3.0, 2.0], 1.0);
+        assert_eq!(
+            dashed,
+            vec![
+                open(&[(0.0, 0.0), (2.0, 0.0)]),
+                open(&[(4.0, 0.0), (4.0, 3.0)]),
+            ]
+        );
+        let negative = dash_polylines(&[open(&[(0.0, 0.0), (10.0, 0.0)])], &[3.0, 2.0], -4.0);
+        assert_eq!(negative, dash_polylines(&[open(&[(0.0, 0.0), (10.0, 0.0)])], &[3.0, 2.0], 1.0));
+    }
+
+    #[test]
+    fn every_subpath_restarts_the_pattern() {
+        let a = open(&[(0.0, 0.0), (3.0, 0.0)]);
+        let b = open(&[(0.0, 5.0), (3.0, 5.0)]);
+        let dashed = dash_polylines(&[a, b], &[2.0, 2.0], 0.0);
+        assert_eq!(
+            dashed,
+            vec![open(&[(0.0, 0.0), (2.0, 0.0)]), open(&[(0.0, 5.0), (2.0, 5.0)])]
+        );
+    }
+
+    #[test]
+    fn zero_length_dashes_become_dots() {
+        let dashed = dash_polylines(&[open(&[(0.0, 0.0), (10.0, 0.0)])], &[0.0, 5.0], 0.0);
+        assert_eq!(
+            dashed,
+            vec![
+                open(&[(0.0, 0.0), (0.0, 0.0)]),
+                open(&[(5.0, 0.0), (5.0, 0.0)]),
+                open(&[(10.0, 0.0), (10.0, 0.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_length_dashes_keep_the_path_direction() {
+        let path = open(&[(0.0, 0.0), (3.0, 4.0), (3.0, 9.0)]);
+        let directions: Vec<_> = dashes(&[path], &[0.0, 5.0], 0.0)
+            .into_iter()
+            .map(|dash| dash.direction)
+            .collect();
+        // The dot at the corner takes the direction of the segment it starts.
+        assert_eq!(directions, vec![Some((0.6, 0.8)), Some((0.0, 1.0)), Some((0.0, 1.0))]);
+        let solid = dashes(&[open(&[(0.0, 0.0), (1.0, 0.0)])], &[2.0, 1.0], 0.0);
+        assert_eq!(solid[0].direction, None);
+    }
+
+    #[test]
+    fn closed_subpaths_join_across_the_start() {
+        let square = line(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)], true);
+        let dashed = dash_polylines(std::slice::from_ref(&square), &[6.0, 4.0], 0.0);
+        // 16 units around: on 0..6, off 6..10, on 10..16 which runs into the first dash.
+        assert_eq!(
+            dashed,
+            vec![open(&[(2.0, 4.0), (0.0, 4.0), (0.0, 0.0), (4.0, 0.0), (4.0, 2.0)])]
+        );
+        assert_eq!(dash_polylines(std::slice::from_ref(&square), &[20.0, 1.0], 0.0), vec![square]);
+    }
+
+    #[test]
+    fn unusable_patterns_leave_the_path_solid() {
+        let input = vec![open(&[(0.0, 0.0), (10.0, 0.0)])];
+        assert_eq!(dash_polylines(&input, &[], 0.0), input);
+        assert_eq!(dash_polylines(&input, &[0.0, 0.0], 0.0), input);
+        assert_eq!(dash_polylines(&input, &[1.0, -1.0], 0.0), input);
+        assert_eq!(dash_polylines(&input, &[1.0], f64::NAN), input);
+    }
+
+    #[test]
+    fn dash_path_flattens_recorded_commands() {
+        let path = RecordedPath::new(vec![PathCommand::Rect {
+            x: 0.0,
+            y: 0.0,
+            w: 4.0,
+            h: 4.0,
+        }]);
+        let dashed = dash_path(&path, &[4.0, 4.0], 0.0, 0.1);
+        assert_eq!(
+            dashed.commands,
+            vec![
+                PathCommand::MoveTo { x: 0.0, y: 0.0 },
+                PathCommand::LineTo { x: 4.0, y: 0.0 },
+                PathCommand::MoveTo { x: 4.0, y: 4.0 },
+                PathCommand::LineTo { x: 0.0, y: 4.0 },
+            ]
+        );
+    }
+}
@@ -4,6 +4,14 @@ pub type Result<T> = std::result::Result<T, LignumError>;
 #[derive(Debug)]
 pub enum LignumError {
     Backend(Box<dyn std::error::Error + Send + Sync>),
+    /// An index or offset argument was outside its allowed range. Mirrors the DOM IndexSizeError.
+    IndexSize(String),
+    /// A string argument could not be parsed. Mirrors the DOM SyntaxError.
+    Syntax(String),
+    /// An argument was not an acceptable value for its type, e.g. a non-finite number. Mirrors TypeError.
+    Type(String),
+    /// The backend cannot perform the requested operation.
+    NotSupported(String),
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
@@ -11,6 +19,10 @@ impl std::fmt::Display for LignumError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LignumError::Backend(_) => write!(f, "Lignum encountered a backend error"),
+            LignumError::IndexSize(msg) => write!(f, "Lignum index size error: {msg}"),
+            LignumError::Syntax(msg) => write!(f, "Lignum syntax error: {msg}"),
+            LignumError::Type(msg) => write!(f, "Lignum type error: {msg}"),
+            LignumError::NotSupported(msg) => write!(f, "Lignum operation not supported: {msg}"),
             LignumError::Other(_) => write!(f, "Lignum encountered an error"),
         }
     }
@@ -20,6 +32,10 @@ impl std::error::Error for LignumError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             LignumError::Backend(err) => Some(err.as_ref()),
+            LignumError::IndexSize(_)
+            | LignumError::Syntax(_)
+            | LignumError::Type(_)
+            | LignumError::NotSupported(_) => None,
             LignumError::Other(err) => Some(err.as_ref()),
         }
     }
@@ -38,6 +54,16 @@ impl From<cairo::Error> for LignumError {
     }
 }
 
+#[cfg(feature = "cairo")]
+impl From<cairo::IoError> for LignumError {
+    fn from(err: cairo::IoError) -> Self {
+        match err {
+            cairo::IoError::Cairo(err) => err.into(),
+            cairo::IoError::Io(err) => err.into(),
+        }
+    }
+}
+
 #[cfg(feature = "svg")]
 impl From<quick_xml::Error> for LignumError {
     fn from(err: quick_xml::Error) -> Self {
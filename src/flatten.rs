@@ -0,0 +1,554 @@
+//! Flattens a [`RecordedPath`] into polylines for code that dashes or strokes paths itself.
+//! Follows the Canvas path rules for implicit subpaths, `arc`/`arcTo`/`ellipse` sweeps,
+//! `rect`/`roundRect` and `closePath`.
+
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::api::FillRule;
+use crate::backends::recording::{PathCommand, RecordedPath};
+
+/// Caps the number of pieces a single curve or arc is split into.
+const MAX_STEPS: f64 = 4096.0;
+
+/// One flattened subpath. Repeated points are kept, so a zero-length `lineTo` still
+/// produces two points and can be told apart from a lone `moveTo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<(f64, f64)>,
+    pub closed: bool,
+}
+
+impl Polyline {
+    /// Iterates the line segments, including the closing segment of a closed polyline.
+    pub fn segments(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        let closing = match (self.closed, self.points.first(), self.points.last()) {
+            (true, Some(&first), Some(&last)) => Some((last, first)),
+            _ => None,
+        };
+        self.points.windows(2).map(|w| (w[0], w[1])).chain(closing)
+    }
+
+    /// Total length of the segments.
+    pub fn length(&self) -> f64 {
+        self.segments().map(|(a, b)| distance(a, b)).sum()
+    }
+}
+
+/// Flattens `path` so no curve strays more than `tolerance` from its polyline.
+/// Subpaths holding a single point are dropped, as Canvas never paints them.
+pub fn flatten(path: &RecordedPath, tolerance: f64) -> Vec<Polyline> {
+    let tolerance = if tolerance > 0.0 { tolerance } else { 0.1 };
+    let mut f = Flattener {
+        tolerance,
+        done: Vec::new(),
+        current: None,
+    };
+    for cmd in &path.commands {
+        f.command(cmd);
+    }
+    f.finish_subpath();
+    f.done
+}
+
+/// Turns polylines back into `moveTo`/`lineTo`/`closePath` commands.
+pub fn to_path(polylines: &[Polyline]) -> RecordedPath {
+    let mut commands = Vec::new();
+    for polyline in polylines {
+        let Some((&(x, y), rest)) = polyline.points.split_first() else {
+            continue;
+        };
+        commands.push(PathCommand::MoveTo { x, y });
+        commands.extend(rest.iter().map(|&(x, y)| PathCommand::LineTo { x, y }));
+        if polyline.closed {
+            commands.push(PathCommand::ClosePath);
+        }
+    }
+    RecordedPath::new(commands)
+}
+
+/// Tests whether `point` is inside the polylines under `rule`. Like a Canvas fill, every
+/// polyline counts as closed.
+pub fn contains(polylines: &[Polyline], point: (f64, f64), rule: FillRule) -> bool {
+    let (x, y) = point;
+    let mut winding = 0i32;
+    for polyline in polylines {
+        let points = &polyline.points;
+        for (i, &a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            let side = (b.0 - a.0) * (y - a.1) - (x - a.0) * (b.1 - a.1);
+            if a.1 <= y && b.1 > y && side > 0.0 {
+                winding += 1;
+            } else if a.1 > y && b.1 <= y && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    match rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+pub(crate) fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// The signed sweep Canvas uses for `arc` and `ellipse`: a full turn once the angles are
+/// a turn or more apart in the drawing direction, otherwise the angle reduced into one turn.
+fn arc_sweep(start_angle: f64, end_angle: f64, ccw: bool) -> f64 {
+    if !ccw && end_angle - start_angle >= TAU {
+        TAU
+    } else if ccw && start_angle - end_angle >= TAU {
+        -TAU
+    } else {
+        let sweep = (end_angle - start_angle).rem_euclid(TAU);
+        if ccw && sweep > 0.0 { sweep - TAU } else { sweep }
+    }
+}
+
+/// Number of pieces that keep a curve within `tolerance`, given a bound computed for it.
+fn steps(bound: f64) -> usize {
+    if bound.is_finite() {
+        bound.ceil().clamp(1.0, MAX_STEPS) as usize
+    } else {
+        1
+    }
+}
+
+struct Flattener {
+    tolerance: f64,
+    done: Vec<Polyline>,
+    current: Option<Polyline>,
+}
+
+impl Flattener {
+    fn command(&mut self, cmd: &PathCommand) {
+        match *cmd {
+            PathCommand::MoveTo { x, y } => self.move_to((x, y)),
+            PathCommand::LineTo { x, y } => self.line_to((x, y)),
+            PathCommand::QuadraticCurveTo { cpx, cpy, x, y } => self.quadratic((cpx, cpy), (x, y)),
+            PathCommand::BezierCurveTo {
+                cp1x,
+                cp1y,
+                cp2x,
+                cp2y,
+                x,
+                y,
+            } => self.cubic((cp1x, cp1y), (cp2x, cp2y), (x, y)),
+            PathCommand::Arc {
+                x,
+                y,
+                radius,
+                start_angle,
+                end_angle,
+                ccw,
+            } => self.ellipse((x, y), radius, radius, 0.0, start_angle, arc_sweep(start_angle, end_angle, ccw)),
+            PathCommand::Ellipse {
+                x,
+                y,
+                radius_x,
+                radius_y,
+                rotation,
+                start_angle,
+                end_angle,
+                ccw,
+            } => self.ellipse(
+                (x, y),
+                radius_x,
+                radius_y,
+                rotation,
+                start_angle,
+                arc_sweep(start_angle, end_angle, ccw),
+            ),
+            PathCommand::ArcTo { x1, y1, x2, y2, radius } => self.arc_to((x1, y1), (x2, y2), radius),
+            PathCommand::Rect { x, y, w, h } => {
+                self.move_to((x, y));
+                self.line_to((x + w, y));
+                self.line_to((x + w, y + h));
+                self.line_to((x, y + h));
+                self.close();
+            }
+            PathCommand::RoundRect { x, y, w, h, radii } => self.round_rect(x, y, w, h, radii),
+            PathCommand::ClosePath => self.close(),
+        }
+    }
+
+    fn current_point(&self) -> Option<(f64, f64)> {
+        self.current.as_ref().and_then(|c| c.points.last().copied())
+    }
+
+    fn finish_subpath(&mut self) {
+        if let Some(polyline) = self.current.take()
+            && polyline.points.len() > 1
+        {
+            self.done.push(polyline);
+        }
+    }
+
+    fn move_to(&mut self, p: (f64, f64)) {
+        self.finish_subpath();
+        self.current = Some(Polyline {
+            points: vec![p],
+            closed: false,
+        });
+    }
+
+    fn line_to(&mut self, p: (f64, f64)) {
+        match &mut self.current {
+            Some(polyline) => polyline.points.push(p),
+            None => self.move_to(p),
+        }
+    }
+
+    /// Adds a point generated along a curve, skipping exact repeats of the last point.
+    fn curve_point(&mut self, p: (f64, f64)) {
+        if self.current_point() != Some(p) {
+            self.line_to(p);
+        }
+    }
+
+    /// Closing marks the subpath closed and starts a new one at the same first point.
+    fn close(&mut self) {
+        let Some(mut polyline) = self.current.take() else {
+            return;
+        };
+        let start = polyline.points[0];
+        polyline.closed = true;
+        self.current = Some(polyline);
+        self.finish_subpath();
+        self.move_to(start);
+    }
+
+    fn quadratic(&mut self, c: (f64, f64), p: (f64, f64)) {
+        let p0 = self.current_point().unwrap_or(c);
+        if self.current.is_none() {
+            self.move_to(c);
+        }
+        let dd = distance((0.0, 0.0), (p0.0 - 2.0 * c.0 + p.0, p0.1 - 2.0 * c.1 + p.1));
+        let n = steps((dd / (4.0 * self.tolerance)).sqrt());
+        for i in 1..=n {
+            let t = i as f64 / n as f64;
+            let mt = 1.0 - t;
+            self.line_to((
+                mt * mt * p0.0 + 2.0 * mt * t * c.0 + t * t * p.0,
+                mt * mt * p0.1 + 2.0 * mt * t * c.1 + t * t * p.1,
+            ));
+        }
+    }
+
+    fn cubic(&mut self, c1: (f64, f64), c2: (f64, f64), p: (f64, f64)) {
+        let p0 = self.current_point().unwrap_or(c1);
+        if self.current.is_none() {
+            self.move_to(c1);
+        }
+        let dd1 = distance((0.0, 0.0), (p0.0 - 2.0 * c1.0 + c2.0, p0.1 - 2.0 * c1.1 + c2.1));
+        let dd2 = distance((0.0, 0.0), (c1.0 - 2.0 * c2.0 + p.0, c1.1 - 2.0 * c2.1 + p.1));
+        let n = steps((0.75 * dd1.max(dd2) / self.tolerance).sqrt());
+        for i in 1..=n {
+            let t = i as f64 / n as f64;
+            let mt = 1.0 - t;
+            let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+            self.line_to((
+                a * p0.0 + b * c1.0 + c * c2.0 + d * p.0,
+                a * p0.1 + b * c1.1 + c * c2.1 + d * p.1,
+            ));
+        }
+    }
+
+    /// Connects the current point to the start of the arc (or starts a subpath there),
+    /// then follows the arc for `sweep` radians.
+    fn ellipse(&mut self, center: (f64, f64), rx: f64, ry: f64, rotation: f64, start: f64, sweep: f64) {
+        let (sin_r, cos_r) = rotation.sin_cos();
+        let point = |angle: f64| {
+            let (sin_a, cos_a) = angle.sin_cos();
+            (
+                center.0 + rx * cos_a * cos_r - ry * sin_a * sin_r,
+                center.1 + rx * cos_a * sin_r + ry * sin_a * cos_r,
+            )
+        };
+        self.line_to(point(start));
+        let radius = rx.max(ry);
+        let n = if radius > self.tolerance {
+            let step = 2.0 * (1.0 - self.tolerance / radius).acos();
+            steps(sweep.abs() / step)
+        } else {
+            steps(sweep.abs() / FRAC_PI_2)
+        };
+        for i in 1..=n {
+            self.curve_point(point(start + sweep * i as f64 / n as f64));
+        }
+    }
+
+    fn arc_to(&mut self, p1: (f64, f64), p2: (f64, f64), radius: f64) {
+        let Some(p0) = self.current_point() else {
+            self.move_to(p1);
+            return;
+        };
+        let v1 = (p0.0 - p1.0, p0.1 - p1.1);
+        let v2 = (p2.0 - p1.0, p2.1 - p1.1);
+        let cross = v1.0 * v2.1 - v1.1 * v2.0;
+        let (len1, len2) = (distance(p0, p1), distance(p1, p2));
+        if radius == 0.0 || len1 == 0.0 || len2 == 0.0 || cross == 0.0 {
+            self.line_to(p1);
+            return;
+        }
+        let (u1, u2) = ((v1.0 / len1, v1.1 / len1), (v2.0 / len2, v2.1 / len2));
+        let angle = (u1.0 * u2.0 + u1.1 * u2.1).clamp(-1.0, 1.0).acos();
+        let tangent = radius / (angle / 2.0).tan();
+        let t1 = (p1.0 + u1.0 * tangent, p1.1 + u1.1 * tangent);
+        let t2 = (p1.0 + u2.0 * tangent, p1.1 + u2.1 * tangent);
+        let bisector = (u1.0 + u2.0, u1.1 + u2.1);
+        let bisector_len = distance((0.0, 0.0), bisector);
+        let center_dist = radius / (angle / 2.0).sin();
+        let center = (
+            p1.0 + bisector.0 / bisector_len * center_dist,
+            p1.1 + bisector.1 / bisector_len * center_dist,
+        );
+        let start = (t1.1 - center.1).atan2(t1.0 - center.0);
+        let end = (t2.1 - center.1).atan2(t2.0 - center.0);
+        let sweep = (end - start + PI).rem_euclid(TAU) - PI;
+        self.ellipse(center, radius, radius, 0.0, start, sweep);
+    }
+
+    fn round_rect(&mut self, x: f64, y: f64, w: f64, h: f64, radii: [f64; 4]) {
+        let corners = round_rect_corners(x, y, w, h, radii);
+        self.move_to(corners[3].point(corners[3].to));
+        for corner in &corners {
+            let r = corner.radius;
+            self.ellipse(corner.center, r, r, 0.0, corner.start_angle(), corner.sweep());
+        }
+        self.close();
+        self.move_to((x, y));
+    }
+}
+
+/// One rounded corner of a `roundRect`, a quarter circle.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RoundRectCorner {
+    pub(crate) center: (f64, f64),
+    pub(crate) radius: f64,
+    /// Unit vector from the center to where the arc starts.
+    pub(crate) from: (f64, f64),
+    /// Unit vector from the center to where the arc ends.
+    pub(crate) to: (f64, f64),
+    /// Whether the arc runs toward increasing angles, clockwise on a y-down surface.
+    pub(crate) clockwise: bool,
+}
+
+impl RoundRectCorner {
+    /// The point on the arc in the direction of the unit vector `d`, e.g. `self.to`.
+    pub(crate) fn point(&self, d: (f64, f64)) -> (f64, f64) {
+        (self.center.0 + self.radius * d.0, self.center.1 + self.radius * d.1)
+    }
+
+    pub(crate) fn start_angle(&self) -> f64 {
+        self.from.1.atan2(self.from.0)
+    }
+
+    pub(crate) fn sweep(&self) -> f64 {
+        if self.clockwise { FRAC_PI_2 } else { -FRAC_PI_2 }
+    }
+}
+
+/// Lays out a `roundRect` call as Canvas traces it: the corners in drawing order, starting
+/// with the one at `x + w`, `y`, joined by straight sides. The subpath starts where the
+/// last corner ends, on the side leaving (`x`, `y`) toward `x + w`.
+///
+/// A negative width or height mirrors the rectangle, which swaps the radii between the
+/// corners it exchanges as Canvas does, and reverses the winding when exactly one of them is negative. Radii that would overlap
+/// along a side are all scaled down by the same factor.
+pub(crate) fn round_rect_corners(x: f64, y: f64, w: f64, h: f64, radii: [f64; 4]) -> [RoundRectCorner; 4] {
+    let [upper_left, upper_right, lower_right, lower_left] = radii;
+    let (width, height) = (w.abs(), h.abs());
+    let fit = |side: f64, a: f64, b: f64| if a + b > side { side / (a + b) } else { 1.0 };
+    let scale = fit(width, upper_left, upper_right)
+        .min(fit(width, lower_left, lower_right))
+        .min(fit(height, upper_left, lower_left))
+        .min(fit(height, upper_right, lower_right));
+
+    // Trace in a frame whose axes point along w and h, then mirror into place.
+    let (sx, sy) = (if w < 0.0 { -1.0 } else { 1.0 }, if h < 0.0 { -1.0 } else { 1.0 });
+    let corner = |radius: f64, (u, v): (f64, f64), from: (f64, f64), to: (f64, f64)| {
+        let radius = radius * scale;
+        // The center sits one radius in from the rectangle's corner along both sides.
+        let (u, v) = (u - radius * (from.0 + to.0), v - radius * (from.1 + to.1));
+        RoundRectCorner {
+            center: (x + sx * u, y + sy * v),
+            radius,
+            from: (sx * from.0, sy * from.1),
+            to: (sx * to.0, sy * to.1),
+            clockwise: sx * sy > 0.0,
+        }
+    };
+    [
+        corner(upper_right, (width, 0.0), (0.0, -1.0), (1.0, 0.0)),
+        corner(lower_right, (width, height), (1.0, 0.0), (0.0, 1.0)),
+        corner(lower_left, (0.0, height), (0.0, 1.0), (-1.0, 0.0)),
+        corner(upper_left, (0.0, 0.0), (-1.0, 0.0), (0.0, -1.0)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close_to(a: (f64, f64), b: (f64, f64)) -> bool {
+        distance(a, b) < 1e-9
+    }
+
+    #[test]
+    fn rect_is_a_closed_square_and_lone_points_are_dropped() {
+        let path = RecordedPath::new(vec![
+            PathCommand::MoveTo { x: 50.0, y: 50.0 },
+            PathCommand::Rect {
+                x: 0.0,
+                y: 0.0,
+                w: 10.0,
+                h: 5.0,
+            },
+        ]);
+        let polylines = flatten(&path, 0.1);
+        assert_eq!(
+            polylines,
+            vec![Polyline {
+                points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 5.0), (0.0, 5.0)],
+                closed: true,
+            }]
+        );
+        assert_eq!(polylines[0].length(), 30.0);
+        assert_eq!(to_path(&polylines).commands.last(), Some(&PathCommand::ClosePath));
+    }
+
+    #[test]
+    fn contains_applies_the_fill_rule() {
+        let square = |x: f64, y: f64, size: f64| Polyline {
+            points: vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)],
+            closed: false,
+        };
+        let nested = [square(0.0, 0.0, 10.0), square(2.0, 2.0, 6.0)];
+        assert!(contains(&nested, (5.0, 5.0), FillRule::NonZero));
+        assert!(!contains(&nested, (5.0, 5.0), FillRule::EvenOdd));
+        assert!(contains(&nested, (1.0, 5.0), FillRule::EvenOdd));
+        assert!(!contains(&nested, (11.0, 5.0), FillRule::NonZero));
+    }
+
+    #[test]
+    fn zero_length_line_keeps_both_points() {
+        let path = RecordedPath::new(vec![
+            PathCommand::MoveTo { x: 3.0, y: 4.0 },
+            PathCommand::LineTo { x: 3.0, y: 4.0 },
+        ]);
+        assert_eq!(flatten(&path, 0.1)[0].points, vec![(3.0, 4.0), (3.0, 4.0)]);
+    }
+
+    #[test]
+    fn arc_follows_canvas_sweep_rules() {
+        assert_eq!(arc_sweep(0.0, 3.0 * PI, false), TAU);
+        assert_eq!(arc_sweep(0.0, -3.0 * PI, true), -TAU);
+        assert!((arc_sweep(0.0, -FRAC_PI_2, false) - 1.5 * PI).abs() < 1e-12);
+        assert!((arc_sweep(0.0, FRAC_PI_2, true) + 1.5 * PI).abs() < 1e-12);
+        assert_eq!(arc_sweep(1.0, 1.0, true), 0.0);
+
+        let path = RecordedPath::new(vec![
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::Arc {
+                x: 10.0,
+                y: 0.0,
+                radius: 5.0,
+                start_angle: PI,
+                end_angle: TAU,
+                ccw: false,
+            },
+        ]);
+        let polyline = &flatten(&path, 0.01)[0];
+        assert!(close_to(polyline.points[1], (5.0, 0.0)));
+        assert!(close_to(*polyline.points.last().unwrap(), (15.0, 0.0)));
+        // Clockwise on screen from angle PI to TAU goes through the top of the circle.
+        assert!(polyline.points.iter().all(|p| p.1 <= 1e-9));
+        assert!((polyline.length() - (5.0 + 5.0 * PI)).abs() < 0.05);
+    }
+
+    #[test]
+    fn arc_to_meets_both_tangents() {
+        let path = RecordedPath::new(vec![
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::ArcTo {
+                x1: 10.0,
+                y1: 0.0,
+                x2: 10.0,
+                y2: 10.0,
+                radius: 4.0,
+            },
+        ]);
+        let polyline = &flatten(&path, 0.01)[0];
+        assert!(close_to(polyline.points[1], (6.0, 0.0)));
+        assert!(close_to(*polyline.points.last().unwrap(), (10.0, 4.0)));
+        assert!((polyline.length() - (6.0 + 2.0 * PI)).abs() < 0.05);
+    }
+
+    #[test]
+    fn curves_stay_within_tolerance() {
+        let path = RecordedPath::new(vec![
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::QuadraticCurveTo {
+                cpx: 10.0,
+                cpy: 20.0,
+                x: 20.0,
+                y: 0.0,
+            },
+        ]);
+        let polyline = &flatten(&path, 0.05)[0];
+        for &(x, y) in &polyline.points {
+            // The curve is y = 2x - x^2 / 10, so every point lies on it exactly.
+            assert!((y - (2.0 * x - x * x / 10.0)).abs() < 1e-9);
+        }
+        for (a, b) in polyline.segments() {
+            let mid_x = (a.0 + b.0) / 2.0;
+            assert!(((a.1 + b.1) / 2.0 - (2.0 * mid_x - mid_x * mid_x / 10.0)).abs() <= 0.05);
+        }
+    }
+
+    #[test]
+    fn round_rect_scales_overlapping_radii() {
+        let path = RecordedPath::new(vec![PathCommand::RoundRect {
+            x: 10.0,
+            y: 0.0,
+            w: -10.0,
+            h: 10.0,
+            radii: [10.0; 4],
+        }]);
+        let polyline = &flatten(&path, 0.001)[0];
+        assert!(polyline.closed);
+        assert!((polyline.length() - 5.0 * TAU).abs() < 0.01);
+    }
+
+    #[test]
+    fn round_rect_winding_follows_the_signs_of_its_size() {
+        let with_round_rect = |x: f64, y: f64, w: f64, h: f64| {
+            let path = RecordedPath::new(vec![
+                PathCommand::Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    w: 10.0,
+                    h: 10.0,
+                },
+                PathCommand::RoundRect {
+                    x,
+                    y,
+                    w,
+                    h,
+                    radii: [1.0; 4],
+                },
+            ]);
+            flatten(&path, 0.01)
+        };
+        // Exactly one negative side runs the outline backwards, cutting a non-zero hole.
+        let mirrored = with_round_rect(8.0, 2.0, -6.0, 6.0);
+        assert!(!contains(&mirrored, (5.0, 5.0), FillRule::NonZero));
+        assert!(contains(&mirrored, (1.0, 1.0), FillRule::NonZero));
+        assert!(close_to(mirrored[1].points[0], (7.0, 2.0)));
+        assert!(!contains(&with_round_rect(2.0, 8.0, 6.0, -6.0), (5.0, 5.0), FillRule::NonZero));
+        assert!(contains(&with_round_rect(8.0, 8.0, -6.0, -6.0), (5.0, 5.0), FillRule::NonZero));
+        assert!(contains(&with_round_rect(2.0, 2.0, 6.0, 6.0), (5.0, 5.0), FillRule::NonZero));
+    }
+}
@@ -3,5 +3,10 @@
 //! (software rasterizer, OpenGL, WebGPU, etc.).
 
 pub mod api;
+pub mod color;
 pub mod error;
 pub mod backends;
+pub mod dash;
+pub mod flatten;
+pub mod stroker;
+mod validate;
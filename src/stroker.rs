@@ -0,0 +1,404 @@
+//! Converts a stroke into an outline path that fills to the same area, for hit testing and
+//! for output formats that can only fill. The outline follows the Canvas stroking rules:
+//! a rectangle per segment plus the joins and caps, each wound the same way so a non-zero
+//! fill paints their union.
+
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::api::{LineCap, LineJoin};
+use crate::backends::recording::{PathCommand, RecordedPath, Snapshot};
+use crate::dash;
+use crate::flatten::{self, Polyline};
+use crate::validate;
+
+/// The line settings that shape a stroke.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub line_width: f64,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub miter_limit: f64,
+    pub line_dash: Vec<f64>,
+    pub line_dash_offset: f64,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.0,
+            line_dash: Vec::new(),
+            line_dash_offset: 0.0,
+        }
+    }
+}
+
+impl From<&Snapshot> for StrokeStyle {
+    fn from(state: &Snapshot) -> Self {
+        Self {
+            line_width: state.line_width,
+            line_cap: state.line_cap.clone(),
+            line_join: state.line_join.clone(),
+            miter_limit: state.miter_limit,
+            line_dash: state.line_dash.clone(),
+            line_dash_offset: state.line_dash_offset,
+        }
+    }
+}
+
+/// Returns the outline of `path` stroked with `style`, to be filled with the non-zero rule.
+/// Curves are flattened to within `tolerance` before stroking; round joins and caps are
+/// emitted as arcs. A line width that is not positive and finite strokes nothing.
+///
+/// Zero-length segments are skipped, but a subpath that never leaves its first point
+/// (such as `moveTo(x, y); lineTo(x, y)`) still gets a round dot or an axis-aligned square
+/// for round and square caps.
+pub fn stroke_path(path: &RecordedPath, style: &StrokeStyle, tolerance: f64) -> RecordedPath {
+    if !validate::positive(style.line_width) {
+        return RecordedPath::new(Vec::new());
+    }
+    let polylines = flatten::flatten(path, tolerance);
+    let dashes = dash::dashes(&polylines, &style.line_dash, style.line_dash_offset);
+    let mut stroker = Stroker {
+        style,
+        half: style.line_width / 2.0,
+        commands: Vec::new(),
+    };
+    for dash in &dashes {
+        stroker.polyline(&dash.polyline, dash.direction);
+    }
+    RecordedPath::new(stroker.commands)
+}
+
+type Point = (f64, f64);
+
+fn unit(from: Point, to: Point) -> Point {
+    let length = flatten::distance(from, to);
+    ((to.0 - from.0) / length, (to.1 - from.1) / length)
+}
+
+/// The unit normal to the left of `d` in a y-up frame.
+fn normal(d: Point) -> Point {
+    (-d.1, d.0)
+}
+
+fn offset(p: Point, d: Point, by: f64) -> Point {
+    (p.0 + d.0 * by, p.1 + d.1 * by)
+}
+
+struct Stroker<'a> {
+    style: &'a StrokeStyle,
+    half: f64,
+    commands: Vec<PathCommand>,
+}
+
+impl Stroker<'_> {
+    /// Strokes one subpath. `direction` orients the square cap of a zero-length one.
+    fn polyline(&mut self, polyline: &Polyline, direction: Option<Point>) {
+        let mut points: Vec<Point> = Vec::with_capacity(polyline.points.len());
+        for &p in &polyline.points {
+            if points.last() != Some(&p) {
+                points.push(p);
+            }
+        }
+        if polyline.closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        let n = points.len();
+        if n == 1 {
+            if !polyline.closed {
+                self.dot(points[0], direction);
+            }
+            return;
+        }
+
+        for pair in points.windows(2) {
+            self.segment(pair[0], pair[1]);
+        }
+        for i in 1..n - 1 {
+            self.join(points[i - 1], points[i], points[i + 1]);
+        }
+        if polyline.closed {
+            self.segment(points[n - 1], points[0]);
+            self.join(points[n - 2], points[n - 1], points[0]);
+            self.join(points[n - 1], points[0], points[1]);
+        } else {
+            self.cap(points[0], unit(points[1], points[0]));
+            self.cap(points[n - 1], unit(points[n - 2], points[n - 1]));
+        }
+    }
+
+    /// Adds a closed polygon, reversed if needed so every piece winds the same way.
+    fn polygon(&mut self, mut points: Vec<Point>) {
+        let area: f64 = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+            .sum();
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+        if area < 0.0 {
+            points.reverse();
+        }
+        let (x, y) = points[0];
+        self.commands.push(PathCommand::MoveTo { x, y });
+        self.commands
+            .extend(points[1..].iter().map(|&(x, y)| PathCommand::LineTo { x, y }));
+        self.commands.push(PathCommand::ClosePath);
+    }
+
+    /// Adds a pie slice around `center` sweeping clockwise on screen (increasing angle),
+    /// which winds the same way as the polygons. The slice starts at the center unless
+    /// the sweep is a full turn.
+    fn sector(&mut self, center: Point, start_angle: f64, sweep: f64) {
+        let (x, y) = if sweep >= TAU {
+            offset(center, (start_angle.cos(), start_angle.sin()), self.half)
+        } else {
+            center
+        };
+        self.commands.push(PathCommand::MoveTo { x, y });
+        self.commands.push(PathCommand::Arc {
+            x: center.0,
+            y: center.1,
+            radius: self.half,
+            start_angle,
+            end_angle: start_angle + sweep,
+            ccw: false,
+        });
+        self.commands.push(PathCommand::ClosePath);
+    }
+
+    fn segment(&mut self, a: Point, b: Point) {
+        let n = normal(unit(a, b));
+        let h = self.half;
+        self.polygon(vec![offset(a, n, h), offset(b, n, h), offset(b, n, -h), offset(a, n, -h)]);
+    }
+
+    /// Caps the end at `p`, where `d` points away from the line.
+    fn cap(&mut self, p: Point, d: Point) {
+        let n = normal(d);
+        let h = self.half;
+        match self.style.line_cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let ahead = offset(p, d, h);
+                self.polygon(vec![offset(p, n, h), offset(ahead, n, h), offset(ahead, n, -h), offset(p, n, -h)]);
+            }
+            LineCap::Round => self.sector(p, d.1.atan2(d.0) - FRAC_PI_2, PI),
+        }
+    }
+
+    /// Marks a subpath that has no length. A square cap follows `direction`, the way the
+    /// path was heading at a zero-length dash; a subpath that never moves has none, so
+    /// its square is aligned with the axes.
+    fn dot(&mut self, p: Point, direction: Option<Point>) {
+        let h = self.half;
+        match self.style.line_cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let d = direction.unwrap_or((1.0, 0.0));
+                let n = normal(d);
+                let (ahead, behind) = (offset(p, d, h), offset(p, d, -h));
+                self.polygon(vec![offset(behind, n, h), offset(ahead, n, h), offset(ahead, n, -h), offset(behind, n, -h)]);
+            }
+            LineCap::Round => self.sector(p, 0.0, TAU),
+        }
+    }
+
+    /// Fills the gap on the outside of the corner at `v`.
+    fn join(&mut self, prev: Point, v: Point, next: Point) {
+        let d1 = unit(prev, v);
+        let d2 = unit(v, next);
+        let cross = d1.0 * d2.1 - d1.1 * d2.0;
+        let dot = d1.0 * d2.0 + d1.1 * d2.1;
+        if cross == 0.0 {
+            // Straight on needs nothing. Turning straight back has no outside corner;
+            // only a round join draws anything, as a half circle ahead of the turn.
+            if dot < 0.0 && self.style.line_join == LineJoin::Round {
+                self.cap(v, d1);
+            }
+            return;
+        }
+        // The outside of the corner is on the side the path turns away from.
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let n1 = normal(d1);
+        let n2 = normal(d2);
+        let (n1, n2) = ((n1.0 * side, n1.1 * side), (n2.0 * side, n2.1 * side));
+        let h = self.half;
+        let (c1, c2) = (offset(v, n1, h), offset(v, n2, h));
+        match self.style.line_join {
+            LineJoin::Bevel => self.polygon(vec![v, c1, c2]),
+            LineJoin::Miter => {
+                // Miter length over half the line width is 1 / sin(theta / 2), where theta
+                // is the angle between the two segments.
+                let ratio = 1.0 / ((1.0 + dot) / 2.0).sqrt();
+                if ratio <= self.style.miter_limit {
+                    let bisector = (n1.0 + n2.0, n1.1 + n2.1);
+                    let length = flatten::distance((0.0, 0.0), bisector);
+                    let tip = offset(v, (bisector.0 / length, bisector.1 / length), h * ratio);
+                    self.polygon(vec![v, c1, tip, c2]);
+                } else {
+                    self.polygon(vec![v, c1, c2]);
+                }
+            }
+            LineJoin::Round => {
+                let a1 = n1.1.atan2(n1.0);
+                let a2 = n2.1.atan2(n2.0);
+                let sweep = (a2 - a1).rem_euclid(TAU);
+                if sweep <= PI {
+                    self.sector(v, a1, sweep);
+                } else {
+                    self.sector(v, a2, TAU - sweep);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::FillRule;
+
+    fn stroked(commands: Vec<PathCommand>, style: &StrokeStyle) -> Vec<Polyline> {
+        let outline = stroke_path(&RecordedPath::new(commands), style, 0.01);
+        flatten::flatten(&outline, 0.01)
+    }
+
+    fn inside(outline: &[Polyline], x: f64, y: f64) -> bool {
+        flatten::contains(outline, (x, y), FillRule::NonZero)
+    }
+
+    fn style(line_width: f64, line_cap: LineCap, line_join: LineJoin) -> StrokeStyle {
+        StrokeStyle {
+            line_width,
+            line_cap,
+            line_join,
+            ..StrokeStyle::default()
+        }
+    }
+
+    fn line(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<PathCommand> {
+        vec![PathCommand::MoveTo { x: x0, y: y0 }, PathCommand::LineTo { x: x1, y: y1 }]
+    }
+
+    fn corner() -> Vec<PathCommand> {
+        vec![
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::LineTo { x: 10.0, y: 0.0 },
+            PathCommand::LineTo { x: 10.0, y: 10.0 },
+        ]
+    }
+
+    #[test]
+    fn caps_extend_the_line_as_canvas_does() {
+        let butt = stroked(line(0.0, 0.0, 10.0, 0.0), &style(4.0, LineCap::Butt, LineJoin::Miter));
+        assert!(inside(&butt, 5.0, 1.9) && inside(&butt, 0.1, -1.9));
+        assert!(!inside(&butt, 5.0, 2.1) && !inside(&butt, -0.1, 0.0) && !inside(&butt, 10.1, 0.0));
+
+        let square = stroked(line(0.0, 0.0, 10.0, 0.0), &style(4.0, LineCap::Square, LineJoin::Miter));
+        assert!(inside(&square, -1.9, 1.9) && inside(&square, 11.9, -1.9));
+        assert!(!inside(&square, -2.1, 0.0) && !inside(&square, 12.1, 0.0));
+
+        let round = stroked(line(0.0, 0.0, 10.0, 0.0), &style(4.0, LineCap::Round, LineJoin::Miter));
+        assert!(inside(&round, -1.9, 0.0) && inside(&round, 11.9, 0.0));
+        assert!(!inside(&round, -1.9, 1.9) && !inside(&round, 11.5, 1.5));
+    }
+
+    #[test]
+    fn joins_fill_the_outside_corner() {
+        let miter = stroked(corner(), &style(4.0, LineCap::Butt, LineJoin::Miter));
+        assert!(inside(&miter, 11.9, -1.9));
+        let bevel = stroked(corner(), &style(4.0, LineCap::Butt, LineJoin::Bevel));
+        assert!(inside(&bevel, 10.9, -0.9) && !inside(&bevel, 11.9, -1.9));
+        let round = stroked(corner(), &style(4.0, LineCap::Butt, LineJoin::Round));
+        assert!(inside(&round, 11.3, -1.3) && !inside(&round, 11.9, -1.9));
+        // The inside of the corner is covered by the segments alone.
+        for outline in [&miter, &bevel, &round] {
+            assert!(inside(outline, 8.5, 1.5) && !inside(outline, 7.0, 3.0));
+        }
+    }
+
+    #[test]
+    fn miter_limit_falls_back_to_bevel() {
+        let sharp = vec![
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::LineTo { x: 20.0, y: 0.0 },
+            PathCommand::LineTo { x: 0.0, y: 2.0 },
+        ];
+        // The miter reaches about 20 half-widths past the corner, to near (40, -1).
+        let mut limited = style(2.0, LineCap::Butt, LineJoin::Miter);
+        limited.miter_limit = 30.0;
+        assert!(inside(&stroked(sharp.clone(), &limited), 35.0, -0.75));
+        limited.miter_limit = 10.0;
+        assert!(!inside(&stroked(sharp, &limited), 35.0, -0.75));
+    }
+
+    #[test]
+    fn closed_subpaths_join_at_the_start_instead_of_capping() {
+        let rect = vec![PathCommand::Rect {
+            x: 0.0,
+            y: 0.0,
+            w: 10.0,
+            h: 10.0,
+        }];
+        let outline = stroked(rect.clone(), &style(2.0, LineCap::Round, LineJoin::Miter));
+        assert!(inside(&outline, -0.9, -0.9) && inside(&outline, 10.9, 10.9));
+        assert!(!inside(&outline, 5.0, 5.0) && !inside(&outline, 1.5, 1.5));
+        let bevel = stroked(rect, &style(2.0, LineCap::Round, LineJoin::Bevel));
+        assert!(!inside(&bevel, -0.9, -0.9));
+    }
+
+    #[test]
+    fn zero_length_subpaths_draw_only_round_and_square_caps() {
+        let dot = line(5.0, 5.0, 5.0, 5.0);
+        let round = stroked(dot.clone(), &style(4.0, LineCap::Round, LineJoin::Miter));
+        assert!(inside(&round, 5.0, 5.0) && inside(&round, 6.9, 5.0) && !inside(&round, 6.5, 6.5));
+        let square = stroked(dot.clone(), &style(4.0, LineCap::Square, LineJoin::Miter));
+        assert!(inside(&square, 6.9, 6.9) && !inside(&square, 7.1, 5.0));
+        assert!(stroked(dot, &style(4.0, LineCap::Butt, LineJoin::Miter)).is_empty());
+        // A lone moveTo is not a subpath with a line, so it paints nothing.
+        let lone = vec![PathCommand::MoveTo { x: 5.0, y: 5.0 }];
+        assert!(stroked(lone, &style(4.0, LineCap::Round, LineJoin::Miter)).is_empty());
+    }
+
+    #[test]
+    fn dashes_are_stroked_separately() {
+        let mut dashed = style(2.0, LineCap::Butt, LineJoin::Miter);
+        dashed.line_dash = vec![2.0];
+        dashed.line_dash_offset = 1.0;
+        let outline = stroked(line(0.0, 0.0, 10.0, 0.0), &dashed);
+        assert!(inside(&outline, 0.5, 0.0) && !inside(&outline, 2.0, 0.0) && inside(&outline, 4.0, 0.0));
+
+        dashed.line_dash = vec![0.0, 5.0];
+        dashed.line_cap = LineCap::Round;
+        let dots = stroked(line(0.0, 0.0, 10.0, 0.0), &dashed);
+        assert!(inside(&dots, 4.5, 0.0) && inside(&dots, 9.5, 0.0) && !inside(&dots, 7.0, 0.0));
+    }
+
+    #[test]
+    fn square_caps_on_zero_length_dashes_follow_the_path() {
+        let mut dashed = style(4.0, LineCap::Square, LineJoin::Miter);
+        dashed.line_dash = vec![0.0, 10.0];
+        let diagonal = stroked(line(0.0, 0.0, 20.0, 20.0), &dashed);
+        // The square at (0, 0) is turned 45 degrees: its corners point along the axes,
+        // 2.8 out, while its sides cross the diagonals only 2 out.
+        assert!(inside(&diagonal, 0.0, 2.7) && inside(&diagonal, 1.3, 1.3));
+        assert!(!inside(&diagonal, 1.7, 1.7) && !inside(&diagonal, 1.9, -1.9));
+    }
+
+    #[test]
+    fn invalid_line_width_strokes_nothing() {
+        for width in [0.0, -1.0, f64::NAN] {
+            let outline = stroke_path(
+                &RecordedPath::new(line(0.0, 0.0, 10.0, 0.0)),
+                &style(width, LineCap::Round, LineJoin::Round),
+                0.1,
+            );
+            assert!(outline.commands.is_empty());
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Argument rules from the Canvas spec that every backend applies the same way. Path,
+//! rectangle, transform and drawing calls with a NaN or infinite argument do nothing, and
+//! style setters ignore out-of-range values rather than failing.
+
+use crate::error::{LignumError, Result};
+
+/// Whether a call with these arguments takes effect; any NaN or infinity makes it a no-op.
+pub(crate) fn finite(values: &[f64]) -> bool {
+    values.iter().all(|v| v.is_finite())
+}
+
+/// Accepted `lineWidth` and `miterLimit` values: finite and greater than zero.
+pub(crate) fn positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+/// Accepted `shadowBlur` values: finite and not negative.
+pub(crate) fn non_negative(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+/// Accepted `globalAlpha` values: within [0, 1].
+pub(crate) fn unit_interval(value: f64) -> bool {
+    value.is_finite() && (0.0..=1.0).contains(&value)
+}
+
+/// `setLineDash` ignores the whole list when any segment is negative or not finite.
+pub(crate) fn line_dash(segments: &[f64]) -> bool {
+    segments.iter().all(|&v| non_negative(v))
+}
+
+/// `setLineDash` repeats an odd-length list, so `[5]` is stored and drawn as `[5, 5]`.
+pub(crate) fn normalize_line_dash(mut segments: Vec<f64>) -> Vec<f64> {
+    if !segments.len().is_multiple_of(2) {
+        segments.extend_from_within(..);
+    }
+    segments
+}
+
+/// Text is not drawn when a `maxWidth` is given that is zero, negative or not finite.
+pub(crate) fn max_width(max_width: Option<f64>) -> bool {
+    max_width.is_none_or(positive)
+}
+
+/// Rejects negative radii for `arc`, `arcTo`, `ellipse` and radial gradients.
+pub(crate) fn radii(values: &[f64]) -> Result<()> {
+    match values.iter().find(|&&r| r < 0.0) {
+        Some(r) => Err(LignumError::IndexSize(format!("radius {r} is negative"))),
+        None => Ok(()),
+    }
+}
+
+/// Gradient constructors take plain doubles, so NaN and infinities are a type error rather
+/// than a silent no-op.
+pub(crate) fn gradient_args(values: &[f64]) -> Result<()> {
+    if finite(values) {
+        Ok(())
+    } else {
+        Err(LignumError::Type("gradient arguments must be finite".into()))
+    }
+}
+
+/// Expands `roundRect` radii to [top-left, top-right, bottom-right, bottom-left]. Returns
+/// `None` when a radius is not finite, which makes the call a no-op, and `IndexSize` for
+/// an empty list, more than four radii or a negative one.
+pub(crate) fn round_rect_radii(radii: &[f64]) -> Result<Option<[f64; 4]>> {
+    if radii.is_empty() || radii.len() > 4 {
+        return Err(LignumError::IndexSize(format!(
+            "roundRect takes 1 to 4 radii, got {}",
+            radii.len()
+        )));
+    }
+    if !finite(radii) {
+        return Ok(None);
+    }
+    self::radii(radii)?;
+    Ok(Some(match *radii {
+        [r] => [r; 4],
+        [a, b] => [a, b, a, b],
+        [a, b, c] => [a, b, c, b],
+        [a, b, c, d] => [a, b, c, d],
+        _ => unreachable!("length checked above"),
+    }))
+}
+
+/// Applies invalid arguments to `canvas` and checks the state is untouched and the
+/// errors are the ones Canvas throws. Shared by every backend's tests.
+#[cfg(test)]
+pub(crate) fn assert_ignores_invalid_arguments<C: crate::api::CanvasRenderingContext2D>(canvas: &mut C) {
+    use crate::api::Matrix2D;
+
+    canvas.set_line_width(3.0).unwrap();
+    canvas.set_line_dash(vec![1.0, 2.0]).unwrap();
+    canvas.translate(1.0, 2.0).unwrap();
+    for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        canvas.set_line_width(bad).unwrap();
+        canvas.set_miter_limit(bad).unwrap();
+        canvas.set_global_alpha(bad).unwrap();
+        canvas.set_shadow_blur(bad).unwrap();
+        canvas.set_shadow_offset_x(bad).unwrap();
+        canvas.set_shadow_offset_y(bad).unwrap();
+        canvas.set_line_dash_offset(bad).unwrap();
+        canvas.set_line_dash(vec![1.0, bad]).unwrap();
+        canvas.scale(bad, 1.0).unwrap();
+        canvas.rotate(bad).unwrap();
+        canvas.translate(0.0, bad).unwrap();
+        canvas.transform(1.0, 0.0, 0.0, 1.0, bad, 0.0).unwrap();
+        canvas.set_transform(bad, 0.0, 0.0, 1.0, 0.0, 0.0).unwrap();
+        canvas.move_to(bad, 0.0).unwrap();
+        canvas.line_to(0.0, bad).unwrap();
+        canvas.rect(0.0, 0.0, bad, 1.0).unwrap();
+        canvas.arc(0.0, 0.0, bad, 0.0, 1.0, false).unwrap();
+        canvas.round_rect(0.0, 0.0, 1.0, 1.0, &[bad]).unwrap();
+        canvas.fill_rect(bad, 0.0, 1.0, 1.0).unwrap();
+        canvas.fill_text("x", 0.0, 0.0, Some(bad)).unwrap();
+        assert!(matches!(canvas.create_linear_gradient(bad, 0.0, 1.0, 1.0), Err(LignumError::Type(_))));
+    }
+    canvas.set_line_width(0.0).unwrap();
+    canvas.set_line_width(-1.0).unwrap();
+    canvas.set_miter_limit(0.0).unwrap();
+    canvas.set_global_alpha(1.5).unwrap();
+    canvas.set_global_alpha(-0.5).unwrap();
+    canvas.set_shadow_blur(-1.0).unwrap();
+    canvas.set_line_dash(vec![-1.0]).unwrap();
+    canvas.fill_text("x", 0.0, 0.0, Some(0.0)).unwrap();
+
+    assert_eq!(canvas.line_width().unwrap(), 3.0);
+    assert_eq!(canvas.miter_limit().unwrap(), 10.0);
+    assert_eq!(canvas.global_alpha().unwrap(), 1.0);
+    assert_eq!(canvas.shadow_blur().unwrap(), 0.0);
+    assert_eq!(canvas.shadow_offset_x().unwrap(), 0.0);
+    assert_eq!(canvas.shadow_offset_y().unwrap(), 0.0);
+    assert_eq!(canvas.line_dash_offset().unwrap(), 0.0);
+    assert_eq!(canvas.line_dash().unwrap(), vec![1.0, 2.0]);
+    assert_eq!(canvas.get_transform().unwrap(), Matrix2D::new(1.0, 0.0, 0.0, 1.0, 1.0, 2.0));
+
+    assert!(matches!(canvas.arc(0.0, 0.0, -1.0, 0.0, 1.0, false), Err(LignumError::IndexSize(_))));
+    assert!(matches!(canvas.arc_to(0.0, 0.0, 1.0, 1.0, -1.0), Err(LignumError::IndexSize(_))));
+    assert!(matches!(
+        canvas.ellipse(0.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, false),
+        Err(LignumError::IndexSize(_))
+    ));
+    assert!(matches!(canvas.round_rect(0.0, 0.0, 1.0, 1.0, &[1.0; 5]), Err(LignumError::IndexSize(_))));
+    assert!(matches!(
+        canvas.create_radial_gradient(0.0, 0.0, -1.0, 0.0, 0.0, 1.0),
+        Err(LignumError::IndexSize(_))
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setters_accept_only_spec_ranges() {
+        assert!(positive(0.5) && !positive(0.0) && !positive(-1.0) && !positive(f64::INFINITY));
+        assert!(non_negative(0.0) && !non_negative(-0.5) && !non_negative(f64::NAN));
+        assert!(unit_interval(0.0) && unit_interval(1.0) && !unit_interval(1.5) && !unit_interval(f64::NAN));
+        assert!(line_dash(&[]) && line_dash(&[0.0, 2.0]) && !line_dash(&[1.0, -1.0]) && !line_dash(&[f64::NAN]));
+        assert!(max_width(None) && max_width(Some(3.0)) && !max_width(Some(0.0)) && !max_width(Some(f64::NAN)));
+    }
+
+    #[test]
+    fn odd_line_dash_lists_are_repeated() {
+        assert_eq!(normalize_line_dash(vec![]), Vec::<f64>::new());
+        assert_eq!(normalize_line_dash(vec![5.0]), vec![5.0, 5.0]);
+        assert_eq!(normalize_line_dash(vec![1.0, 2.0]), vec![1.0, 2.0]);
+        assert_eq!(normalize_line_dash(vec![1.0, 2.0, 3.0]), vec![1.0, 2.0, 3.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn round_rect_radii_expand_like_css() {
+        assert!(matches!(round_rect_radii(&[]), Err(LignumError::IndexSize(_))));
+        assert_eq!(round_rect_radii(&[1.0, 2.0]).unwrap(), Some([1.0, 2.0, 1.0, 2.0]));
+        assert_eq!(round_rect_radii(&[1.0, 2.0, 3.0]).unwrap(), Some([1.0, 2.0, 3.0, 2.0]));
+        assert_eq!(round_rect_radii(&[f64::NAN]).unwrap(), None);
+        assert!(matches!(round_rect_radii(&[-1.0]), Err(LignumError::IndexSize(_))));
+        assert!(matches!(round_rect_radii(&[1.0; 5]), Err(LignumError::IndexSize(_))));
+    }
+}